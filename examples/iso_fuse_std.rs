@@ -6,35 +6,58 @@ use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
 
 use fuser::{ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, Request};
-use libc::{EISDIR, ENOTDIR};
+use libc::{EINVAL, EISDIR, ENOTDIR};
 use std::time::Duration;
 
 use iso9660::{DirectoryEntry, ISODirectory, ISOFileReader, ISO9660};
 
 fn entry_to_filetype(entry: &DirectoryEntry<File>) -> fuser::FileType {
+    // Rock Ridge records device nodes, FIFOs and sockets as files with the
+    // type in their mode
+    if let Some(attributes) = entry.rock_ridge().and_then(|rr| rr.attributes.as_ref()) {
+        match attributes.mode & libc::S_IFMT {
+            libc::S_IFCHR => return fuser::FileType::CharDevice,
+            libc::S_IFBLK => return fuser::FileType::BlockDevice,
+            libc::S_IFIFO => return fuser::FileType::NamedPipe,
+            libc::S_IFSOCK => return fuser::FileType::Socket,
+            _ => {}
+        }
+    }
+
     match entry {
         DirectoryEntry::File(_) => fuser::FileType::RegularFile,
         DirectoryEntry::Directory(_) => fuser::FileType::Directory,
+        DirectoryEntry::Symlink(_) => fuser::FileType::Symlink,
     }
 }
 
 fn get_fileattr(ino: u64, entry: &DirectoryEntry<File>) -> fuser::FileAttr {
-    let blocks = (entry.header().extent_length + 2048 - 1) / 2048; // ceil(len / 2048
+    let blocks = entry.header().extent_length.div_ceil(2048);
     let time = entry.header().time.into();
+    let size = match entry {
+        DirectoryEntry::File(file) => file.size(),
+        DirectoryEntry::Symlink(link) => link.target.len() as u64,
+        DirectoryEntry::Directory(_) => entry.header().extent_length as u64,
+    };
+    let rock_ridge = entry.rock_ridge();
+    let attributes = rock_ridge.and_then(|rr| rr.attributes.as_ref());
+    let rdev = rock_ridge
+        .and_then(|rr| rr.device)
+        .map_or(0, |dev| libc::makedev(dev.major(), dev.minor()) as u32);
     fuser::FileAttr {
         ino,
-        size: entry.header().extent_length as u64,
+        size,
         blocks: blocks as u64,
         atime: time,
         mtime: time,
         ctime: time,
         crtime: time,
-        kind: entry_to_filetype(&entry),
-        perm: 0444,
-        nlink: 1,
-        uid: 0,
-        gid: 0,
-        rdev: 0,
+        kind: entry_to_filetype(entry),
+        perm: attributes.map_or(0o444, |attributes| (attributes.mode & 0o7777) as u16),
+        nlink: attributes.map_or(1, |attributes| attributes.links),
+        uid: attributes.map_or(0, |attributes| attributes.uid),
+        gid: attributes.map_or(0, |attributes| attributes.gid),
+        rdev,
         flags: 0,
         blksize: 512,
     }
@@ -101,6 +124,15 @@ impl fuser::Filesystem for ISOFuse {
         reply.attr(&Duration::from_secs(0), &fileattr);
     }
 
+    fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+        let entry = self.inodes.get(&ino).unwrap();
+        if let DirectoryEntry::Symlink(link) = entry {
+            reply.data(link.target.as_bytes());
+        } else {
+            reply.error(EINVAL);
+        }
+    }
+
     fn open(&mut self, _req: &Request, ino: u64, _flags: i32, reply: ReplyOpen) {
         let entry = self.inodes.get(&ino).unwrap();
         if let DirectoryEntry::File(file) = entry {
@@ -180,6 +212,17 @@ impl fuser::Filesystem for ISOFuse {
                 .read_entry_at(&mut block, &mut block_num, offset)
                 .unwrap();
 
+            // Relocated directories are reached through their "CL" entry
+            if entry.rock_ridge().is_some_and(|rr| rr.relocated) {
+                match next_offset {
+                    Some(next_offset) => {
+                        offset = next_offset;
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+
             let kind = entry_to_filetype(&entry);
             if reply.add(
                 self.inode_number,
@@ -1,37 +1,50 @@
 // SPDX-License-Identifier: (MIT OR Apache-2.0)
 extern crate iso9660;
 
+#[cfg(not(feature = "std"))]
 use embedded_io::Read;
 use std::fs::File;
-use std::io::{self, Read as _, Seek as _, Write as _};
+#[cfg(feature = "std")]
+use std::io::Read;
+use std::io::{self, Write as _};
+#[cfg(not(feature = "std"))]
+use std::io::{Read as _, Seek as _};
 use std::{env, process};
 
 use iso9660::{DirectoryEntry, ISO9660};
 
+#[cfg(not(feature = "std"))]
 #[derive(Debug)]
 struct MyError(std::io::Error);
+#[cfg(not(feature = "std"))]
 impl core::error::Error for MyError {}
+#[cfg(not(feature = "std"))]
 impl embedded_io::Error for MyError {
     fn kind(&self) -> embedded_io::ErrorKind {
         embedded_io::ErrorKind::Other
     }
 }
+#[cfg(not(feature = "std"))]
 impl core::fmt::Display for MyError {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:?}", self.0)
     }
 }
 
+#[cfg(not(feature = "std"))]
 struct MyFile(File);
+#[cfg(not(feature = "std"))]
 impl embedded_io::ErrorType for MyFile {
     type Error = MyError;
 }
 
+#[cfg(not(feature = "std"))]
 impl embedded_io::Read for MyFile {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         self.0.read(buf).map_err(MyError)
     }
 }
+#[cfg(not(feature = "std"))]
 impl embedded_io::Seek for MyFile {
     fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Self::Error> {
         let seek = match pos {
@@ -43,7 +56,6 @@ impl embedded_io::Seek for MyFile {
     }
 }
 
-#[cfg(not(feature = "std"))]
 fn main() {
     let args = env::args();
 
@@ -56,6 +68,10 @@ fn main() {
     let file_path = env::args().nth(2).unwrap();
 
     let file = File::open(iso_path).unwrap();
+    // With the std feature, a `File` is read directly
+    #[cfg(feature = "std")]
+    let fs = ISO9660::new(file).unwrap();
+    #[cfg(not(feature = "std"))]
     let fs = ISO9660::new(MyFile(file)).unwrap();
 
     match fs.open(&file_path).unwrap() {
@@ -4,9 +4,8 @@
 
 extern crate iso9660;
 
-use embedded_io::Read;
 use std::fs::File;
-use std::io::{self, Read as _, Seek as _, Write as _};
+use std::io::{self, Read as _, Write as _};
 use std::{env, process};
 
 use iso9660::{DirectoryEntry, ISO9660};
@@ -0,0 +1,54 @@
+//! Example code for using the 'tokio' feature flag: prints a file, or
+//! lists a directory, of an image read with tokio.
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+extern crate iso9660;
+
+use std::io::{self, Write as _};
+use std::{env, process};
+
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
+
+use iso9660::{AsyncDirectoryEntry, AsyncISO9660, TokioReader};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let args = env::args();
+
+    if args.len() != 3 {
+        eprintln!("Requires 2 arguments.");
+        process::exit(1);
+    }
+
+    let iso_path = env::args().nth(1).unwrap();
+    let file_path = env::args().nth(2).unwrap();
+
+    let file = File::open(iso_path).await.unwrap();
+    let mut fs = AsyncISO9660::new(TokioReader(file)).await.unwrap();
+
+    match fs.open(&file_path).await.unwrap() {
+        Some(AsyncDirectoryEntry::File(file)) => {
+            let mut reader = fs.read(&file);
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf.len() as u64, file.size());
+
+            // Reading again from the start gives the same contents
+            reader.seek(SeekFrom::Start(0)).await.unwrap();
+            let mut again = Vec::new();
+            reader.read_to_end(&mut again).await.unwrap();
+            assert_eq!(buf, again);
+
+            io::stdout().write_all(&buf).unwrap();
+        }
+        Some(AsyncDirectoryEntry::Directory(dir)) => {
+            let mut entries = fs.contents(&dir);
+            while let Some(entry) = entries.next().await {
+                println!("{}", entry.unwrap().identifier());
+            }
+        }
+        Some(_) => panic!("{} is not a file.", file_path),
+        None => panic!("'{}' not found", file_path),
+    }
+}
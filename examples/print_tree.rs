@@ -3,35 +3,44 @@
 extern crate iso9660;
 
 use std::fs::File;
+#[cfg(not(feature = "std"))]
 use std::io::{self, Read as _, Seek as _};
 use std::{env, process};
 
 use iso9660::{DirectoryEntry, ISO9660Reader, ISODirectory, ISO9660};
 
+#[cfg(not(feature = "std"))]
 #[derive(Debug)]
 struct MyError(std::io::Error);
+#[cfg(not(feature = "std"))]
 impl core::error::Error for MyError {}
+#[cfg(not(feature = "std"))]
 impl embedded_io::Error for MyError {
     fn kind(&self) -> embedded_io::ErrorKind {
         embedded_io::ErrorKind::Other
     }
 }
+#[cfg(not(feature = "std"))]
 impl core::fmt::Display for MyError {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:?}", self.0)
     }
 }
 
+#[cfg(not(feature = "std"))]
 struct MyFile(File);
+#[cfg(not(feature = "std"))]
 impl embedded_io::ErrorType for MyFile {
     type Error = MyError;
 }
 
+#[cfg(not(feature = "std"))]
 impl embedded_io::Read for MyFile {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         self.0.read(buf).map_err(MyError)
     }
 }
+#[cfg(not(feature = "std"))]
 impl embedded_io::Seek for MyFile {
     fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Self::Error> {
         let seek = match pos {
@@ -56,6 +65,10 @@ fn main() {
     let dirpath = args.next();
 
     let file = File::open(path).unwrap();
+    // With the std feature, a `File` is read directly
+    #[cfg(feature = "std")]
+    let fs = ISO9660::new(file).unwrap();
+    #[cfg(not(feature = "std"))]
     let fs = ISO9660::new(MyFile(file)).unwrap();
 
     if let Some(dirpath) = dirpath {
@@ -63,7 +76,7 @@ fn main() {
             Some(DirectoryEntry::Directory(dir)) => {
                 print_tree(&dir, 0);
             }
-            Some(DirectoryEntry::File(_)) => {
+            Some(_) => {
                 eprintln!("'{}' is not a directory", dirpath);
                 process::exit(1);
             }
@@ -96,6 +109,12 @@ fn print_tree<T: ISO9660Reader>(dir: &ISODirectory<T>, level: u32) {
                 }
                 println!("- {}", file.identifier);
             }
+            DirectoryEntry::Symlink(link) => {
+                for _i in 0..level {
+                    print!("  ");
+                }
+                println!("- {} -> {}", link.identifier, link.target);
+            }
         }
     }
 }
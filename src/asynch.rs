@@ -0,0 +1,666 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+//! Async API, reading the image with `embedded-io-async`.
+//!
+//! Parsing is done by the synchronous code, with a `Prefetched` reader
+//! which only has the data read by the async reader. When an operation
+//! (opening the image, reading an entry of a directory, or reading a file)
+//! needs data it doesn't have, it fails, the data is read, and the
+//! operation is run again. The sectors read are cached, so later operations
+//! don't read them again. The start of the volume descriptor set and whole
+//! directories are read ahead, so operations on them rarely run twice.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::max;
+use core::convert::TryInto;
+use core::fmt;
+use core::future::{poll_fn, Future};
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+
+use embedded_io_async::{ErrorType, Read, Seek, SeekFrom};
+use futures_core::Stream;
+use time::OffsetDateTime;
+
+use crate::cache::SectorCache;
+use crate::directory_entry::ISODirectoryIterator;
+use crate::parse::DirectoryEntryHeader;
+use crate::shared::Shared;
+use crate::{
+    BothEndianMismatch, CacheStats, DirectoryEntry, Extension, Extent, Hierarchy, ISO9660Options,
+    ISO9660Reader, ISODirectory, ISOError, ISOFile, ISOFileReader, ISOSymlink,
+    PrimaryVolumeDescriptor, RockRidge, SystemUseEntries, VolumeDescriptorSetEntry, VolumeInfo,
+    ISO9660,
+};
+
+#[allow(async_fn_in_trait)]
+pub trait AsyncISO9660Reader: ErrorType {
+    /// Read the 2048 byte sector(s) at a given LBA (logical block address)
+    async fn read_at(&mut self, buf: &mut [u8], lba: u64) -> Result<usize, Self::Error>;
+
+    /// See `ISO9660Reader::max_transfer_size`
+    fn max_transfer_size(&self) -> usize {
+        usize::MAX
+    }
+}
+
+impl<T: Read + Seek> AsyncISO9660Reader for T {
+    async fn read_at(&mut self, buf: &mut [u8], lba: u64) -> Result<usize, Self::Error> {
+        self.seek(SeekFrom::Start(lba * SECTOR_SIZE as u64)).await?;
+        let mut count = 0;
+        while count < buf.len() {
+            match self.read(&mut buf[count..]).await? {
+                0 => break,
+                read => count += read,
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// Size of the sectors addressed by `AsyncISO9660Reader` and the
+/// `Prefetched` reader, whatever the logical block size of the volume
+pub(crate) const SECTOR_SIZE: usize = 2048;
+
+/// Sectors kept between operations unless `ISO9660Options::cache` is set,
+/// see `AsyncISO9660::with_options`
+const CACHE_SECTORS: usize = 64;
+
+/// Sectors read ahead from the start of the volume descriptor set, which
+/// has few descriptors
+const DESCRIPTOR_SECTORS: usize = 16;
+
+/// Data read by the async reader
+pub(crate) struct Staging {
+    /// Sectors read, kept between operations
+    cache: SectorCache,
+    /// LBA and length of each read of the operation in progress, with the
+    /// data read
+    reads: Vec<(u64, usize, Vec<u8>)>,
+    /// First read the last attempt of the operation needed, but wasn't done
+    missing: Option<(u64, usize)>,
+}
+
+impl Staging {
+    fn new(capacity: usize) -> Staging {
+        Staging {
+            cache: SectorCache::new(capacity),
+            reads: Vec::new(),
+            missing: None,
+        }
+    }
+
+    /// Read of `len` bytes at `lba` the last attempt needed, if any
+    pub(crate) fn take_missing(&mut self) -> Option<(u64, usize)> {
+        self.missing.take()
+    }
+
+    /// Keep `data` read at `lba` for the rest of the operation, and its
+    /// whole sectors in the cache if they fit
+    pub(crate) fn add(&mut self, lba: u64, len: usize, data: Vec<u8>) {
+        self.cache_sectors(lba, &data);
+        self.reads.push((lba, len, data));
+    }
+
+    /// Cache the whole sectors of `data` read at `lba`, if they fit
+    fn cache_sectors(&mut self, lba: u64, data: &[u8]) {
+        let sectors = data.chunks_exact(SECTOR_SIZE);
+        if sectors.len() <= self.cache.stats().capacity {
+            for (sector, chunk) in (lba..).zip(sectors) {
+                self.cache.insert(sector, chunk.try_into().unwrap());
+            }
+        }
+    }
+
+    /// End the operation in progress
+    pub(crate) fn finish(&mut self) {
+        self.reads.clear();
+        self.missing = None;
+    }
+
+    /// Read `buf` at `lba` from the reads of the operation or the cache
+    fn read_at(&mut self, buf: &mut [u8], lba: u64) -> Option<usize> {
+        let read = self
+            .reads
+            .iter()
+            .find(|&&(read_lba, len, _)| read_lba == lba && len >= buf.len());
+        if let Some((_, _, data)) = read {
+            let count = buf.len().min(data.len());
+            buf[..count].copy_from_slice(&data[..count]);
+            return Some(count);
+        }
+
+        for (sector, chunk) in (lba..).zip(buf.chunks_mut(SECTOR_SIZE)) {
+            let data = self.cache.get(sector)?;
+            chunk.copy_from_slice(&data[..chunk.len()]);
+        }
+        Some(buf.len())
+    }
+}
+
+/// Error reading data of a `Prefetched` reader which wasn't read by the
+/// async reader
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct NotRead;
+
+impl fmt::Display for NotRead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "data not read by the async reader")
+    }
+}
+
+impl core::error::Error for NotRead {}
+
+#[cfg(not(feature = "std"))]
+impl embedded_io::Error for NotRead {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        embedded_io::ErrorKind::Other
+    }
+}
+
+/// Reader of the image parsed by an `AsyncISO9660`, with the data read by
+/// the async reader
+#[derive(Clone)]
+pub(crate) struct Prefetched {
+    staging: Shared<Staging>,
+    max_transfer_size: usize,
+}
+
+#[cfg(not(feature = "std"))]
+impl embedded_io::ErrorType for Prefetched {
+    type Error = NotRead;
+}
+
+impl ISO9660Reader for Prefetched {
+    fn read_at(&mut self, buf: &mut [u8], lba: u64) -> Result<usize, ReaderError!(Self)> {
+        let mut staging = self.staging.lock();
+        if let Some(count) = staging.read_at(buf, lba) {
+            return Ok(count);
+        }
+
+        staging.missing.get_or_insert((lba, buf.len()));
+        #[cfg(not(feature = "std"))]
+        return Err(NotRead);
+        #[cfg(feature = "std")]
+        return Err(std::io::Error::other(NotRead));
+    }
+
+    fn max_transfer_size(&self) -> usize {
+        self.max_transfer_size
+    }
+}
+
+/// Run `op` until it succeeds or fails with all the data it needs, reading
+/// the data it lacks with `reader` in between
+async fn fetch<T: AsyncISO9660Reader, R>(
+    reader: &mut T,
+    staging: &Shared<Staging>,
+    mut op: impl FnMut() -> Result<R, ISOError<ReaderError!(Prefetched)>>,
+) -> Result<R, ISOError<T::Error>> {
+    let result = loop {
+        let result = op();
+        let Some((lba, len)) = staging.lock().take_missing() else {
+            // Reads only fail for data to fetch, which there isn't
+            break result.map_err(|err| {
+                err.map_io(|_| ISOError::Unsupported("read without data to fetch"))
+            });
+        };
+
+        let mut data = vec![0; len];
+        match reader.read_at(&mut data, lba).await {
+            Ok(count) => {
+                data.truncate(count);
+                staging.lock().add(lba, len, data);
+            }
+            Err(err) => break Err(ISOError::Io(err)),
+        }
+    };
+    staging.lock().finish();
+    result
+}
+
+/// Cache the `count` sectors at `lba`, as many as the cache holds, unless
+/// the first one already is. Operations reading a structure whose extent is
+/// known, such as a directory, then don't each run again for its sectors.
+async fn prefetch<T: AsyncISO9660Reader>(
+    reader: &mut T,
+    staging: &Shared<Staging>,
+    lba: u64,
+    count: usize,
+) -> Result<(), ISOError<T::Error>> {
+    let count = {
+        let staging = staging.lock();
+        if staging.cache.contains(lba) {
+            return Ok(());
+        }
+        count.min(staging.cache.stats().capacity)
+    };
+
+    let mut data = vec![0; count * SECTOR_SIZE];
+    let max_size = max(reader.max_transfer_size() / SECTOR_SIZE, 1) * SECTOR_SIZE;
+    let mut read = 0;
+    for chunk in data.chunks_mut(max_size) {
+        let sector = lba + (read / SECTOR_SIZE) as u64;
+        let count = reader.read_at(chunk, sector).await.map_err(ISOError::Io)?;
+        read += count;
+        if count < chunk.len() {
+            break;
+        }
+    }
+    staging.lock().cache_sectors(lba, &data[..read]);
+    Ok(())
+}
+
+/// Sectors holding the extent of `directory`, as the first one and the
+/// count
+fn extent_sectors(directory: &ISODirectory<Prefetched>, block_size: u16) -> (u64, usize) {
+    let start = directory.header.extent_loc as u64 * block_size as u64;
+    let end = start + directory.header.extent_length as u64;
+    let lba = start / SECTOR_SIZE as u64;
+    (lba, (end.div_ceil(SECTOR_SIZE as u64) - lba) as usize)
+}
+
+/// An image read with an `AsyncISO9660Reader`
+pub struct AsyncISO9660<T: AsyncISO9660Reader> {
+    fs: ISO9660<Prefetched>,
+    reader: T,
+    staging: Shared<Staging>,
+}
+
+impl<T: AsyncISO9660Reader> AsyncISO9660<T> {
+    /// Open an image, with the default options, see `ISO9660::new`
+    pub async fn new(reader: T) -> Result<AsyncISO9660<T>, ISOError<T::Error>> {
+        AsyncISO9660::with_options(reader, &ISO9660Options::default()).await
+    }
+
+    /// Open an image with `options`, see `ISO9660::with_options`. The
+    /// sectors read are cached for later operations: `ISO9660Options::cache`
+    /// sets how many, 64 by default. Lenient mode isn't supported, and
+    /// fails with `ISOError::Unsupported`.
+    pub async fn with_options(
+        mut reader: T,
+        options: &ISO9660Options,
+    ) -> Result<AsyncISO9660<T>, ISOError<T::Error>> {
+        if options.lenient {
+            return Err(ISOError::Unsupported("lenient mode with the async API"));
+        }
+        let capacity = match options.cache {
+            0 => CACHE_SECTORS,
+            sectors => sectors,
+        };
+        // The sectors are cached before the parser reads them
+        let mut options = options.clone();
+        options.cache(0);
+
+        let staging = Shared::new(Staging::new(capacity));
+        prefetch(&mut reader, &staging, 16, DESCRIPTOR_SECTORS).await?;
+        let max_transfer_size = reader.max_transfer_size();
+        let fs = fetch(&mut reader, &staging, || {
+            let prefetched = Prefetched {
+                staging: staging.clone(),
+                max_transfer_size,
+            };
+            ISO9660::with_options(prefetched, &options)
+        })
+        .await?;
+        Ok(AsyncISO9660 {
+            fs,
+            reader,
+            staging,
+        })
+    }
+
+    /// The root directory, see `ISO9660::root`
+    pub fn root(&self) -> AsyncISODirectory {
+        AsyncISODirectory(self.fs.root.clone())
+    }
+
+    /// See `ISO9660::hierarchy`
+    pub fn hierarchy(&self) -> Hierarchy {
+        self.fs.hierarchy()
+    }
+
+    /// See `ISO9660::block_size`
+    pub fn block_size(&self) -> u16 {
+        self.fs.block_size()
+    }
+
+    /// See `ISO9660::volume_descriptors`
+    pub fn volume_descriptors(&self) -> &[VolumeDescriptorSetEntry] {
+        self.fs.volume_descriptors()
+    }
+
+    /// See `ISO9660::primary_volume_descriptor`
+    pub fn primary_volume_descriptor(&self) -> &PrimaryVolumeDescriptor {
+        self.fs.primary_volume_descriptor()
+    }
+
+    /// See `ISO9660::volume_info`
+    pub fn volume_info(&self) -> &VolumeInfo {
+        self.fs.volume_info()
+    }
+
+    /// See `ISO9660::joliet_volume_info`
+    #[cfg(feature = "joliet")]
+    pub fn joliet_volume_info(&self) -> Option<&VolumeInfo> {
+        self.fs.joliet_volume_info()
+    }
+
+    /// See `ISO9660::extensions`
+    pub fn extensions(&self) -> &[Extension] {
+        self.fs.extensions()
+    }
+
+    /// See `ISO9660::both_endian_mismatches`
+    pub fn both_endian_mismatches(&self) -> Vec<BothEndianMismatch> {
+        self.fs.both_endian_mismatches()
+    }
+
+    /// Statistics of the sectors cached between operations
+    pub fn cache_stats(&self) -> CacheStats {
+        self.staging.lock().cache.stats()
+    }
+
+    /// See `ISO9660::open`. Each directory is read one entry at a time, so
+    /// only the sectors of the entry being read are read again.
+    pub async fn open(
+        &mut self,
+        path: &str,
+    ) -> Result<Option<AsyncDirectoryEntry>, ISOError<T::Error>> {
+        let AsyncISO9660 {
+            fs,
+            reader,
+            staging,
+        } = self;
+        let root = &fs.root;
+        let (mut entry, path) = match &fs.path_table {
+            Some(path_table) => {
+                let path = path.trim_end_matches('/');
+                let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+                if name.is_empty() {
+                    return Ok(Some(DirectoryEntry::Directory(root.clone()).into()));
+                }
+                let Some(record) = path_table.find(parent) else {
+                    return Ok(None);
+                };
+                let parent = if record.extent_loc == root.header.extent_loc {
+                    root.clone()
+                } else {
+                    fetch(reader, staging, || {
+                        root.directory_at(record.extent_loc, record.identifier.clone())
+                    })
+                    .await?
+                };
+                (DirectoryEntry::Directory(parent), name)
+            }
+            None => (DirectoryEntry::Directory(root.clone()), path),
+        };
+
+        for segment in path.split('/').filter(|x| !x.is_empty()) {
+            let DirectoryEntry::Directory(parent) = entry else {
+                return Ok(None);
+            };
+            let (lba, count) = extent_sectors(&parent, fs.block_size());
+            prefetch(reader, staging, lba, count).await?;
+            let mut entries = parent.contents();
+            entry = loop {
+                match fetch(reader, staging, || entries.next().transpose()).await? {
+                    Some(entry) if entry.matches(segment) => break entry,
+                    Some(_) => {}
+                    None => return Ok(None),
+                }
+            };
+        }
+
+        Ok(Some(entry.into()))
+    }
+
+    /// Stream of the entries of `directory`, see `ISODirectory::contents`
+    pub fn contents<'a>(
+        &'a mut self,
+        directory: &'a AsyncISODirectory,
+    ) -> AsyncDirectoryEntries<'a, T> {
+        AsyncDirectoryEntries {
+            entries: Some(Entries {
+                reader: &mut self.reader,
+                staging: &self.staging,
+                extent: Some(extent_sectors(&directory.0, self.fs.block_size())),
+                iter: directory.0.contents(),
+            }),
+            next: None,
+        }
+    }
+
+    /// Reader of the contents of `file`, see `ISOFile::read`
+    pub fn read<'a>(&'a mut self, file: &AsyncISOFile) -> AsyncISOFileReader<'a, T> {
+        AsyncISOFileReader {
+            reader: &mut self.reader,
+            staging: &self.staging,
+            file: file.0.read(),
+            #[cfg(feature = "tokio")]
+            pending: None,
+        }
+    }
+}
+
+/// Entry of a directory of an `AsyncISO9660`, see `DirectoryEntry`
+#[derive(Clone, Debug)]
+pub enum AsyncDirectoryEntry {
+    Directory(AsyncISODirectory),
+    File(AsyncISOFile),
+    Symlink(ISOSymlink),
+}
+
+impl From<DirectoryEntry<Prefetched>> for AsyncDirectoryEntry {
+    fn from(entry: DirectoryEntry<Prefetched>) -> AsyncDirectoryEntry {
+        match entry {
+            DirectoryEntry::Directory(dir) => {
+                AsyncDirectoryEntry::Directory(AsyncISODirectory(dir))
+            }
+            DirectoryEntry::File(file) => AsyncDirectoryEntry::File(AsyncISOFile(file)),
+            DirectoryEntry::Symlink(link) => AsyncDirectoryEntry::Symlink(link),
+        }
+    }
+}
+
+impl AsyncDirectoryEntry {
+    pub fn header(&self) -> &DirectoryEntryHeader {
+        match *self {
+            AsyncDirectoryEntry::Directory(ref dir) => dir.header(),
+            AsyncDirectoryEntry::File(ref file) => file.header(),
+            AsyncDirectoryEntry::Symlink(ref link) => &link.header,
+        }
+    }
+
+    pub fn identifier(&self) -> &str {
+        match *self {
+            AsyncDirectoryEntry::Directory(ref dir) => dir.identifier(),
+            AsyncDirectoryEntry::File(ref file) => file.identifier(),
+            AsyncDirectoryEntry::Symlink(ref link) => &link.identifier,
+        }
+    }
+
+    /// Rock Ridge metadata, if the entry has any
+    pub fn rock_ridge(&self) -> Option<&RockRidge> {
+        match *self {
+            AsyncDirectoryEntry::Directory(ref dir) => dir.rock_ridge(),
+            AsyncDirectoryEntry::File(ref file) => file.rock_ridge(),
+            AsyncDirectoryEntry::Symlink(ref link) => Some(link.rock_ridge()),
+        }
+    }
+
+    /// See `DirectoryEntry::system_use_entries`
+    pub fn system_use_entries(&self) -> SystemUseEntries<'_> {
+        match *self {
+            AsyncDirectoryEntry::Directory(ref dir) => dir.system_use_entries(),
+            AsyncDirectoryEntry::File(ref file) => file.system_use_entries(),
+            AsyncDirectoryEntry::Symlink(ref link) => link.system_use_entries(),
+        }
+    }
+}
+
+/// Directory of an `AsyncISO9660`, listed with `AsyncISO9660::contents`.
+/// See `ISODirectory`.
+#[derive(Clone, Debug)]
+pub struct AsyncISODirectory(ISODirectory<Prefetched>);
+
+impl AsyncISODirectory {
+    pub fn header(&self) -> &DirectoryEntryHeader {
+        &self.0.header
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.0.identifier
+    }
+
+    pub fn block_count(&self) -> u32 {
+        self.0.block_count()
+    }
+
+    pub fn time(&self) -> OffsetDateTime {
+        self.0.time()
+    }
+
+    pub fn rock_ridge(&self) -> Option<&RockRidge> {
+        self.0.rock_ridge()
+    }
+
+    pub fn system_use_entries(&self) -> SystemUseEntries<'_> {
+        self.0.system_use_entries()
+    }
+}
+
+/// File of an `AsyncISO9660`, read with `AsyncISO9660::read`. See
+/// `ISOFile`.
+#[derive(Clone, Debug)]
+pub struct AsyncISOFile(ISOFile<Prefetched>);
+
+impl AsyncISOFile {
+    pub fn header(&self) -> &DirectoryEntryHeader {
+        &self.0.header
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.0.identifier
+    }
+
+    pub fn version(&self) -> u16 {
+        self.0.version
+    }
+
+    /// See `ISOFile::size`
+    pub fn size(&self) -> u64 {
+        self.0.size()
+    }
+
+    /// See `ISOFile::extents`
+    pub fn extents(&self) -> &[Extent] {
+        self.0.extents()
+    }
+
+    pub fn time(&self) -> OffsetDateTime {
+        self.0.time()
+    }
+
+    pub fn rock_ridge(&self) -> Option<&RockRidge> {
+        self.0.rock_ridge()
+    }
+
+    pub fn system_use_entries(&self) -> SystemUseEntries<'_> {
+        self.0.system_use_entries()
+    }
+}
+
+type Entry<E> = Result<AsyncDirectoryEntry, ISOError<E>>;
+
+/// State of `AsyncDirectoryEntries`, moved in and out of the future reading
+/// the next entry
+struct Entries<'a, T: AsyncISO9660Reader> {
+    reader: &'a mut T,
+    staging: &'a Shared<Staging>,
+    /// Sectors of the directory, prefetched before the first entry
+    extent: Option<(u64, usize)>,
+    iter: ISODirectoryIterator<'a, Prefetched>,
+}
+
+impl<'a, T: AsyncISO9660Reader> Entries<'a, T> {
+    async fn next(mut self) -> (Self, Option<Entry<T::Error>>) {
+        if let Some((lba, count)) = self.extent.take() {
+            if let Err(err) = prefetch(self.reader, self.staging, lba, count).await {
+                return (self, Some(Err(err)));
+            }
+        }
+        let iter = &mut self.iter;
+        let entry = fetch(self.reader, self.staging, || iter.next().transpose()).await;
+        (self, entry.map(|entry| entry.map(Into::into)).transpose())
+    }
+}
+
+/// Stream of the entries of a directory, see `AsyncISO9660::contents`
+pub struct AsyncDirectoryEntries<'a, T: AsyncISO9660Reader> {
+    entries: Option<Entries<'a, T>>,
+    #[allow(clippy::type_complexity)]
+    next: Option<Pin<Box<dyn Future<Output = (Entries<'a, T>, Option<Entry<T::Error>>)> + 'a>>>,
+}
+
+impl<'a, T: AsyncISO9660Reader> AsyncDirectoryEntries<'a, T> {
+    /// The next entry, like `StreamExt::next`
+    pub async fn next(&mut self) -> Option<Entry<T::Error>> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl<'a, T: AsyncISO9660Reader> Stream for AsyncDirectoryEntries<'a, T> {
+    type Item = Entry<T::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let next = match &mut this.next {
+            Some(next) => next,
+            None => match this.entries.take() {
+                Some(entries) => this.next.insert(Box::pin(entries.next())),
+                None => return Poll::Ready(None),
+            },
+        };
+        let (entries, entry) = ready!(next.as_mut().poll(cx));
+        this.next = None;
+        if entry.is_some() {
+            this.entries = Some(entries);
+        }
+        Poll::Ready(entry)
+    }
+}
+
+/// Reader of the contents of a file, see `AsyncISO9660::read`
+pub struct AsyncISOFileReader<'a, T: AsyncISO9660Reader> {
+    pub(crate) reader: &'a mut T,
+    pub(crate) staging: &'a Shared<Staging>,
+    pub(crate) file: ISOFileReader<Prefetched>,
+    #[cfg(feature = "tokio")]
+    pub(crate) pending: Option<crate::tokio_io::PendingRead>,
+}
+
+impl<'a, T: AsyncISO9660Reader> ErrorType for AsyncISOFileReader<'a, T> {
+    type Error = ISOError<T::Error>;
+}
+
+impl<'a, T: AsyncISO9660Reader> Read for AsyncISOFileReader<'a, T> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let file = &mut self.file;
+        fetch(self.reader, self.staging, || file.read_next(buf)).await
+    }
+}
+
+impl<'a, T: AsyncISO9660Reader> Seek for AsyncISOFileReader<'a, T> {
+    async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        #[cfg(feature = "std")]
+        let pos = match pos {
+            SeekFrom::Start(pos) => std::io::SeekFrom::Start(pos),
+            SeekFrom::End(pos) => std::io::SeekFrom::End(pos),
+            SeekFrom::Current(pos) => std::io::SeekFrom::Current(pos),
+        };
+        Ok(self.file.seek_from(pos))
+    }
+}
@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+// Encoding of the structures read by the `parse` module
+
+use alloc::vec::Vec;
+use time::OffsetDateTime;
+
+use crate::parse::FileFlags;
+
+pub fn both_endian16(buf: &mut Vec<u8>, n: u16) {
+    buf.extend_from_slice(&n.to_le_bytes());
+    buf.extend_from_slice(&n.to_be_bytes());
+}
+
+pub fn both_endian32(buf: &mut Vec<u8>, n: u32) {
+    buf.extend_from_slice(&n.to_le_bytes());
+    buf.extend_from_slice(&n.to_be_bytes());
+}
+
+/// Offset from GMT in 15 minute intervals
+fn gmt_offset(time: &OffsetDateTime) -> u8 {
+    (time.offset().whole_seconds() / (15 * 60)) as i8 as u8
+}
+
+pub fn date_time(buf: &mut Vec<u8>, time: &OffsetDateTime) {
+    buf.extend_from_slice(&[
+        (time.year() - 1900).clamp(0, 255) as u8,
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+        gmt_offset(time),
+    ]);
+}
+
+/// Digits of `n`, zero padded to `width`
+fn ascii_digits(buf: &mut Vec<u8>, n: u32, width: u32) {
+    for i in (0..width).rev() {
+        buf.push(b'0' + (n / 10u32.pow(i) % 10) as u8);
+    }
+}
+
+/// A date in the format of volume descriptors. `None` is recorded as "not
+/// specified".
+pub fn date_time_ascii(buf: &mut Vec<u8>, time: Option<&OffsetDateTime>) {
+    let Some(time) = time else {
+        buf.extend_from_slice(&[b'0'; 16]);
+        buf.push(0);
+        return;
+    };
+
+    ascii_digits(buf, time.year().clamp(0, 9999) as u32, 4);
+    ascii_digits(buf, time.month() as u32, 2);
+    ascii_digits(buf, time.day() as u32, 2);
+    ascii_digits(buf, time.hour() as u32, 2);
+    ascii_digits(buf, time.minute() as u32, 2);
+    ascii_digits(buf, time.second() as u32, 2);
+    ascii_digits(buf, time.millisecond() as u32 / 10, 2);
+    buf.push(gmt_offset(time));
+}
+
+/// `s` padded with spaces, or truncated, to `len` bytes
+pub fn padded_str(buf: &mut Vec<u8>, s: &str, len: usize) {
+    let bytes = &s.as_bytes()[..s.len().min(len)];
+    buf.extend_from_slice(bytes);
+    buf.resize(buf.len() + len - bytes.len(), b' ');
+}
+
+/// `s` in UCS-2 (big endian) padded with spaces, or truncated, to `len`
+/// bytes
+#[cfg(feature = "joliet")]
+pub fn padded_ucs2(buf: &mut Vec<u8>, s: &str, len: usize) {
+    let end = buf.len() + len;
+    for c in s.encode_utf16().take(len / 2) {
+        buf.extend_from_slice(&c.to_be_bytes());
+    }
+    while buf.len() + 2 <= end {
+        buf.extend_from_slice(&[0, b' ']);
+    }
+    buf.resize(end, 0);
+}
+
+/// Length of the directory record for `identifier`, including padding
+pub fn directory_record_len(identifier: &[u8]) -> usize {
+    33 + identifier.len() + (identifier.len() + 1) % 2
+}
+
+pub fn directory_record(
+    buf: &mut Vec<u8>,
+    identifier: &[u8],
+    extent_loc: u32,
+    extent_length: u32,
+    time: &OffsetDateTime,
+    file_flags: FileFlags,
+) {
+    buf.push(directory_record_len(identifier) as u8);
+    buf.push(0); // extended_attribute_record_length
+    both_endian32(buf, extent_loc);
+    both_endian32(buf, extent_length);
+    date_time(buf, time);
+    buf.push(file_flags.bits());
+    buf.push(0); // file_unit_size
+    buf.push(0); // interleave_gap_size
+    both_endian16(buf, 1); // volume_sequence_number
+    buf.push(identifier.len() as u8);
+    buf.extend_from_slice(identifier);
+    if identifier.len() % 2 == 0 {
+        buf.push(0);
+    }
+}
+
+/// Length of the path table record for `identifier`, including padding
+pub fn path_table_record_len(identifier: &[u8]) -> usize {
+    8 + identifier.len() + identifier.len() % 2
+}
+
+pub fn path_table_record(
+    buf: &mut Vec<u8>,
+    identifier: &[u8],
+    extent_loc: u32,
+    parent: u16,
+    big_endian: bool,
+) {
+    buf.push(identifier.len() as u8);
+    buf.push(0); // extended_attribute_record_length
+    if big_endian {
+        buf.extend_from_slice(&extent_loc.to_be_bytes());
+        buf.extend_from_slice(&parent.to_be_bytes());
+    } else {
+        buf.extend_from_slice(&extent_loc.to_le_bytes());
+        buf.extend_from_slice(&parent.to_le_bytes());
+    }
+    buf.extend_from_slice(identifier);
+    if identifier.len() % 2 == 1 {
+        buf.push(0);
+    }
+}
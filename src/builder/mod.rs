@@ -0,0 +1,684 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+//! Writing ISO 9660 images
+
+pub use self::names::InterchangeLevel;
+#[cfg(feature = "joliet")]
+pub use self::names::JolietNames;
+pub use self::source::{FileSource, ReaderSource};
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::mem;
+
+use time::OffsetDateTime;
+
+use self::encode::*;
+#[cfg(feature = "joliet")]
+use self::names::joliet_name;
+use self::names::{primary_name, resolve_collisions};
+use crate::io;
+use crate::parse::FileFlags;
+use crate::ISOError;
+
+mod encode;
+mod names;
+mod source;
+
+enum Node<'a> {
+    Directory(BuilderDirectory<'a>),
+    File(Box<dyn FileSource + 'a>),
+}
+
+/// A directory of an image being built
+#[derive(Default)]
+pub struct BuilderDirectory<'a> {
+    entries: Vec<(String, Node<'a>)>,
+}
+
+impl<'a> BuilderDirectory<'a> {
+    /// Add a subdirectory, or return the existing one called `name`
+    pub fn add_directory(&mut self, name: &str) -> &mut BuilderDirectory<'a> {
+        let index = self
+            .entries
+            .iter()
+            .position(|(n, node)| n == name && matches!(node, Node::Directory(_)))
+            .unwrap_or_else(|| {
+                self.entries.push((
+                    name.to_string(),
+                    Node::Directory(BuilderDirectory::default()),
+                ));
+                self.entries.len() - 1
+            });
+
+        match &mut self.entries[index].1 {
+            Node::Directory(dir) => dir,
+            Node::File(_) => unreachable!(),
+        }
+    }
+
+    pub fn add_file<S: FileSource + 'a>(&mut self, name: &str, source: S) {
+        self.entries
+            .push((name.to_string(), Node::File(Box::new(source))));
+    }
+}
+
+macro_rules! builder_prop_str {
+    ($name:ident) => {
+        pub fn $name(&mut self, value: &str) -> &mut Self {
+            self.$name = value.to_string();
+            self
+        }
+    };
+}
+
+/// Builds an ISO 9660 image from a tree of directories and files.
+///
+/// Names in the primary hierarchy are upper cased, characters other than
+/// letters, digits and "_" are replaced with "_", and they are shortened to
+/// fit the interchange level. Names that collide after this are told apart
+/// by numbering them. A Joliet hierarchy can also be recorded, which keeps
+/// names as they are, up to 64 (or 103) characters.
+pub struct ISOBuilder<'a> {
+    root: BuilderDirectory<'a>,
+    system_identifier: String,
+    volume_identifier: String,
+    volume_set_identifier: String,
+    publisher_identifier: String,
+    data_preparer_identifier: String,
+    application_identifier: String,
+    time: OffsetDateTime,
+    level: InterchangeLevel,
+    #[cfg(feature = "joliet")]
+    joliet: Option<JolietNames>,
+}
+
+impl Default for ISOBuilder<'_> {
+    fn default() -> Self {
+        ISOBuilder::new()
+    }
+}
+
+struct LayoutEntry {
+    identifier: Vec<u8>,
+    /// Number of directory records: one per extent of a file
+    records: usize,
+    /// Identifier in the Joliet hierarchy, in UCS-2
+    #[cfg(feature = "joliet")]
+    joliet_identifier: Vec<u16>,
+    target: Target,
+}
+
+impl LayoutEntry {
+    /// Identifier as recorded in `hierarchy`
+    fn identifier(&self, hierarchy: Hierarchy) -> Vec<u8> {
+        match hierarchy {
+            Hierarchy::Primary => self.identifier.clone(),
+            #[cfg(feature = "joliet")]
+            Hierarchy::Joliet => self
+                .joliet_identifier
+                .iter()
+                .flat_map(|c| c.to_be_bytes())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Target {
+    Directory(usize),
+    File(usize),
+}
+
+struct LayoutDirectory {
+    /// Index of the parent directory; the root is its own parent
+    parent: usize,
+    /// Index of the directory's entry in its parent, except for the root
+    entry: Option<usize>,
+    entries: Vec<LayoutEntry>,
+}
+
+struct LayoutFile<'a> {
+    source: Box<dyn FileSource + 'a>,
+    lba: u32,
+    size: u64,
+}
+
+/// Length of the extents of a file too large for one, but the last: the
+/// most whole blocks whose length fits in the 32 bit data length of a
+/// directory record
+const MAX_EXTENT_LENGTH: u64 = u32::MAX as u64 / 2048 * 2048;
+
+impl LayoutFile<'_> {
+    /// Location and length of each extent of the file
+    fn extents(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let count = extent_count(self.size);
+        (0..count).map(move |i| {
+            let start = i * MAX_EXTENT_LENGTH;
+            let length = if i + 1 == count {
+                self.size - start
+            } else {
+                MAX_EXTENT_LENGTH
+            };
+            (self.lba + (start / 2048) as u32, length as u32)
+        })
+    }
+}
+
+/// Number of extents of a file of `size` bytes: one if its length fits in
+/// a directory record, empty files included
+fn extent_count(size: u64) -> u64 {
+    if size <= u32::MAX as u64 {
+        1
+    } else {
+        size.div_ceil(MAX_EXTENT_LENGTH)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Hierarchy {
+    Primary,
+    #[cfg(feature = "joliet")]
+    Joliet,
+}
+
+/// Placement of the directories of one hierarchy. The file data is shared.
+struct HierarchyLayout {
+    hierarchy: Hierarchy,
+    /// Directories in the order of the path table: breadth first, sorted
+    /// by identifier
+    order: Vec<usize>,
+    /// Entries of each directory, in the order of their records
+    sorted_entries: Vec<Vec<usize>>,
+    lba: Vec<u32>,
+    size: Vec<u32>,
+    path_table_size: usize,
+    l_path_table: u32,
+    m_path_table: u32,
+}
+
+impl HierarchyLayout {
+    fn new(dirs: &[LayoutDirectory], hierarchy: Hierarchy) -> HierarchyLayout {
+        let sorted_entries: Vec<Vec<usize>> = dirs
+            .iter()
+            .map(|dir| {
+                let mut sorted: Vec<usize> = (0..dir.entries.len()).collect();
+                sorted.sort_by_cached_key(|&i| sort_key(&dir.entries[i], hierarchy));
+                sorted
+            })
+            .collect();
+
+        let mut order = vec![0];
+        let mut index = 0;
+        while index < order.len() {
+            let dir = order[index];
+            for &entry in &sorted_entries[dir] {
+                if let Target::Directory(child) = dirs[dir].entries[entry].target {
+                    order.push(child);
+                }
+            }
+            index += 1;
+        }
+
+        HierarchyLayout {
+            hierarchy,
+            path_table_size: order
+                .iter()
+                .map(|&dir| path_table_record_len(&directory_identifier(dirs, dir, hierarchy)))
+                .sum(),
+            order,
+            sorted_entries,
+            lba: vec![0; dirs.len()],
+            size: dirs
+                .iter()
+                .map(|dir| directory_size(&dir.entries, hierarchy))
+                .collect(),
+            l_path_table: 0,
+            m_path_table: 0,
+        }
+    }
+}
+
+impl<'a> ISOBuilder<'a> {
+    pub fn new() -> ISOBuilder<'a> {
+        ISOBuilder {
+            root: BuilderDirectory::default(),
+            system_identifier: String::new(),
+            volume_identifier: String::new(),
+            volume_set_identifier: String::new(),
+            publisher_identifier: String::new(),
+            data_preparer_identifier: String::new(),
+            application_identifier: String::new(),
+            time: OffsetDateTime::UNIX_EPOCH,
+            level: InterchangeLevel::Level1,
+            #[cfg(feature = "joliet")]
+            joliet: None,
+        }
+    }
+
+    /// The root directory of the image
+    pub fn root(&mut self) -> &mut BuilderDirectory<'a> {
+        &mut self.root
+    }
+
+    builder_prop_str!(system_identifier);
+    builder_prop_str!(volume_identifier);
+    builder_prop_str!(volume_set_identifier);
+    builder_prop_str!(publisher_identifier);
+    builder_prop_str!(data_preparer_identifier);
+    builder_prop_str!(application_identifier);
+
+    /// Time recorded for the volume and every directory record. Defaults to
+    /// the Unix epoch, since `no_std` targets don't have a clock.
+    pub fn time(&mut self, time: OffsetDateTime) -> &mut Self {
+        self.time = time;
+        self
+    }
+
+    /// Interchange level limiting names in the primary hierarchy, and
+    /// files to less than 4 GiB below level 3. Defaults to level 1, which
+    /// every system can read.
+    pub fn interchange_level(&mut self, level: InterchangeLevel) -> &mut Self {
+        self.level = level;
+        self
+    }
+
+    /// Also record a Joliet hierarchy, with names of up to the given
+    /// length. Off by default.
+    #[cfg(feature = "joliet")]
+    pub fn joliet(&mut self, names: Option<JolietNames>) -> &mut Self {
+        self.joliet = names;
+        self
+    }
+
+    /// Write the image to `sink`, starting at offset 0
+    pub fn write<W: io::Write + io::Seek>(
+        mut self,
+        sink: &mut W,
+    ) -> Result<(), ISOError<ReaderError!(W)>> {
+        let root = mem::take(&mut self.root);
+        let (dirs, mut files) = layout_tree(root, &self)?;
+        if dirs.len() > u16::MAX as usize {
+            return Err(ISOError::InvalidFs(
+                "Too many directories for the path table",
+            ));
+        }
+
+        #[allow(unused_mut)]
+        let mut layouts = vec![HierarchyLayout::new(&dirs, Hierarchy::Primary)];
+        #[cfg(feature = "joliet")]
+        if self.joliet.is_some() {
+            layouts.push(HierarchyLayout::new(&dirs, Hierarchy::Joliet));
+        }
+
+        // The system area (16 blocks), the volume descriptors and the
+        // terminator come first, followed by the path tables, directories
+        // and file data.
+        let mut lba = 16 + layouts.len() as u32 + 1;
+        for layout in &mut layouts {
+            let blocks = layout.path_table_size.div_ceil(2048) as u32;
+            layout.l_path_table = lba;
+            layout.m_path_table = lba + blocks;
+            lba += 2 * blocks;
+        }
+        for layout in &mut layouts {
+            for &dir in &layout.order {
+                layout.lba[dir] = lba;
+                lba += layout.size[dir] / 2048;
+            }
+        }
+        for file in &mut files {
+            file.lba = lba;
+            lba += file.size.div_ceil(2048) as u32;
+        }
+        let volume_space_size = lba;
+
+        write_at(sink, 0, &[0; 16 * 2048])?;
+        for (i, layout) in layouts.iter().enumerate() {
+            let descriptor = self.volume_descriptor(layout, volume_space_size);
+            write_at(sink, 16 + i as u32, &descriptor)?;
+        }
+        write_at(sink, 16 + layouts.len() as u32, b"\xffCD001\x01")?;
+
+        for layout in &layouts {
+            write_hierarchy(sink, &dirs, &files, layout, &self.time)?;
+        }
+
+        for file in &mut files {
+            sink.seek(io::SeekFrom::Start(file.lba as u64 * 2048))
+                .map_err(ISOError::Io)?;
+
+            let mut buf = [0; 2048];
+            let mut written = 0;
+            while written < file.size {
+                let len = min(file.size - written, buf.len() as u64) as usize;
+                let count = file
+                    .source
+                    .read(&mut buf[..len])
+                    .map_err(ISOError::Source)?;
+                if count == 0 {
+                    return Err(ISOError::ReadSize(file.size as usize, written as usize));
+                }
+                sink.write_all(&buf[..count]).map_err(ISOError::Io)?;
+                written += count as u64;
+            }
+
+            let padding = (written.next_multiple_of(2048) - written) as usize;
+            sink.write_all(&[0; 2048][..padding])
+                .map_err(ISOError::Io)?;
+        }
+
+        sink.flush().map_err(ISOError::Io)
+    }
+
+    /// The primary volume descriptor, or the Joliet supplementary volume
+    /// descriptor
+    fn volume_descriptor(&self, layout: &HierarchyLayout, volume_space_size: u32) -> Vec<u8> {
+        let hierarchy = layout.hierarchy;
+        let mut descriptor = Vec::with_capacity(2048);
+        descriptor.push(match hierarchy {
+            Hierarchy::Primary => 1,
+            #[cfg(feature = "joliet")]
+            Hierarchy::Joliet => 2,
+        });
+        descriptor.extend_from_slice(b"CD001\x01\x00");
+        padded_text(&mut descriptor, &self.system_identifier, 32, hierarchy);
+        padded_text(&mut descriptor, &self.volume_identifier, 32, hierarchy);
+        descriptor.extend_from_slice(&[0; 8]);
+        both_endian32(&mut descriptor, volume_space_size);
+        // Escape sequences of a supplementary volume descriptor
+        let escape_sequences: &[u8] = match hierarchy {
+            Hierarchy::Primary => &[],
+            #[cfg(feature = "joliet")]
+            Hierarchy::Joliet => b"%/E", // UCS-2 level 3
+        };
+        descriptor.extend_from_slice(escape_sequences);
+        descriptor.resize(descriptor.len() + 32 - escape_sequences.len(), 0);
+        both_endian16(&mut descriptor, 1); // volume_set_size
+        both_endian16(&mut descriptor, 1); // volume_sequence_number
+        both_endian16(&mut descriptor, 2048); // logical_block_size
+        both_endian32(&mut descriptor, layout.path_table_size as u32);
+        descriptor.extend_from_slice(&layout.l_path_table.to_le_bytes());
+        descriptor.extend_from_slice(&0u32.to_le_bytes()); // optional_path_table_loc
+        descriptor.extend_from_slice(&layout.m_path_table.to_be_bytes());
+        descriptor.extend_from_slice(&0u32.to_be_bytes()); // optional_path_table_loc_be
+        directory_record(
+            &mut descriptor,
+            &[0],
+            layout.lba[0],
+            layout.size[0],
+            &self.time,
+            FileFlags::DIRECTORY,
+        );
+        padded_text(&mut descriptor, &self.volume_set_identifier, 128, hierarchy);
+        padded_text(&mut descriptor, &self.publisher_identifier, 128, hierarchy);
+        padded_text(
+            &mut descriptor,
+            &self.data_preparer_identifier,
+            128,
+            hierarchy,
+        );
+        padded_text(
+            &mut descriptor,
+            &self.application_identifier,
+            128,
+            hierarchy,
+        );
+        padded_text(&mut descriptor, "", 37, hierarchy); // copyright_file_identifier
+        padded_text(&mut descriptor, "", 37, hierarchy); // abstract_file_identifier
+        padded_text(&mut descriptor, "", 37, hierarchy); // bibliographic_file_identifier
+        date_time_ascii(&mut descriptor, Some(&self.time)); // creation_time
+        date_time_ascii(&mut descriptor, Some(&self.time)); // modification_time
+        date_time_ascii(&mut descriptor, None); // expiration_time
+        date_time_ascii(&mut descriptor, None); // effective_time
+        descriptor.push(1); // file_structure_version
+        descriptor
+    }
+}
+
+/// Text of a volume descriptor, in ASCII or for Joliet in UCS-2
+fn padded_text(buf: &mut Vec<u8>, s: &str, len: usize, hierarchy: Hierarchy) {
+    match hierarchy {
+        Hierarchy::Primary => padded_str(buf, s, len),
+        #[cfg(feature = "joliet")]
+        Hierarchy::Joliet => padded_ucs2(buf, s, len),
+    }
+}
+
+/// Write the path tables and directories of a hierarchy
+fn write_hierarchy<W: io::Write + io::Seek>(
+    sink: &mut W,
+    dirs: &[LayoutDirectory],
+    files: &[LayoutFile<'_>],
+    layout: &HierarchyLayout,
+    time: &OffsetDateTime,
+) -> Result<(), ISOError<ReaderError!(W)>> {
+    let hierarchy = layout.hierarchy;
+
+    // Directory numbers in the path table start at 1
+    let mut numbers = vec![0; dirs.len()];
+    for (number, &dir) in layout.order.iter().enumerate() {
+        numbers[dir] = number as u16 + 1;
+    }
+    for (lba, big_endian) in [(layout.l_path_table, false), (layout.m_path_table, true)] {
+        let mut path_table = Vec::with_capacity(layout.path_table_size);
+        for &dir in &layout.order {
+            path_table_record(
+                &mut path_table,
+                &directory_identifier(dirs, dir, hierarchy),
+                layout.lba[dir],
+                numbers[dirs[dir].parent],
+                big_endian,
+            );
+        }
+        write_at(sink, lba, &path_table)?;
+    }
+
+    for &dir in &layout.order {
+        let (lba, size) = (layout.lba[dir], layout.size[dir]);
+        let parent = dirs[dir].parent;
+        let mut records = Vec::with_capacity(size as usize);
+        let flags = FileFlags::DIRECTORY;
+        directory_record(&mut records, &[0], lba, size, time, flags.clone());
+        let (parent_lba, parent_size) = (layout.lba[parent], layout.size[parent]);
+        directory_record(&mut records, &[1], parent_lba, parent_size, time, flags);
+
+        for &index in &layout.sorted_entries[dir] {
+            let entry = &dirs[dir].entries[index];
+            let extents: Vec<(u32, u32, FileFlags)> = match entry.target {
+                Target::Directory(child) => {
+                    vec![(layout.lba[child], layout.size[child], FileFlags::DIRECTORY)]
+                }
+                // Every record of a file but the last has the multi-extent
+                // flag
+                Target::File(file) => files[file]
+                    .extents()
+                    .enumerate()
+                    .map(|(i, (loc, length))| {
+                        let flags = if i + 1 == entry.records {
+                            FileFlags::empty()
+                        } else {
+                            FileFlags::MULTIEXTENT
+                        };
+                        (loc, length, flags)
+                    })
+                    .collect(),
+            };
+
+            let identifier = entry.identifier(hierarchy);
+            let len = directory_record_len(&identifier);
+            for (extent_loc, extent_length, flags) in extents {
+                // Records may not cross block boundaries
+                if records.len() % 2048 + len > 2048 {
+                    records.resize(records.len().next_multiple_of(2048), 0);
+                }
+                directory_record(
+                    &mut records,
+                    &identifier,
+                    extent_loc,
+                    extent_length,
+                    time,
+                    flags,
+                );
+            }
+        }
+        write_at(sink, lba, &records)?;
+    }
+
+    Ok(())
+}
+
+/// Assign identifiers to the entries of the tree and flatten it
+#[allow(clippy::type_complexity)]
+fn layout_tree<'a, E>(
+    root: BuilderDirectory<'a>,
+    builder: &ISOBuilder<'_>,
+) -> Result<(Vec<LayoutDirectory>, Vec<LayoutFile<'a>>), ISOError<E>> {
+    let mut dirs = vec![LayoutDirectory {
+        parent: 0,
+        entry: None,
+        entries: Vec::new(),
+    }];
+    let mut files = Vec::new();
+    let mut pending = vec![root];
+
+    let mut index = 0;
+    while index < dirs.len() {
+        let directory = mem::take(&mut pending[index]);
+
+        let mut names = BTreeSet::new();
+        if !directory.entries.iter().all(|(name, _)| names.insert(name)) {
+            return Err(ISOError::InvalidFs("Duplicate file name"));
+        }
+
+        let is_directory = |node: &Node<'_>| matches!(node, Node::Directory(_));
+        let identifiers = resolve_collisions(
+            directory
+                .entries
+                .iter()
+                .map(|(name, node)| primary_name(name, is_directory(node), builder.level))
+                .collect(),
+        );
+        #[cfg(feature = "joliet")]
+        let mut joliet_identifiers = resolve_collisions(
+            directory
+                .entries
+                .iter()
+                .map(|(name, node)| {
+                    let names = builder.joliet.unwrap_or(JolietNames::Standard);
+                    joliet_name(name, is_directory(node), names)
+                })
+                .collect(),
+        )
+        .into_iter();
+
+        for ((_, node), mut identifier) in directory.entries.into_iter().zip(identifiers) {
+            #[cfg(feature = "joliet")]
+            let mut joliet_identifier = joliet_identifiers.next().unwrap();
+            let mut records = 1;
+            let target = match node {
+                Node::Directory(dir) => {
+                    dirs.push(LayoutDirectory {
+                        parent: index,
+                        entry: Some(dirs[index].entries.len()),
+                        entries: Vec::new(),
+                    });
+                    pending.push(dir);
+                    Target::Directory(dirs.len() - 1)
+                }
+                Node::File(source) => {
+                    // Files have a version number
+                    identifier.extend_from_slice(b";1");
+                    #[cfg(feature = "joliet")]
+                    joliet_identifier.extend_from_slice(&[b';' as u16, b'1' as u16]);
+
+                    let size = source.size();
+                    if size > u32::MAX as u64 && builder.level != InterchangeLevel::Level3 {
+                        return Err(ISOError::FileTooLarge(size));
+                    }
+                    records = extent_count(size) as usize;
+                    files.push(LayoutFile {
+                        source,
+                        lba: 0,
+                        size,
+                    });
+                    Target::File(files.len() - 1)
+                }
+            };
+            dirs[index].entries.push(LayoutEntry {
+                identifier,
+                records,
+                #[cfg(feature = "joliet")]
+                joliet_identifier,
+                target,
+            });
+        }
+
+        index += 1;
+    }
+
+    Ok((dirs, files))
+}
+
+/// Identifier of a directory in `hierarchy`, as recorded in the path table
+fn directory_identifier(dirs: &[LayoutDirectory], dir: usize, hierarchy: Hierarchy) -> Vec<u8> {
+    match dirs[dir].entry {
+        Some(entry) => dirs[dirs[dir].parent].entries[entry].identifier(hierarchy),
+        None => vec![0],
+    }
+}
+
+/// Directory records are sorted by name, then extension
+fn sort_key(entry: &LayoutEntry, hierarchy: Hierarchy) -> (Vec<u16>, Vec<u16>) {
+    let units: Vec<u16> = match hierarchy {
+        Hierarchy::Primary => entry.identifier.iter().map(|&c| c as u16).collect(),
+        #[cfg(feature = "joliet")]
+        Hierarchy::Joliet => entry.joliet_identifier.clone(),
+    };
+    let end = units
+        .iter()
+        .rposition(|&c| c == b';' as u16)
+        .unwrap_or(units.len());
+    let identifier = &units[..end];
+
+    if let Target::File(_) = entry.target {
+        if let Some(dot) = identifier.iter().rposition(|&c| c == b'.' as u16) {
+            return (identifier[..dot].to_vec(), identifier[dot + 1..].to_vec());
+        }
+    }
+    (identifier.to_vec(), Vec::new())
+}
+
+/// Size of a directory with `entries`, in whole blocks
+fn directory_size(entries: &[LayoutEntry], hierarchy: Hierarchy) -> u32 {
+    // The "." and ".." records
+    let mut size = 2 * directory_record_len(&[0]);
+    for entry in entries {
+        let len = directory_record_len(&entry.identifier(hierarchy));
+        for _ in 0..entry.records {
+            if size % 2048 + len > 2048 {
+                size = size.next_multiple_of(2048);
+            }
+            size += len;
+        }
+    }
+    size.next_multiple_of(2048) as u32
+}
+
+/// Write `data` at the start of block `lba`, padded with zeros to a whole
+/// number of blocks
+fn write_at<W: io::Write + io::Seek>(
+    sink: &mut W,
+    lba: u32,
+    data: &[u8],
+) -> Result<(), ISOError<ReaderError!(W)>> {
+    sink.seek(io::SeekFrom::Start(lba as u64 * 2048))
+        .map_err(ISOError::Io)?;
+    sink.write_all(data).map_err(ISOError::Io)?;
+    let padding = data.len().next_multiple_of(2048) - data.len();
+    sink.write_all(&[0; 2048][..padding]).map_err(ISOError::Io)
+}
@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::vec::Vec;
+use core::cmp::min;
+#[cfg(feature = "joliet")]
+use core::convert::TryFrom;
+
+/// ISO 9660 interchange level, which limits the names in the primary
+/// hierarchy and the extents of files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterchangeLevel {
+    /// File names of up to 8 characters with an extension of up to 3, and
+    /// directory names of up to 8 characters
+    Level1,
+    /// Up to 30 characters for file names, including the extension, and 31
+    /// for directory names
+    Level2,
+    /// Same names as level 2. Files of 4 GiB or more are recorded in
+    /// several extents, each with its own directory record.
+    Level3,
+}
+
+/// Length of the names in the Joliet hierarchy
+#[cfg(feature = "joliet")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JolietNames {
+    /// Up to 64 characters, as required by the Joliet specification
+    Standard,
+    /// Up to 103 characters, the most that fits in a directory record.
+    /// Like `mkisofs -joliet-long`, this is understood by most systems.
+    Relaxed,
+}
+
+#[cfg(feature = "joliet")]
+impl JolietNames {
+    fn max_len(self) -> usize {
+        match self {
+            JolietNames::Standard => 64,
+            JolietNames::Relaxed => 103,
+        }
+    }
+}
+
+/// A name being converted to an identifier, made of code units `T`
+pub(super) struct Name<T> {
+    base: Vec<T>,
+    /// Extension of a file, recorded after a "."
+    extension: Option<Vec<T>>,
+    /// Maximum length of `base`
+    max_base: usize,
+}
+
+impl<T: Copy + Ord + From<u8>> Name<T> {
+    fn identifier(&self, suffix: &[T]) -> Vec<T> {
+        let keep = min(self.base.len(), self.max_base.saturating_sub(suffix.len()));
+        let mut identifier = self.base[..keep].to_vec();
+        identifier.extend_from_slice(suffix);
+        if let Some(extension) = &self.extension {
+            identifier.push(T::from(b'.'));
+            identifier.extend_from_slice(extension);
+        }
+        identifier
+    }
+}
+
+/// Identifiers for the names of the entries of one directory. Names that
+/// would collide get the end of their base replaced by a number, as in
+/// "LONGNA00.TXT", "LONGNA01.TXT", ...
+pub(super) fn resolve_collisions<T: Copy + Ord + From<u8>>(names: Vec<Name<T>>) -> Vec<Vec<T>> {
+    let mut used = BTreeSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            let mut identifier = name.identifier(&[]);
+            let mut n = 0u32;
+            while used.contains(&identifier) {
+                let suffix: Vec<T> = format!("{:02}", n).bytes().map(T::from).collect();
+                identifier = name.identifier(&suffix);
+                n += 1;
+            }
+            used.insert(identifier.clone());
+            identifier
+        })
+        .collect()
+}
+
+/// Name in the primary hierarchy. Letters are upper cased and characters
+/// other than letters, digits and "_" are replaced with "_".
+pub(super) fn primary_name(name: &str, is_directory: bool, level: InterchangeLevel) -> Name<u8> {
+    let d_characters = |s: &str| -> Vec<u8> {
+        s.chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c.to_ascii_uppercase() as u8,
+                _ => b'_',
+            })
+            .collect()
+    };
+
+    if is_directory {
+        let max_base = match level {
+            InterchangeLevel::Level1 => 8,
+            InterchangeLevel::Level2 | InterchangeLevel::Level3 => 31,
+        };
+        let mut base = d_characters(name);
+        base.truncate(max_base);
+        if base.is_empty() {
+            base.push(b'_');
+        }
+        return Name {
+            base,
+            extension: None,
+            max_base,
+        };
+    }
+
+    let (base, extension) = match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => (name, ""),
+    };
+    let mut extension = d_characters(extension);
+    let max_base = match level {
+        InterchangeLevel::Level1 => {
+            extension.truncate(3);
+            8
+        }
+        InterchangeLevel::Level2 | InterchangeLevel::Level3 => {
+            extension.truncate(29);
+            30 - extension.len()
+        }
+    };
+    let mut base = d_characters(base);
+    base.truncate(max_base);
+    if base.is_empty() && extension.is_empty() {
+        base.push(b'_');
+    }
+
+    Name {
+        base,
+        extension: Some(extension),
+        max_base,
+    }
+}
+
+/// Name in the Joliet hierarchy, in UCS-2. Characters Joliet doesn't allow,
+/// and those outside the Basic Multilingual Plane, are replaced with "_".
+#[cfg(feature = "joliet")]
+pub(super) fn joliet_name(name: &str, is_directory: bool, names: JolietNames) -> Name<u16> {
+    let ucs2 = |s: &str| -> Vec<u16> {
+        s.chars()
+            .map(|c| match c {
+                '*' | '/' | ':' | ';' | '?' | '\\' | '\0'..='\x1f' => b'_' as u16,
+                c => u16::try_from(c as u32).unwrap_or(b'_' as u16),
+            })
+            .collect()
+    };
+    let max_len = names.max_len();
+
+    let dot = name.rfind('.').filter(|&dot| dot > 0 && !is_directory);
+    let Some(dot) = dot else {
+        let mut base = ucs2(name);
+        base.truncate(max_len);
+        return Name {
+            base,
+            extension: None,
+            max_base: max_len,
+        };
+    };
+
+    // Keep the extension when truncating long names, unless it's long
+    // itself
+    let mut extension = ucs2(&name[dot + 1..]);
+    extension.truncate(max_len / 2);
+    let max_base = max_len - extension.len() - 1;
+    let mut base = ucs2(&name[..dot]);
+    base.truncate(max_base);
+    Name {
+        base,
+        extension: Some(extension),
+        max_base,
+    }
+}
@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+use crate::io;
+#[cfg(not(feature = "std"))]
+use crate::io::Error as _;
+
+/// Contents of a file added to an `ISOBuilder`. The contents are read once,
+/// from start to end, while the image is written.
+pub trait FileSource {
+    /// Size of the contents in bytes
+    fn size(&self) -> u64;
+
+    /// Read the next part of the contents into `buf`, like `Read::read`
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::ErrorKind>;
+}
+
+impl FileSource for &[u8] {
+    fn size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::ErrorKind> {
+        let count = core::cmp::min(buf.len(), self.len());
+        let (data, rest) = self.split_at(count);
+        buf[..count].copy_from_slice(data);
+        *self = rest;
+        Ok(count)
+    }
+}
+
+/// File contents read from a reader, which must provide exactly `size`
+/// bytes
+pub struct ReaderSource<R: io::Read> {
+    reader: R,
+    size: u64,
+}
+
+impl<R: io::Read> ReaderSource<R> {
+    pub fn new(reader: R, size: u64) -> ReaderSource<R> {
+        ReaderSource { reader, size }
+    }
+}
+
+impl<R: io::Read> FileSource for ReaderSource<R> {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::ErrorKind> {
+        self.reader.read(buf).map_err(|err| err.kind())
+    }
+}
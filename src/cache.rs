@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+
+/// Statistics of the sector cache, see `ISO9660Options::cache`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Sectors read from the cache
+    pub hits: u64,
+    /// Sectors read from the reader, because they weren't cached
+    pub misses: u64,
+    /// Sectors held now
+    pub len: usize,
+    /// Maximum number of sectors held
+    pub capacity: usize,
+}
+
+/// Bounded cache of 2048 byte sectors, evicting the least recently used
+pub(crate) struct SectorCache {
+    /// Data of each sector held, with the time it was last used
+    sectors: BTreeMap<u64, (u64, Box<[u8; 2048]>)>,
+    /// Sector last used at each time, oldest first
+    uses: BTreeMap<u64, u64>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl SectorCache {
+    pub fn new(capacity: usize) -> SectorCache {
+        SectorCache {
+            sectors: BTreeMap::new(),
+            uses: BTreeMap::new(),
+            clock: 0,
+            stats: CacheStats {
+                capacity,
+                ..CacheStats::default()
+            },
+        }
+    }
+
+    /// The data of `sector`, if held, counting a hit or a miss
+    pub fn get(&mut self, sector: u64) -> Option<&[u8; 2048]> {
+        let Some((used, data)) = self.sectors.get_mut(&sector) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.stats.hits += 1;
+        self.uses.remove(used);
+        self.clock += 1;
+        *used = self.clock;
+        self.uses.insert(self.clock, sector);
+        Some(data)
+    }
+
+    /// Whether `sector` is held, without counting a hit or a miss
+    pub fn contains(&self, sector: u64) -> bool {
+        self.sectors.contains_key(&sector)
+    }
+
+    /// Count misses for `count` sectors looked up with `contains`
+    pub fn count_misses(&mut self, count: u64) {
+        self.stats.misses += count;
+    }
+
+    /// Hold the data of `sector`, evicting the least recently used sector
+    /// if the cache is full
+    pub fn insert(&mut self, sector: u64, data: &[u8; 2048]) {
+        if self.stats.capacity == 0 || self.sectors.contains_key(&sector) {
+            return;
+        }
+        let buf = if self.sectors.len() < self.stats.capacity {
+            Box::new(*data)
+        } else {
+            // Reuse the buffer of the evicted sector
+            let (_, oldest) = self.uses.pop_first().unwrap();
+            let (_, mut buf) = self.sectors.remove(&oldest).unwrap();
+            buf.copy_from_slice(data);
+            buf
+        };
+        self.clock += 1;
+        self.sectors.insert(sector, (self.clock, buf));
+        self.uses.insert(self.clock, sector);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            len: self.sectors.len(),
+            ..self.stats
+        }
+    }
+}
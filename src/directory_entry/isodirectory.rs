@@ -2,18 +2,28 @@
 
 use alloc::str;
 use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use core::fmt;
 
 use time::OffsetDateTime;
 
-use crate::parse::{DirectoryEntryHeader, DirectoryEntryReader, FileFlags};
-use crate::{DirectoryEntry, FileRef, ISO9660Reader, ISOError};
+use super::{DirectoryRecords, Extent, ISOFile};
+use crate::parse::{
+    append_entries, susp_skip, system_use_area, system_use_entries, ContinuationArea,
+    DirectoryEntryHeader, DirectoryEntryReader, FileFlags,
+};
+use crate::{
+    DirectoryEntry, FileRef, ISO9660Reader, ISOError, RockRidge, SliceReader, Structure,
+    SystemUseEntries, WarningKind,
+};
 
 pub struct ISODirectory<T: ISO9660Reader> {
     pub(crate) header: DirectoryEntryHeader,
     pub identifier: String,
+    pub(crate) rock_ridge: Option<RockRidge>,
+    pub(crate) system_use: Vec<u8>,
     file: FileRef<T>,
-    reader: DirectoryEntryReader,
+    pub(crate) reader: DirectoryEntryReader,
 }
 
 impl<T: ISO9660Reader> Clone for ISODirectory<T> {
@@ -21,6 +31,8 @@ impl<T: ISO9660Reader> Clone for ISODirectory<T> {
         ISODirectory {
             header: self.header.clone(),
             identifier: self.identifier.clone(),
+            rock_ridge: self.rock_ridge.clone(),
+            system_use: self.system_use.clone(),
             file: self.file.clone(),
             reader: self.reader,
         }
@@ -29,9 +41,10 @@ impl<T: ISO9660Reader> Clone for ISODirectory<T> {
 
 impl<T: ISO9660Reader> fmt::Debug for ISODirectory<T> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.debug_struct("ISOFile")
+        fmt.debug_struct("ISODirectory")
             .field("header", &self.header)
             .field("identifier", &self.identifier)
+            .field("rock_ridge", &self.rock_ridge)
             .finish()
     }
 }
@@ -40,6 +53,8 @@ impl<T: ISO9660Reader> ISODirectory<T> {
     pub(crate) fn new(
         header: DirectoryEntryHeader,
         mut identifier: String,
+        system_use: Vec<u8>,
+        rock_ridge: Option<RockRidge>,
         file: FileRef<T>,
         reader: DirectoryEntryReader,
     ) -> ISODirectory<T> {
@@ -47,11 +62,15 @@ impl<T: ISO9660Reader> ISODirectory<T> {
             identifier = ".".to_string();
         } else if &identifier == "\u{1}" {
             identifier = "..".to_string();
+        } else if let Some(name) = rock_ridge.as_ref().and_then(|rr| rr.name.as_ref()) {
+            identifier.clone_from(name);
         }
 
         ISODirectory {
             header,
             identifier,
+            rock_ridge,
+            system_use,
             file,
             reader,
         }
@@ -59,48 +78,256 @@ impl<T: ISO9660Reader> ISODirectory<T> {
 
     pub fn block_count(&self) -> u32 {
         let len = self.header.extent_length;
-        len.div_ceil(2048) // ceil(len / 2048)
+        len.div_ceil(self.block_size() as u32) // ceil(len / block_size)
     }
 
+    /// Logical block size of the volume
+    fn block_size(&self) -> usize {
+        self.file.block_size() as usize
+    }
+
+    /// Read the logical block at `lba`, holding `structure`, into the start
+    /// of `block`
+    fn read_block(
+        &self,
+        block: &mut [u8; 2048],
+        lba: u64,
+        structure: Structure,
+    ) -> Result<(), ISOError<ReaderError!(T)>> {
+        let block_size = self.block_size();
+        let count = self
+            .file
+            .read_at(&mut block[..block_size], lba)
+            .map_err(ISOError::Io)?;
+        if count != block_size {
+            return Err(ISOError::ReadSize(block_size, count).at(structure, lba, 0));
+        }
+        Ok(())
+    }
+
+    /// Read the entry at byte `offset` of the directory, returning it with
+    /// the offset of the next entry. `block` holds the logical block read
+    /// last, whose number is `buf_block_num`.
+    #[allow(clippy::type_complexity)]
     pub fn read_entry_at(
         &self,
         block: &mut [u8; 2048],
         buf_block_num: &mut Option<u64>,
         offset: u64,
     ) -> Result<(DirectoryEntry<T>, Option<u64>), ISOError<ReaderError!(T)>> {
-        let mut block_num = offset / 2048;
-        let mut block_pos = (offset % 2048) as usize;
+        let block_size = self.block_size();
+        let block_num = offset / block_size as u64;
+        let mut block_pos = (offset % block_size as u64) as usize;
+        let lba = self.header.extent_loc as u64 + block_num;
+        let record_pos = block_pos;
+        let at = |err: ISOError<ReaderError!(T)>| {
+            err.at(Structure::DirectoryRecord, lba, record_pos as u32)
+        };
 
         if buf_block_num != &Some(block_num) {
-            let lba = self.header.extent_loc as u64 + block_num;
-            let count = self.file.read_at(block, lba).map_err(ISOError::Io)?;
-
-            if count != 2048 {
-                *buf_block_num = None;
-                return Err(ISOError::ReadSize(2048, count));
-            }
-
+            *buf_block_num = None;
+            self.read_block(block, lba, Structure::DirectoryRecord)?;
             *buf_block_num = Some(block_num);
         }
 
-        let (header, identifier) = DirectoryEntryHeader::parse(&block[block_pos..], self.reader)?;
+        let endian = self.file.both_endian();
+        let (mut header, mut identifier) =
+            DirectoryEntryHeader::parse(&block[block_pos..block_size], self.reader, &endian)
+                .map_err(at)?;
+        if header.length < 34 || block_pos + header.length as usize > block_size {
+            return Err(at(ISOError::InvalidFs("Invalid directory record length")));
+        }
+        self.file.record_mismatches(endian.finish(
+            Structure::DirectoryRecord,
+            lba,
+            record_pos as u32,
+        )?);
+        let system_use = match self.reader {
+            DirectoryEntryReader::RockRidge { susp_skip } => {
+                let area = system_use_area(&block[block_pos..block_size]);
+                let area = area.get(susp_skip as usize..).unwrap_or(&[]);
+                self.read_system_use(area, lba, record_pos as u32)?
+            }
+            _ => Vec::new(),
+        };
+        // The entries are located at their directory record, wherever they
+        // are recorded
+        let endian = self.file.both_endian();
+        let rock_ridge = RockRidge::parse(&system_use, &endian);
+        self.file.record_mismatches(endian.finish(
+            Structure::SystemUseEntry,
+            lba,
+            record_pos as u32,
+        )?);
         block_pos += header.length as usize;
 
-        let entry = DirectoryEntry::new(header, identifier, self.file.clone(), self.reader)?;
+        if self.file.lenient() && !header.file_flags.contains(FileFlags::DIRECTORY) {
+            if let Some(idx) = identifier.rfind(';') {
+                if identifier[idx + 1..].parse::<u16>().is_err() {
+                    self.file
+                        .warn(lba, record_pos as u32, WarningKind::InvalidVersion);
+                    identifier.truncate(idx);
+                }
+            }
+        }
 
-        // All bytes after the last directory entry are zero.
-        if block_pos >= (2048 - 33) || block[block_pos] == 0 {
-            block_num += 1;
-            block_pos = 0;
+        // Rock Ridge may relocate deep directories elsewhere (often to
+        // "rr_moved"), leaving a file with a "CL" entry in their place. The
+        // ".." of a relocated directory has a "PL" entry pointing to the
+        // original parent. In both cases, use the "." record of the directory
+        // being linked to.
+        let link = match &rock_ridge {
+            Some(rr) if identifier == "\u{1}" => rr.parent_link,
+            Some(rr) => rr.child_link,
+            None => None,
+        };
+        if let Some(lba) = link {
+            let record_length = header.length;
+            header = self.read_dot_header(lba)?;
+            header.length = record_length;
         }
 
-        let next_offset = if block_num < self.block_count() as u64 {
-            Some(2048 * block_num + block_pos as u64)
-        } else {
-            None
+        let entry = DirectoryEntry::new(
+            header,
+            identifier,
+            system_use,
+            rock_ridge,
+            self.file.clone(),
+            self.reader,
+        )
+        .map_err(at)?;
+
+        Ok((entry, self.next_offset(block, block_num, block_pos)))
+    }
+
+    /// Offset of the entry following one that ends at `block_pos` in block
+    /// `block_num`, which is in `block`
+    fn next_offset(&self, block: &[u8; 2048], block_num: u64, block_pos: usize) -> Option<u64> {
+        next_offset(
+            &block[..self.block_size()],
+            self.block_count() as u64,
+            block_num,
+            block_pos,
+        )
+    }
+
+    /// In lenient mode, record a warning for the entry at `offset` which
+    /// couldn't be read, and return the offset of the next entry. `block`
+    /// holds the logical block read last, whose number is `buf_block_num`.
+    fn skip_entry(
+        &self,
+        block: &[u8; 2048],
+        buf_block_num: Option<u64>,
+        offset: u64,
+    ) -> Option<u64> {
+        let block_size = self.block_size();
+        let block_num = offset / block_size as u64;
+        let block_pos = (offset % block_size as u64) as usize;
+        self.file.warn(
+            self.header.extent_loc as u64 + block_num,
+            block_pos as u32,
+            WarningKind::InvalidRecord,
+        );
+
+        let length = match buf_block_num {
+            Some(num) if num == block_num => block[block_pos] as usize,
+            _ => 0,
         };
+        if length >= 34 && block_pos + length <= block_size {
+            self.next_offset(block, block_num, block_pos + length)
+        } else {
+            // Without a usable length, skip the rest of the block
+            let block_num = block_num + 1;
+            (block_num < self.block_count() as u64).then(|| block_size as u64 * block_num)
+        }
+    }
 
-        Ok((entry, next_offset))
+    /// The directory of the same hierarchy whose extent is at `lba`
+    pub(crate) fn directory_at(
+        &self,
+        lba: u32,
+        identifier: String,
+    ) -> Result<ISODirectory<T>, ISOError<ReaderError!(T)>> {
+        let header = self.read_dot_header(lba)?;
+        Ok(ISODirectory::new(
+            header,
+            identifier,
+            Vec::new(),
+            None,
+            self.file.clone(),
+            self.reader,
+        ))
+    }
+
+    /// Read the header of the "." record of the directory at `lba`
+    fn read_dot_header(&self, lba: u32) -> Result<DirectoryEntryHeader, ISOError<ReaderError!(T)>> {
+        let at = |err: ISOError<ReaderError!(T)>| err.at(Structure::DirectoryRecord, lba as u64, 0);
+        let mut block = [0; 2048];
+        self.read_block(&mut block, lba as u64, Structure::DirectoryRecord)?;
+
+        let endian = self.file.both_endian();
+        let (header, _) =
+            DirectoryEntryHeader::parse(&block[..self.block_size()], self.reader, &endian)
+                .map_err(at)?;
+        self.file
+            .record_mismatches(endian.finish(Structure::DirectoryRecord, lba as u64, 0)?);
+        if !header.file_flags.contains(FileFlags::DIRECTORY) {
+            return Err(at(ISOError::InvalidFs(
+                "Rock Ridge link to a non-directory",
+            )));
+        }
+        Ok(header)
+    }
+
+    /// Collect the SUSP entries of a system use area, at byte `offset` of
+    /// block `lba`, following "CE" entries into their continuation areas.
+    fn read_system_use(
+        &self,
+        area: &[u8],
+        lba: u64,
+        offset: u32,
+    ) -> Result<Vec<u8>, ISOError<ReaderError!(T)>> {
+        let mut entries = Vec::new();
+        let endian = self.file.both_endian();
+        let mut continuation = append_entries(area, &mut entries, &endian);
+        self.file
+            .record_mismatches(endian.finish(Structure::SystemUseEntry, lba, offset)?);
+
+        // Like Linux, stop after 32 continuation areas in case of a loop
+        for _ in 0..32 {
+            let Some(ContinuationArea {
+                block,
+                offset,
+                length,
+            }) = continuation
+            else {
+                break;
+            };
+
+            let (offset, length) = (offset as usize, length as usize);
+            if offset + length > self.block_size() {
+                return Err(
+                    ISOError::InvalidFs("SUSP continuation area crosses a block boundary").at(
+                        Structure::SystemUseEntry,
+                        block as u64,
+                        offset as u32,
+                    ),
+                );
+            }
+
+            let mut buf = [0; 2048];
+            self.read_block(&mut buf, block as u64, Structure::SystemUseEntry)?;
+
+            let endian = self.file.both_endian();
+            continuation = append_entries(&buf[offset..offset + length], &mut entries, &endian);
+            self.file.record_mismatches(endian.finish(
+                Structure::SystemUseEntry,
+                block as u64,
+                offset as u32,
+            )?);
+        }
+
+        Ok(entries)
     }
 
     pub fn contents(&'_ self) -> ISODirectoryIterator<'_, T> {
@@ -116,21 +343,48 @@ impl<T: ISO9660Reader> ISODirectory<T> {
         self.header.time
     }
 
+    pub fn rock_ridge(&self) -> Option<&RockRidge> {
+        self.rock_ridge.as_ref()
+    }
+
+    pub fn system_use_entries(&self) -> SystemUseEntries<'_> {
+        system_use_entries(&self.system_use)
+    }
+
+    /// If this is a root directory whose "." record starts with a SUSP "SP"
+    /// entry, returns the number of bytes to skip in each system use area.
+    pub(crate) fn susp_skip(&self) -> Result<Option<u8>, ISOError<ReaderError!(T)>> {
+        let mut block = [0; 2048];
+        self.read_block(
+            &mut block,
+            self.header.extent_loc as u64,
+            Structure::DirectoryRecord,
+        )?;
+
+        Ok(susp_skip(system_use_area(&block[..self.block_size()])))
+    }
+
     pub fn find(
         &self,
         identifier: &str,
     ) -> Result<Option<DirectoryEntry<T>>, ISOError<ReaderError!(T)>> {
-        for entry in self.contents() {
-            let entry = entry?;
-            if entry
-                .header()
-                .file_flags
-                .contains(FileFlags::ASSOCIATEDFILE)
-            {
-                continue;
-            }
-            if entry.identifier().eq_ignore_ascii_case(identifier) {
-                return Ok(Some(entry));
+        Ok(self.find_located(identifier)?.map(|(entry, _, _)| entry))
+    }
+
+    /// Find an entry like `find`, with the logical block and byte offset of
+    /// its record
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn find_located(
+        &self,
+        identifier: &str,
+    ) -> Result<Option<(DirectoryEntry<T>, u64, u32)>, ISOError<ReaderError!(T)>> {
+        let block_size = self.block_size() as u64;
+        let mut entries = self.contents();
+        while let Some(entry) = entries.next_located() {
+            let (entry, offset) = entry?;
+            if entry.matches(identifier) {
+                let lba = self.header.extent_loc as u64 + offset / block_size;
+                return Ok(Some((entry, lba, (offset % block_size) as u32)));
             }
         }
 
@@ -156,6 +410,37 @@ impl<T: ISO9660Reader> ISODirectory<T> {
     }
 }
 
+impl<'a> ISODirectory<SliceReader<'a>> {
+    /// Iterate over the records of the directory, borrowed from the image
+    /// in memory. Unlike `contents`, nothing is allocated: identifiers are
+    /// left as recorded, and Rock Ridge names and links aren't used.
+    pub fn records(&self) -> DirectoryRecords<'a> {
+        DirectoryRecords::new(&self.header, self.file.clone(), self.reader)
+    }
+}
+
+/// Offset in a directory of the entry following one that ends at
+/// `block_pos` in `block`, the `block_num`th of `block_count` blocks
+pub(super) fn next_offset(
+    block: &[u8],
+    block_count: u64,
+    mut block_num: u64,
+    mut block_pos: usize,
+) -> Option<u64> {
+    let block_size = block.len();
+    // All bytes after the last directory entry are zero.
+    if block_pos >= (block_size - 33) || block[block_pos] == 0 {
+        block_num += 1;
+        block_pos = 0;
+    }
+
+    if block_num < block_count {
+        Some(block_size as u64 * block_num + block_pos as u64)
+    } else {
+        None
+    }
+}
+
 pub struct ISODirectoryIterator<'a, T: ISO9660Reader> {
     directory: &'a ISODirectory<T>,
     next_offset: Option<u64>,
@@ -167,16 +452,93 @@ impl<'a, T: ISO9660Reader> Iterator for ISODirectoryIterator<'a, T> {
     type Item = Result<DirectoryEntry<T>, ISOError<ReaderError!(T)>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let offset = self.next_offset?;
-        match self
-            .directory
-            .read_entry_at(&mut self.block, &mut self.block_num, offset)
-        {
-            Ok((entry, next_offset)) => {
-                self.next_offset = next_offset;
-                Some(Ok(entry))
+        self.next_located()
+            .map(|entry| entry.map(|(entry, _)| entry))
+    }
+}
+
+impl<'a, T: ISO9660Reader> ISODirectoryIterator<'a, T> {
+    /// The next entry, with the offset of its record in the directory
+    #[allow(clippy::type_complexity)]
+    fn next_located(
+        &mut self,
+    ) -> Option<Result<(DirectoryEntry<T>, u64), ISOError<ReaderError!(T)>>> {
+        loop {
+            let offset = self.next_offset?;
+            match self
+                .directory
+                .read_entry_at(&mut self.block, &mut self.block_num, offset)
+            {
+                Ok((mut entry, next_offset)) => {
+                    self.next_offset = next_offset;
+                    // Relocated directories are reached through their "CL"
+                    // entry instead
+                    if entry.rock_ridge().is_some_and(|rr| rr.relocated) {
+                        continue;
+                    }
+                    if let DirectoryEntry::File(file) = &mut entry {
+                        if let Err(err) = self.read_extents(file, offset) {
+                            // Read the entry again on the next call
+                            self.next_offset = Some(offset);
+                            return Some(Err(err));
+                        }
+                    }
+                    return Some(Ok((entry, offset)));
+                }
+                Err(_) if self.directory.file.lenient() => {
+                    self.next_offset =
+                        self.directory
+                            .skip_entry(&self.block, self.block_num, offset);
+                }
+                Err(err) => return Some(Err(err)),
             }
-            Err(err) => Some(Err(err)),
         }
     }
+
+    /// A file recorded in several extents has a record for each of them,
+    /// all but the last with the multi-extent flag. Add the extents of the
+    /// records following the first one, at `offset`, to `file`.
+    fn read_extents(
+        &mut self,
+        file: &mut ISOFile<T>,
+        offset: u64,
+    ) -> Result<(), ISOError<ReaderError!(T)>> {
+        let block_size = self.directory.block_size() as u64;
+        let lba = self.directory.header.extent_loc as u64 + offset / block_size;
+        let pos = (offset % block_size) as u32;
+        let at = |err: ISOError<ReaderError!(T)>| err.at(Structure::DirectoryRecord, lba, pos);
+        let mut file_flags = file.header.file_flags.clone();
+        while file_flags.contains(FileFlags::MULTIEXTENT) {
+            let next = match self.next_offset {
+                Some(offset) => {
+                    self.directory
+                        .read_entry_at(&mut self.block, &mut self.block_num, offset)
+                }
+                None => Err(ISOError::InvalidFs("Missing last extent of file")),
+            };
+            let (entry, next_offset) = match next {
+                Ok((entry, next_offset)) if entry.identifier() == file.identifier => {
+                    (entry, next_offset)
+                }
+                // Leave the entry that isn't part of the file to the iterator
+                _ if self.directory.file.lenient() => {
+                    self.directory
+                        .file
+                        .warn(lba, pos, WarningKind::MissingExtent);
+                    return Ok(());
+                }
+                Ok(_) => return Err(at(ISOError::InvalidFs("Missing last extent of file"))),
+                Err(err) => return Err(at(err)),
+            };
+            self.next_offset = next_offset;
+
+            let header = entry.header();
+            file.extents.push(Extent {
+                loc: header.extent_loc,
+                length: header.extent_length,
+            });
+            file_flags = header.file_flags.clone();
+        }
+        Ok(())
+    }
 }
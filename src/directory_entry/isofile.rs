@@ -3,13 +3,32 @@
 use crate::io;
 use alloc::str::FromStr;
 use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
 use core::cmp::min;
 use core::fmt;
+use core::mem;
 
 use time::OffsetDateTime;
 
+#[cfg(feature = "zisofs")]
+use super::zisofs::ZisofsReader;
 use super::DirectoryEntryHeader;
-use crate::{FileRef, ISO9660Reader, ISOError};
+use crate::parse::system_use_entries;
+#[cfg(feature = "zisofs")]
+use crate::CompressedFile;
+use crate::{
+    FileRef, ISO9660Reader, ISOError, RockRidge, SliceReader, SparseFile, SystemUseEntries,
+};
+
+/// A contiguous part of the data of a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Extent {
+    /// Location of the first block
+    pub loc: u32,
+    /// Length in bytes
+    pub length: u32,
+}
 
 #[derive(Clone)]
 pub struct ISOFile<T: ISO9660Reader> {
@@ -17,6 +36,10 @@ pub struct ISOFile<T: ISO9660Reader> {
     pub identifier: String,
     // File version; ranges from 1 to 32767
     pub version: u16,
+    /// Extents of the data, more than one for multi-extent files
+    pub(crate) extents: Vec<Extent>,
+    pub(crate) rock_ridge: Option<RockRidge>,
+    pub(crate) system_use: Vec<u8>,
     file: FileRef<T>,
 }
 
@@ -26,6 +49,8 @@ impl<T: ISO9660Reader> fmt::Debug for ISOFile<T> {
             .field("header", &self.header)
             .field("identifier", &self.identifier)
             .field("version", &self.version)
+            .field("extents", &self.extents)
+            .field("rock_ridge", &self.rock_ridge)
             .finish()
     }
 }
@@ -34,6 +59,8 @@ impl<T: ISO9660Reader> ISOFile<T> {
     pub(crate) fn new(
         header: DirectoryEntryHeader,
         mut identifier: String,
+        system_use: Vec<u8>,
+        rock_ridge: Option<RockRidge>,
         file: FileRef<T>,
     ) -> Result<ISOFile<T>, ISOError<ReaderError!(T)>> {
         // Files (not directories) in ISO 9660 have a version number, which is
@@ -53,72 +80,314 @@ impl<T: ISO9660Reader> ISOFile<T> {
             identifier.pop();
         }
 
+        // Rock Ridge names are used as-is
+        if let Some(name) = rock_ridge.as_ref().and_then(|rr| rr.name.as_ref()) {
+            identifier.clone_from(name);
+        }
+
+        let extents = vec![Extent {
+            loc: header.extent_loc,
+            length: header.extent_length,
+        }];
+
         Ok(ISOFile {
             header,
             identifier,
             version,
+            extents,
+            rock_ridge,
+            system_use,
             file,
         })
     }
 
-    pub fn size(&self) -> u32 {
-        self.header.extent_length
+    /// Size of the file. For Rock Ridge sparse files this is the logical
+    /// size, which may be larger than the extent, and for zisofs compressed
+    /// files (with the `zisofs` feature) the uncompressed size.
+    pub fn size(&self) -> u64 {
+        #[cfg(feature = "zisofs")]
+        if let Some(compressed) = self.compressed() {
+            return compressed.size as u64;
+        }
+
+        match self.sparse() {
+            Some(sparse) => sparse.size,
+            None => self.recorded_size(),
+        }
+    }
+
+    /// Size of the data recorded in the extents of the file
+    fn recorded_size(&self) -> u64 {
+        self.extents.iter().map(|extent| extent.length as u64).sum()
+    }
+
+    /// Extents of the file in order. Files of 4 GiB or more are recorded
+    /// in several.
+    pub fn extents(&self) -> &[Extent] {
+        &self.extents
+    }
+
+    fn sparse(&self) -> Option<SparseFile> {
+        self.rock_ridge.as_ref().and_then(|rr| rr.sparse)
+    }
+
+    #[cfg(feature = "zisofs")]
+    fn compressed(&self) -> Option<CompressedFile> {
+        self.rock_ridge
+            .as_ref()
+            .and_then(|rr| rr.compressed)
+            .filter(|compressed| &compressed.algorithm == b"pz")
     }
 
     pub fn time(&self) -> OffsetDateTime {
         self.header.time
     }
 
+    pub fn rock_ridge(&self) -> Option<&RockRidge> {
+        self.rock_ridge.as_ref()
+    }
+
+    pub fn system_use_entries(&self) -> SystemUseEntries<'_> {
+        system_use_entries(&self.system_use)
+    }
+
     pub fn read(&self) -> ISOFileReader<T> {
-        ISOFileReader {
+        let data = FileData {
             buf: [0; 2048],
             buf_lba: None,
-            seek: 0,
-            start_lba: self.header.extent_loc,
-            size: self.size() as usize,
+            buf_len: 0,
+            extents: self.extents.clone(),
+            size: match self.sparse() {
+                Some(sparse) => sparse.size,
+                None => self.recorded_size(),
+            },
+            sparse_table_depth: self.sparse().map(|sparse| sparse.table_depth),
             file: self.file.clone(),
+        };
+
+        ISOFileReader {
+            #[cfg(feature = "zisofs")]
+            zisofs: self.compressed().map(ZisofsReader::new),
+            data,
+            seek: 0,
+            size: self.size(),
         }
     }
 }
 
-pub struct ISOFileReader<T: ISO9660Reader> {
+impl<'a> ISOFile<SliceReader<'a>> {
+    /// The data of the file, borrowed from the image in memory. `None` if
+    /// it isn't recorded contiguously, as for sparse files or files whose
+    /// extents are apart, if it is compressed (with the `zisofs` feature),
+    /// or if the image ends before the data.
+    pub fn as_slice(&self) -> Option<&'a [u8]> {
+        #[cfg(feature = "zisofs")]
+        if self.compressed().is_some() {
+            return None;
+        }
+        if self.sparse().is_some() {
+            return None;
+        }
+
+        let block_size = self.file.block_size() as u64;
+        let contiguous = self.extents.windows(2).all(|pair| {
+            pair[0].loc as u64 * block_size + pair[0].length as u64
+                == pair[1].loc as u64 * block_size
+        });
+        let size = self.recorded_size();
+        let data = self.file.slice(self.extents[0].loc as u64, size);
+        (contiguous && data.len() as u64 == size).then_some(data)
+    }
+}
+
+impl<T: ISO9660Reader> ISOFileReader<T> {
+    /// Reader for data which doesn't belong to a directory entry (such as
+    /// a boot image)
+    pub(crate) fn from_extent(file: FileRef<T>, extent: Extent) -> ISOFileReader<T> {
+        let size = extent.length as u64;
+        ISOFileReader {
+            data: FileData {
+                buf: [0; 2048],
+                buf_lba: None,
+                buf_len: 0,
+                extents: vec![extent],
+                size,
+                sparse_table_depth: None,
+                file,
+            },
+            #[cfg(feature = "zisofs")]
+            zisofs: None,
+            seek: 0,
+            size,
+        }
+    }
+}
+
+/// The data recorded for a file, which is read one block at a time
+pub(super) struct FileData<T: ISO9660Reader> {
     buf: [u8; 2048],
     buf_lba: Option<u64>,
-    seek: usize,
-    start_lba: u32,
-    size: usize,
+    /// Bytes of `buf` read, less than a block at the end of the image
+    buf_len: usize,
+    /// For sparse files, only the first extent is used
+    extents: Vec<Extent>,
+    size: u64,
+    sparse_table_depth: Option<u8>,
     file: FileRef<T>,
 }
 
-#[cfg(not(feature = "std"))]
-impl<T: ISO9660Reader> io::ErrorType for ISOFileReader<T> {
-    type Error = T::Error;
-}
+impl<T: ISO9660Reader> FileData<T> {
+    /// Location of the `block`th block of an extent starting at
+    /// `start_lba`, or `None` if it is a region of a sparse file that isn't
+    /// recorded.
+    fn block_lba(&self, start_lba: u32, block: u64) -> Result<Option<u64>, ReaderError!(T)> {
+        let Some(depth) = self.sparse_table_depth else {
+            return Ok(Some(start_lba as u64 + block));
+        };
 
-impl<T: ISO9660Reader> io::Read for ISOFileReader<T> {
-    fn read(&mut self, mut buf: &mut [u8]) -> core::result::Result<usize, ReaderError!(T)> {
+        // Walk the tables of a sparse file, see `SparseFile`
+        let block_size = self.file.block_size() as usize;
+        let entries = block_size as u64 / 4;
+        if entries
+            .checked_pow(depth as u32)
+            .is_some_and(|blocks| block >= blocks)
+        {
+            return Ok(None);
+        }
+
+        let mut table = [0; 2048];
+        let mut lba = start_lba as u64;
+        for level in (0..depth as u32).rev() {
+            let index = entries
+                .checked_pow(level)
+                .map_or(0, |span| (block / span) % entries) as usize;
+            self.file.read_at(&mut table[..block_size], lba)?;
+            let entry = &table[index * 4..index * 4 + 4];
+            lba = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) as u64;
+            if lba == 0 {
+                return Ok(None);
+            }
+        }
+        Ok(Some(lba))
+    }
+
+    /// Logical block holding the recorded data at `offset`, and the offset
+    /// of the data in that block
+    #[cfg(feature = "zisofs")]
+    pub(super) fn position(&self, offset: u64) -> (u64, u32) {
+        let block_size = self.file.block_size() as u64;
+        let mut extent_offset = offset;
+        let mut extent = self.extents[0];
+        for &next in &self.extents {
+            extent = next;
+            if extent_offset < next.length as u64 {
+                break;
+            }
+            extent_offset -= next.length as u64;
+        }
+        (
+            extent.loc as u64 + extent_offset / block_size,
+            (extent_offset % block_size) as u32,
+        )
+    }
+
+    /// Read the data at `offset` into `buf`, stopping at the end of the data
+    pub(super) fn read_at(
+        &mut self,
+        offset: u64,
+        mut buf: &mut [u8],
+    ) -> Result<usize, ReaderError!(T)> {
         use crate::io::Write as _;
-        let mut seek = self.seek;
+        let block_size = self.file.block_size() as u64;
+        let mut seek = offset;
         while !buf.is_empty() && seek < self.size {
-            let lba = self.start_lba as u64 + (seek as u64 / 2048);
+            // Find the extent containing `seek`
+            let mut extent = self.extents[0];
+            let mut extent_offset = seek;
+            let mut extent_size = self.size;
+            if self.sparse_table_depth.is_none() {
+                for &next in &self.extents {
+                    extent = next;
+                    extent_size = next.length as u64;
+                    if extent_offset < extent_size {
+                        break;
+                    }
+                    extent_offset -= extent_size;
+                }
+            }
+
+            let block = extent_offset / block_size;
+            let start = (extent_offset % block_size) as usize;
+            let end = min(extent_size - block * block_size, block_size) as usize;
+
+            // Whole blocks of the extent are read straight into `buf`, in
+            // one read
+            let whole = min(buf.len() as u64, extent_size - block * block_size) / block_size;
+            if start == 0 && whole > 0 && self.sparse_table_depth.is_none() {
+                let len = (whole * block_size) as usize;
+                let (head, rest) = mem::take(&mut buf).split_at_mut(len);
+                let count = self.file.read_at(head, extent.loc as u64 + block)?;
+                buf = rest;
+                seek += count as u64;
+                if count < len {
+                    break;
+                }
+                continue;
+            }
+
+            let Some(lba) = self.block_lba(extent.loc, block)? else {
+                // Unrecorded region of a sparse file
+                seek += buf.write(&[0; 2048][start..end]).unwrap() as u64;
+                continue;
+            };
             if self.buf_lba != Some(lba) {
-                self.file.read_at(&mut self.buf, lba)?;
+                self.buf_len = self
+                    .file
+                    .read_at(&mut self.buf[..block_size as usize], lba)?;
                 self.buf_lba = Some(lba);
             }
 
-            let start = seek % 2048;
-            let end = min(self.size - (seek / 2048) * 2048, 2048);
-            seek += buf.write(&self.buf[start..end]).unwrap();
+            let count = buf.write(&self.buf[start..min(end, self.buf_len)]).unwrap();
+            seek += count as u64;
+            if count < end - start {
+                break;
+            }
         }
 
-        let bytes = seek - self.seek;
-        self.seek = seek;
-        Ok(bytes)
+        Ok((seek - offset) as usize)
     }
 }
 
-impl<T: ISO9660Reader> io::Seek for ISOFileReader<T> {
-    fn seek(&mut self, pos: io::SeekFrom) -> core::result::Result<u64, ReaderError!(T)> {
+pub struct ISOFileReader<T: ISO9660Reader> {
+    data: FileData<T>,
+    #[cfg(feature = "zisofs")]
+    zisofs: Option<ZisofsReader>,
+    seek: u64,
+    size: u64,
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: ISO9660Reader> io::ErrorType for ISOFileReader<T> {
+    type Error = ISOError<T::Error>;
+}
+
+impl<T: ISO9660Reader> ISOFileReader<T> {
+    /// Read at the current position, advancing it
+    pub(crate) fn read_next(&mut self, buf: &mut [u8]) -> Result<usize, ISOError<ReaderError!(T)>> {
+        #[cfg(feature = "zisofs")]
+        if let Some(zisofs) = &mut self.zisofs {
+            let count = zisofs.read_at(&mut self.data, self.seek, buf)?;
+            self.seek += count as u64;
+            return Ok(count);
+        }
+
+        let count = self.data.read_at(self.seek, buf).map_err(ISOError::Io)?;
+        self.seek += count as u64;
+        Ok(count)
+    }
+
+    /// Move to `pos`, returning the new position from the start
+    pub(crate) fn seek_from(&mut self, pos: io::SeekFrom) -> u64 {
         let seek = match pos {
             io::SeekFrom::Start(pos) => pos as i64,
             io::SeekFrom::End(pos) => self.size as i64 + pos,
@@ -126,10 +395,25 @@ impl<T: ISO9660Reader> io::Seek for ISOFileReader<T> {
         };
 
         if seek < 0 {
-            Ok(0) // incorrect shld return error.
+            0 // incorrect shld return error.
         } else {
-            self.seek = seek as usize;
-            Ok(seek as u64)
+            self.seek = seek as u64;
+            seek as u64
         }
     }
 }
+
+impl<T: ISO9660Reader> io::Read for ISOFileReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, <Self as io::ErrorType>::Error> {
+        #[cfg(feature = "std")]
+        return Ok(self.read_next(buf)?);
+        #[cfg(not(feature = "std"))]
+        return self.read_next(buf);
+    }
+}
+
+impl<T: ISO9660Reader> io::Seek for ISOFileReader<T> {
+    fn seek(&mut self, pos: io::SeekFrom) -> Result<u64, <Self as io::ErrorType>::Error> {
+        Ok(self.seek_from(pos))
+    }
+}
@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use time::OffsetDateTime;
+
+use super::DirectoryEntryHeader;
+use crate::parse::system_use_entries;
+use crate::{RockRidge, SystemUseEntries};
+
+/// A Rock Ridge symbolic link
+#[derive(Clone, Debug)]
+pub struct ISOSymlink {
+    pub header: DirectoryEntryHeader,
+    pub identifier: String,
+    /// Target of the link, as recorded by the "SL" entries
+    pub target: String,
+    pub(crate) rock_ridge: RockRidge,
+    pub(crate) system_use: Vec<u8>,
+}
+
+impl ISOSymlink {
+    pub(crate) fn new(
+        header: DirectoryEntryHeader,
+        mut identifier: String,
+        system_use: Vec<u8>,
+        rock_ridge: RockRidge,
+    ) -> ISOSymlink {
+        match &rock_ridge.name {
+            Some(name) => identifier.clone_from(name),
+            None => {
+                if let Some(idx) = identifier.rfind(';') {
+                    identifier.truncate(idx);
+                }
+            }
+        }
+
+        ISOSymlink {
+            header,
+            identifier,
+            target: rock_ridge.symlink.clone().unwrap_or_default(),
+            rock_ridge,
+            system_use,
+        }
+    }
+
+    pub fn time(&self) -> OffsetDateTime {
+        self.header.time
+    }
+
+    pub fn rock_ridge(&self) -> &RockRidge {
+        &self.rock_ridge
+    }
+
+    pub fn system_use_entries(&self) -> SystemUseEntries<'_> {
+        system_use_entries(&self.system_use)
+    }
+}
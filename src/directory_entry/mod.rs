@@ -1,37 +1,67 @@
 // SPDX-License-Identifier: (MIT OR Apache-2.0)
 
 pub use self::isodirectory::ISODirectory;
-pub use self::isofile::ISOFile;
+#[cfg(feature = "async")]
+pub(crate) use self::isodirectory::ISODirectoryIterator;
+pub use self::isofile::{Extent, ISOFile, ISOFileReader};
+pub use self::isosymlink::ISOSymlink;
+pub use self::record::{DirectoryRecord, DirectoryRecords};
 
 use crate::parse::DirectoryEntryReader;
 use crate::parse::{DirectoryEntryHeader, FileFlags};
-use crate::{FileRef, ISO9660Reader, ISOError};
+use crate::{FileRef, ISO9660Reader, ISOError, RockRidge, SystemUseEntries};
 use alloc::string::String;
+use alloc::vec::Vec;
 
 mod isodirectory;
 mod isofile;
+mod isosymlink;
+mod record;
+#[cfg(feature = "zisofs")]
+mod zisofs;
 
 #[derive(Clone, Debug)]
 pub enum DirectoryEntry<T: ISO9660Reader> {
     Directory(ISODirectory<T>),
     File(ISOFile<T>),
+    Symlink(ISOSymlink),
 }
 
 impl<T: ISO9660Reader> DirectoryEntry<T> {
     pub(crate) fn new(
         header: DirectoryEntryHeader,
         identifier: String,
+        system_use: Vec<u8>,
+        rock_ridge: Option<RockRidge>,
         file: FileRef<T>,
         reader: DirectoryEntryReader,
     ) -> Result<Self, ISOError<ReaderError!(T)>> {
         if header.file_flags.contains(FileFlags::DIRECTORY) {
             Ok(DirectoryEntry::Directory(ISODirectory::new(
-                header, identifier, file, reader,
+                header, identifier, system_use, rock_ridge, file, reader,
             )))
         } else {
-            Ok(DirectoryEntry::File(ISOFile::new(
-                header, identifier, file,
-            )?))
+            match rock_ridge {
+                Some(rock_ridge) if rock_ridge.symlink.is_some() => Ok(DirectoryEntry::Symlink(
+                    ISOSymlink::new(header, identifier, system_use, rock_ridge),
+                )),
+                rock_ridge => Ok(DirectoryEntry::File(ISOFile::new(
+                    header, identifier, system_use, rock_ridge, file,
+                )?)),
+            }
+        }
+    }
+
+    /// Whether `find` returns this entry for `identifier`
+    pub(crate) fn matches(&self, identifier: &str) -> bool {
+        if self.header().file_flags.contains(FileFlags::ASSOCIATEDFILE) {
+            return false;
+        }
+        // Rock Ridge names are case sensitive, like POSIX file names
+        if self.rock_ridge().is_some_and(|rr| rr.name.is_some()) {
+            self.identifier() == identifier
+        } else {
+            self.identifier().eq_ignore_ascii_case(identifier)
         }
     }
 
@@ -39,6 +69,7 @@ impl<T: ISO9660Reader> DirectoryEntry<T> {
         match *self {
             DirectoryEntry::Directory(ref dir) => &dir.header,
             DirectoryEntry::File(ref file) => &file.header,
+            DirectoryEntry::Symlink(ref link) => &link.header,
         }
     }
 
@@ -46,6 +77,26 @@ impl<T: ISO9660Reader> DirectoryEntry<T> {
         match *self {
             DirectoryEntry::Directory(ref dir) => &dir.identifier,
             DirectoryEntry::File(ref file) => &file.identifier,
+            DirectoryEntry::Symlink(ref link) => &link.identifier,
+        }
+    }
+
+    /// Rock Ridge metadata, if the entry has any
+    pub fn rock_ridge(&self) -> Option<&RockRidge> {
+        match *self {
+            DirectoryEntry::Directory(ref dir) => dir.rock_ridge.as_ref(),
+            DirectoryEntry::File(ref file) => file.rock_ridge.as_ref(),
+            DirectoryEntry::Symlink(ref link) => Some(&link.rock_ridge),
+        }
+    }
+
+    /// Iterate over the raw SUSP entries recorded for this entry, including
+    /// those in continuation areas. Empty unless the hierarchy uses SUSP.
+    pub fn system_use_entries(&self) -> SystemUseEntries<'_> {
+        match *self {
+            DirectoryEntry::Directory(ref dir) => dir.system_use_entries(),
+            DirectoryEntry::File(ref file) => file.system_use_entries(),
+            DirectoryEntry::Symlink(ref link) => link.system_use_entries(),
         }
     }
 }
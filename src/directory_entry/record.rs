@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+use alloc::str;
+use core::fmt;
+
+use super::isodirectory::next_offset;
+use super::DirectoryEntryHeader;
+use crate::parse::{
+    directory_record, system_use_area, system_use_entries, DirectoryEntryReader, FileFlags,
+};
+use crate::{FileRef, ISOError, SliceReader, Structure, SystemUseEntries};
+
+/// A directory record borrowed from an image in memory, see
+/// `ISODirectory::records`
+#[derive(Clone)]
+pub struct DirectoryRecord<'a> {
+    pub header: DirectoryEntryHeader,
+    /// File identifier as recorded: with the version of files, in UCS-2
+    /// for Joliet, and a 0 or 1 byte for the directory itself and its parent
+    pub identifier: &'a [u8],
+    /// System use area, without the bytes skipped before SUSP entries
+    pub system_use: &'a [u8],
+    file: FileRef<SliceReader<'a>>,
+    reader: DirectoryEntryReader,
+}
+
+impl<'a> fmt::Debug for DirectoryRecord<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("DirectoryRecord")
+            .field("header", &self.header)
+            .field("identifier", &self.identifier)
+            .field("system_use", &self.system_use)
+            .finish()
+    }
+}
+
+impl<'a> DirectoryRecord<'a> {
+    pub fn is_directory(&self) -> bool {
+        self.header.file_flags.contains(FileFlags::DIRECTORY)
+    }
+
+    /// The identifier as `ISODirectory::contents` names entries, without
+    /// Rock Ridge names. `None` for Joliet identifiers, which aren't UTF-8.
+    pub fn name(&self) -> Option<&'a str> {
+        if let DirectoryEntryReader::Joliet = self.reader {
+            return None;
+        }
+        match self.identifier {
+            b"\0" => return Some("."),
+            b"\x01" => return Some(".."),
+            _ => {}
+        }
+
+        let mut name = str::from_utf8(self.identifier).ok()?;
+        if !self.is_directory() {
+            if let Some(idx) = name.rfind(';') {
+                name = &name[..idx];
+            }
+            // Files without an extension have a '.' at the end
+            name = name.strip_suffix('.').unwrap_or(name);
+        }
+        Some(name)
+    }
+
+    /// The data recorded in the extent of the record. For files recorded in
+    /// several extents, this is only one part, and Rock Ridge sparse or
+    /// compressed files are as recorded. `None` if the image ends before.
+    pub fn data(&self) -> Option<&'a [u8]> {
+        let len = self.header.extent_length as u64;
+        let data = self.file.slice(self.header.extent_loc as u64, len);
+        (data.len() as u64 == len).then_some(data)
+    }
+
+    /// The records of the directory this record is for, if it is one
+    pub fn records(&self) -> Option<DirectoryRecords<'a>> {
+        self.is_directory()
+            .then(|| DirectoryRecords::new(&self.header, self.file.clone(), self.reader))
+    }
+
+    /// Iterate over the SUSP entries of the system use area, without those
+    /// in continuation areas. Empty unless the hierarchy uses SUSP.
+    pub fn system_use_entries(&self) -> SystemUseEntries<'a> {
+        match self.reader {
+            DirectoryEntryReader::RockRidge { .. } => system_use_entries(self.system_use),
+            _ => system_use_entries(&[]),
+        }
+    }
+}
+
+/// Iterator over the records of a directory in an image in memory, see
+/// `ISODirectory::records`
+#[derive(Clone)]
+pub struct DirectoryRecords<'a> {
+    file: FileRef<SliceReader<'a>>,
+    reader: DirectoryEntryReader,
+    extent_loc: u32,
+    block_count: u64,
+    next_offset: Option<u64>,
+}
+
+impl<'a> DirectoryRecords<'a> {
+    pub(crate) fn new(
+        header: &DirectoryEntryHeader,
+        file: FileRef<SliceReader<'a>>,
+        reader: DirectoryEntryReader,
+    ) -> DirectoryRecords<'a> {
+        let block_size = file.block_size() as u32;
+        DirectoryRecords {
+            block_count: header.extent_length.div_ceil(block_size) as u64,
+            file,
+            reader,
+            extent_loc: header.extent_loc,
+            next_offset: Some(0),
+        }
+    }
+
+    /// Read the record at byte `offset` of the directory, returning it with
+    /// the offset of the next record
+    #[allow(clippy::type_complexity)]
+    fn read_record_at(
+        &self,
+        offset: u64,
+    ) -> Result<(DirectoryRecord<'a>, Option<u64>), ISOError<ReaderError!(SliceReader<'a>)>> {
+        let block_size = self.file.block_size() as usize;
+        let block_num = offset / block_size as u64;
+        let block_pos = (offset % block_size as u64) as usize;
+        let lba = self.extent_loc as u64 + block_num;
+        let at = |err: ISOError<ReaderError!(SliceReader<'a>)>| {
+            err.at(Structure::DirectoryRecord, lba, block_pos as u32)
+        };
+
+        let block = self.file.slice(lba, block_size as u64);
+        if block.len() != block_size {
+            return Err(ISOError::ReadSize(block_size, block.len()).at(
+                Structure::DirectoryRecord,
+                lba,
+                0,
+            ));
+        }
+
+        let endian = self.file.both_endian();
+        let (_, (header, identifier)) =
+            directory_record(&block[block_pos..], &endian).map_err(|err| at(err.into()))?;
+        if header.length < 34 || block_pos + header.length as usize > block_size {
+            return Err(at(ISOError::InvalidFs("Invalid directory record length")));
+        }
+        self.file.record_mismatches(endian.finish(
+            Structure::DirectoryRecord,
+            lba,
+            block_pos as u32,
+        )?);
+
+        let area = system_use_area(&block[block_pos..]);
+        let system_use = match self.reader {
+            DirectoryEntryReader::RockRidge { susp_skip } => {
+                area.get(susp_skip as usize..).unwrap_or(&[])
+            }
+            _ => area,
+        };
+        let next = next_offset(
+            block,
+            self.block_count,
+            block_num,
+            block_pos + header.length as usize,
+        );
+
+        let record = DirectoryRecord {
+            header,
+            identifier,
+            system_use,
+            file: self.file.clone(),
+            reader: self.reader,
+        };
+        Ok((record, next))
+    }
+}
+
+impl<'a> Iterator for DirectoryRecords<'a> {
+    type Item = Result<DirectoryRecord<'a>, ISOError<ReaderError!(SliceReader<'a>)>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.next_offset?;
+        match self.read_record_at(offset) {
+            Ok((record, next_offset)) => {
+                self.next_offset = next_offset;
+                Some(Ok(record))
+            }
+            Err(err) => {
+                // The records that follow can't be found
+                self.next_offset = None;
+                Some(Err(err))
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+//! Transparent decompression of zisofs files, as recorded by `mkzftree` and
+//! `mkisofs -z`. The file data starts with a header, followed by a table of
+//! block pointers and the zlib compressed blocks.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::iter;
+
+use miniz_oxide::inflate::decompress_slice_iter_to_slice;
+
+use super::isofile::FileData;
+use crate::{CompressedFile, ISO9660Reader, ISOError, Structure};
+
+const MAGIC: [u8; 8] = [0x37, 0xe4, 0x53, 0x96, 0xc9, 0xdb, 0xd6, 0x07];
+
+/// Error for invalid zisofs data at byte `offset` of the recorded data
+fn invalid<T: ISO9660Reader>(
+    data: &FileData<T>,
+    offset: u64,
+    msg: &'static str,
+) -> ISOError<ReaderError!(T)> {
+    let (lba, pos) = data.position(offset);
+    ISOError::InvalidFs(msg).at(Structure::ZisofsData, lba, pos)
+}
+
+pub(super) struct ZisofsReader {
+    compressed: CompressedFile,
+    /// Offsets of the compressed blocks in the file data, plus the end of
+    /// the last one. Read on first use.
+    pointers: Vec<u32>,
+    /// The most recently decompressed block
+    block: Vec<u8>,
+    block_index: Option<u64>,
+}
+
+impl ZisofsReader {
+    pub(super) fn new(compressed: CompressedFile) -> ZisofsReader {
+        ZisofsReader {
+            compressed,
+            pointers: Vec::new(),
+            block: Vec::new(),
+            block_index: None,
+        }
+    }
+
+    fn block_size(&self) -> u64 {
+        1 << self.compressed.block_size_log2
+    }
+
+    /// Read and check the header and the block pointers
+    fn read_pointers<T: ISO9660Reader>(
+        &mut self,
+        data: &mut FileData<T>,
+    ) -> Result<(), ISOError<ReaderError!(T)>> {
+        // Linux only supports 32K, 64K and 128K blocks
+        if !(15..=17).contains(&self.compressed.block_size_log2) {
+            return Err(invalid(data, 0, "Unsupported zisofs block size"));
+        }
+
+        let mut header = [0; 16];
+        let count = data.read_at(0, &mut header).map_err(ISOError::Io)?;
+        if count != header.len() || header[..8] != MAGIC {
+            return Err(invalid(data, 0, "Invalid zisofs header"));
+        }
+
+        let blocks = (self.compressed.size as u64).div_ceil(self.block_size());
+        let mut pointers = vec![0; (blocks as usize + 1) * 4];
+        let offset = self.compressed.header_size as u64 * 4;
+        let count = data.read_at(offset, &mut pointers).map_err(ISOError::Io)?;
+        if count != pointers.len() {
+            return Err(invalid(data, offset, "Truncated zisofs block pointers"));
+        }
+
+        self.pointers = pointers
+            .chunks_exact(4)
+            .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+            .collect();
+        Ok(())
+    }
+
+    /// Decompress block `index` of the file into `self.block`
+    fn read_block<T: ISO9660Reader>(
+        &mut self,
+        data: &mut FileData<T>,
+        index: u64,
+    ) -> Result<(), ISOError<ReaderError!(T)>> {
+        self.block_index = None;
+
+        let start = index * self.block_size();
+        let length = min(self.compressed.size as u64 - start, self.block_size()) as usize;
+        self.block.clear();
+        self.block.resize(length, 0);
+
+        let (begin, end) = (
+            self.pointers[index as usize],
+            self.pointers[index as usize + 1],
+        );
+        if end < begin || (end - begin) as u64 > self.block_size() * 2 {
+            let offset = self.compressed.header_size as u64 * 4 + index * 4;
+            return Err(invalid(data, offset, "Invalid zisofs block pointer"));
+        }
+
+        // A block without data is all zeros
+        if begin != end {
+            let mut input = vec![0; (end - begin) as usize];
+            let count = data
+                .read_at(begin as u64, &mut input)
+                .map_err(ISOError::Io)?;
+            if count != input.len() {
+                return Err(invalid(data, begin as u64, "Truncated zisofs block"));
+            }
+
+            match decompress_slice_iter_to_slice(
+                &mut self.block,
+                iter::once(&input[..]),
+                true,
+                false,
+            ) {
+                Ok(count) if count == length => {}
+                _ => {
+                    return Err(invalid(
+                        data,
+                        begin as u64,
+                        "Invalid zisofs compressed data",
+                    ))
+                }
+            }
+        }
+
+        self.block_index = Some(index);
+        Ok(())
+    }
+
+    /// Read the uncompressed data at `offset` into `buf`
+    pub(super) fn read_at<T: ISO9660Reader>(
+        &mut self,
+        data: &mut FileData<T>,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, ISOError<ReaderError!(T)>> {
+        let size = self.compressed.size as u64;
+        if offset >= size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.pointers.is_empty() {
+            self.read_pointers(data)?;
+        }
+
+        let mut count = 0;
+        while count < buf.len() && offset + (count as u64) < size {
+            let seek = offset + count as u64;
+            let index = seek / self.block_size();
+            if self.block_index != Some(index) {
+                self.read_block(data, index)?;
+            }
+
+            let start = (seek % self.block_size()) as usize;
+            let len = min(buf.len() - count, self.block.len() - start);
+            buf[count..count + len].copy_from_slice(&self.block[start..start + len]);
+            count += len;
+        }
+
+        Ok(count)
+    }
+}
@@ -8,17 +8,175 @@ use core::num::ParseIntError;
 #[derive(Debug)]
 pub enum ISOError<T> {
     Io(T),
+    /// Reading the contents of a file being written by `ISOBuilder` failed
+    Source(crate::io::ErrorKind),
     Utf8(str::Utf8Error),
     InvalidFs(&'static str),
     ParseInt(ParseIntError),
     ReadSize(usize, usize),
     Nom(nom::error::ErrorKind),
+    /// A `nom` parser needed more data than it was given
+    Incomplete(nom::Needed),
+    /// A structure of the image is invalid, see `ParseError`
+    Parse(ParseError),
+    /// An option or operation isn't supported by the API used
+    Unsupported(&'static str),
+    /// A file added to `ISOBuilder`, of this size, is too large for one
+    /// extent. Use `InterchangeLevel::Level3` to record it in several.
+    FileTooLarge(u64),
+}
+
+/// Structure of the image being parsed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Structure {
+    VolumeDescriptor,
+    DirectoryRecord,
+    PathTable,
+    SystemUseEntry,
+    BootCatalog,
+    /// Header, block pointers or blocks of a zisofs compressed file
+    ZisofsData,
+}
+
+/// Error parsing a structure of the image, with its location
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub structure: Structure,
+    /// Logical block where the structure is
+    pub lba: u64,
+    /// Byte offset of the structure in that block
+    pub offset: u32,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A `nom` parser failed
+    Nom(nom::error::ErrorKind),
+    /// The structure continues past the end of the data holding it
+    Incomplete,
+    /// A field doesn't have the expected value
+    Mismatch {
+        field: &'static str,
+        expected: u64,
+        found: u64,
+    },
+    /// The type M copy of a path table differs from the type L copy, at
+    /// block `l_lba`, from the record at `index`: `field` is `found`
+    /// instead of `expected`. The error is located at the type M copy.
+    PathTableCopiesDiffer {
+        l_lba: u64,
+        index: usize,
+        field: &'static str,
+        expected: u64,
+        found: u64,
+    },
+    Utf8(str::Utf8Error),
+    ParseInt(ParseIntError),
+    Invalid(&'static str),
+}
+
+impl<T> ISOError<T> {
+    /// Locate an error found parsing the structure at byte `offset` of
+    /// block `lba`. Errors which already have a location, or aren't about
+    /// the contents of the image, are returned unchanged.
+    pub(crate) fn at(self, structure: Structure, lba: u64, offset: u32) -> ISOError<T> {
+        let kind = match self {
+            ISOError::Nom(kind) => ParseErrorKind::Nom(kind),
+            ISOError::Incomplete(_) => ParseErrorKind::Incomplete,
+            ISOError::InvalidFs(msg) => ParseErrorKind::Invalid(msg),
+            ISOError::Utf8(err) => ParseErrorKind::Utf8(err),
+            ISOError::ParseInt(err) => ParseErrorKind::ParseInt(err),
+            ISOError::ReadSize(expected, found) => ParseErrorKind::Mismatch {
+                field: "bytes read",
+                expected: expected as u64,
+                found: found as u64,
+            },
+            err => return err,
+        };
+        ISOError::Parse(ParseError {
+            structure,
+            lba,
+            offset,
+            kind,
+        })
+    }
+
+    /// Convert the error of the reader with `f`
+    #[cfg(feature = "async")]
+    pub(crate) fn map_io<U>(self, f: impl FnOnce(T) -> ISOError<U>) -> ISOError<U> {
+        match self {
+            ISOError::Io(err) => f(err),
+            ISOError::Source(kind) => ISOError::Source(kind),
+            ISOError::Utf8(err) => ISOError::Utf8(err),
+            ISOError::InvalidFs(msg) => ISOError::InvalidFs(msg),
+            ISOError::ParseInt(err) => ISOError::ParseInt(err),
+            ISOError::ReadSize(expected, found) => ISOError::ReadSize(expected, found),
+            ISOError::Nom(kind) => ISOError::Nom(kind),
+            ISOError::Incomplete(needed) => ISOError::Incomplete(needed),
+            ISOError::Parse(err) => ISOError::Parse(err),
+            ISOError::Unsupported(msg) => ISOError::Unsupported(msg),
+            ISOError::FileTooLarge(size) => ISOError::FileTooLarge(size),
+        }
+    }
+}
+
+impl Display for Structure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Structure::VolumeDescriptor => "volume descriptor",
+            Structure::DirectoryRecord => "directory record",
+            Structure::PathTable => "path table",
+            Structure::SystemUseEntry => "system use entry",
+            Structure::BootCatalog => "El Torito boot catalog",
+            Structure::ZisofsData => "zisofs data",
+        })
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseErrorKind::Nom(ref err) => write!(f, "parse error: {:?}", err),
+            ParseErrorKind::Incomplete => write!(f, "truncated"),
+            ParseErrorKind::Mismatch {
+                field,
+                expected,
+                found,
+            } => write!(f, "expected {} {}, found {}", field, expected, found),
+            ParseErrorKind::PathTableCopiesDiffer {
+                l_lba,
+                index,
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "differs from the type L copy at block {} from record {}: expected {} {}, found {}",
+                l_lba, index, field, expected, found
+            ),
+            ParseErrorKind::Utf8(ref err) => write!(f, "UTF8 error: {}", err),
+            ParseErrorKind::ParseInt(ref err) => write!(f, "int parse error: {}", err),
+            ParseErrorKind::Invalid(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid {} at block {}, offset {}: {}",
+            self.structure, self.lba, self.offset, self.kind
+        )
+    }
 }
 
 impl<T: core::fmt::Debug> Display for ISOError<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ISOError::Io(ref err) => write!(f, "IO error: {:?}", err),
+            ISOError::Source(kind) => write!(f, "Error reading file contents: {:?}", kind),
             ISOError::Utf8(ref err) => write!(f, "UTF8 error: {}", err),
             ISOError::InvalidFs(msg) => write!(f, "Invalid ISO9660: {}", msg),
             ISOError::ParseInt(ref err) => write!(f, "Int parse error: {}", err),
@@ -28,6 +186,14 @@ impl<T: core::fmt::Debug> Display for ISOError<T> {
                 size, size_read
             ),
             ISOError::Nom(ref err) => write!(f, "Parse error: {:?}", err),
+            ISOError::Incomplete(needed) => write!(f, "Parse error: incomplete, {:?}", needed),
+            ISOError::Parse(ref err) => write!(f, "{}", err),
+            ISOError::Unsupported(msg) => write!(f, "Unsupported: {}", msg),
+            ISOError::FileTooLarge(size) => write!(
+                f,
+                "File of {} bytes too large for one extent, below interchange level 3",
+                size
+            ),
         }
     }
 }
@@ -38,11 +204,41 @@ impl<T: core::error::Error> Error for ISOError<T> {
             ISOError::Io(ref err) => Some(err),
             ISOError::Utf8(ref err) => Some(err),
             ISOError::ParseInt(ref err) => Some(err),
+            ISOError::Parse(ParseError {
+                kind: ParseErrorKind::Utf8(ref err),
+                ..
+            }) => Some(err),
+            ISOError::Parse(ParseError {
+                kind: ParseErrorKind::ParseInt(ref err),
+                ..
+            }) => Some(err),
             _ => None,
         }
     }
 }
 
+/// Allows `ISOError` to be used as the error type of `ISOFileReader` and
+/// `AsyncISOFileReader`
+#[cfg(any(not(feature = "std"), feature = "async"))]
+impl<T: embedded_io::Error> embedded_io::Error for ISOError<T> {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match *self {
+            ISOError::Io(ref err) => err.kind(),
+            _ => embedded_io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ISOError<std::io::Error>> for std::io::Error {
+    fn from(err: ISOError<std::io::Error>) -> std::io::Error {
+        match err {
+            ISOError::Io(err) => err,
+            err => std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
 macro_rules! impl_from_error {
     ($t:ty, $e:expr) => {
         impl<T> From<$t> for ISOError<T> {
@@ -58,9 +254,9 @@ impl_from_error!(ParseIntError, ISOError::ParseInt);
 
 impl<T> From<nom::Err<nom::error::Error<&[u8]>>> for ISOError<T> {
     fn from(err: nom::Err<nom::error::Error<&[u8]>>) -> ISOError<T> {
-        ISOError::Nom(match err {
-            nom::Err::Error(e) | nom::Err::Failure(e) => e.code,
-            nom::Err::Incomplete(_) => panic!(), // XXX
-        })
+        match err {
+            nom::Err::Error(e) | nom::Err::Failure(e) => ISOError::Nom(e.code),
+            nom::Err::Incomplete(needed) => ISOError::Incomplete(needed),
+        }
     }
 }
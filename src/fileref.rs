@@ -1,37 +1,407 @@
 // SPDX-License-Identifier: (MIT OR Apache-2.0)
 
+use crate::cache::SectorCache;
 use crate::io::{ErrorType, Read, Seek, SeekFrom};
-use alloc::rc::Rc;
-use core::cell::RefCell;
+use crate::parse::BothEndian;
+use crate::shared::{Shared, SharedRef};
+use crate::{
+    BothEndianMismatch, BothEndianPolicy, CacheStats, ISO9660Options, Warning, WarningKind,
+};
+use alloc::vec::Vec;
+use core::cmp::{max, min};
+use core::convert::{TryFrom, TryInto};
+use core::mem;
 
 pub trait ISO9660Reader: ErrorType {
-    /// Read the block(s) at a given LBA (logical block address)
+    /// Read the 2048 byte sector(s) at a given LBA (logical block address)
     fn read_at(&mut self, buf: &mut [u8], lba: u64) -> Result<usize, ReaderError!(Self)>;
+
+    /// Largest read, in bytes, the reader prefers. Reads of contiguous
+    /// sectors are split in reads of this size, rounded down to whole
+    /// sectors. Defaults to no limit.
+    fn max_transfer_size(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Whether the reader reads through `read_at_shared`. It's then kept
+    /// without a lock, so that reads don't wait for each other. Defaults
+    /// to `false`. See `PositionalReader`.
+    fn reads_shared(&self) -> bool {
+        false
+    }
+
+    /// Read like `read_at`, through a shared reference. Only called if
+    /// `reads_shared` returns `true`.
+    fn read_at_shared(&self, _buf: &mut [u8], _lba: u64) -> Result<usize, ReaderError!(Self)> {
+        Ok(0)
+    }
 }
 
 impl<T: Read + Seek> ISO9660Reader for T {
     fn read_at(&mut self, buf: &mut [u8], lba: u64) -> Result<usize, ReaderError!(Self)> {
         self.seek(SeekFrom::Start(lba * 2048))?;
-        self.read(buf)
+        let mut count = 0;
+        while count < buf.len() {
+            match self.read(&mut buf[count..])? {
+                0 => break,
+                read => count += read,
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// Reads sectors at any position through a shared reference, like `pread`
+pub trait ISO9660ReadAt: ErrorType {
+    /// Read the 2048 byte sector(s) at a given LBA (logical block address)
+    fn read_at(&self, buf: &mut [u8], lba: u64) -> Result<usize, ReaderError!(Self)>;
+
+    /// See `ISO9660Reader::max_transfer_size`
+    fn max_transfer_size(&self) -> usize {
+        usize::MAX
+    }
+}
+
+#[cfg(all(feature = "std", any(unix, windows)))]
+impl ISO9660ReadAt for std::fs::File {
+    fn read_at(&self, buf: &mut [u8], lba: u64) -> Result<usize, ReaderError!(Self)> {
+        #[cfg(unix)]
+        use std::os::unix::fs::FileExt;
+        #[cfg(windows)]
+        use std::os::windows::fs::FileExt;
+
+        let mut count = 0;
+        while count < buf.len() {
+            let pos = lba * 2048 + count as u64;
+            #[cfg(unix)]
+            let read = FileExt::read_at(self, &mut buf[count..], pos)?;
+            #[cfg(windows)]
+            let read = self.seek_read(&mut buf[count..], pos)?;
+            if read == 0 {
+                break;
+            }
+            count += read;
+        }
+        Ok(count)
+    }
+}
+
+impl ISO9660ReadAt for &[u8] {
+    fn read_at(&self, buf: &mut [u8], lba: u64) -> Result<usize, ReaderError!(Self)> {
+        let data = usize::try_from(lba)
+            .ok()
+            .and_then(|lba| lba.checked_mul(2048))
+            .and_then(|pos| self.get(pos..))
+            .unwrap_or(&[]);
+        let count = min(buf.len(), data.len());
+        buf[..count].copy_from_slice(&data[..count]);
+        Ok(count)
+    }
+}
+
+impl ISO9660ReadAt for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], lba: u64) -> Result<usize, ReaderError!(Self)> {
+        ISO9660ReadAt::read_at(&self.as_slice(), buf, lba)
+    }
+}
+
+/// Reader of an `ISO9660ReadAt`. Reads don't need exclusive access to the
+/// reader: with the `sync` feature, threads reading files of the same image
+/// don't wait for each other.
+pub struct PositionalReader<R>(pub R);
+
+#[cfg(not(feature = "std"))]
+impl<R: ISO9660ReadAt> ErrorType for PositionalReader<R> {
+    type Error = R::Error;
+}
+
+impl<R: ISO9660ReadAt> ISO9660Reader for PositionalReader<R> {
+    fn read_at(&mut self, buf: &mut [u8], lba: u64) -> Result<usize, ReaderError!(Self)> {
+        self.0.read_at(buf, lba)
+    }
+
+    fn max_transfer_size(&self) -> usize {
+        self.0.max_transfer_size()
+    }
+
+    fn reads_shared(&self) -> bool {
+        true
+    }
+
+    fn read_at_shared(&self, buf: &mut [u8], lba: u64) -> Result<usize, ReaderError!(Self)> {
+        self.0.read_at(buf, lba)
+    }
+}
+
+/// Reader of an image in memory, `PositionalReader(image)`. Files and
+/// directory records can borrow their data from it, see `ISOFile::as_slice`
+/// and `ISODirectory::records`.
+pub type SliceReader<'a> = PositionalReader<&'a [u8]>;
+
+/// How `FileRef` keeps the reader
+enum Backend<T> {
+    /// Behind a lock, for readers reading through `&mut self`
+    Locked(Shared<T>),
+    /// Without a lock, for readers reading through `read_at_shared`
+    Unlocked(SharedRef<T>),
+}
+
+impl<T> Clone for Backend<T> {
+    fn clone(&self) -> Backend<T> {
+        match self {
+            Backend::Locked(reader) => Backend::Locked(reader.clone()),
+            Backend::Unlocked(reader) => Backend::Unlocked(reader.clone()),
+        }
     }
 }
 
-// TODO: Figure out if sane API possible without Rc/RefCell
-pub(crate) struct FileRef<T: ISO9660Reader>(Rc<RefCell<T>>);
+pub(crate) struct FileRef<T: ISO9660Reader> {
+    reader: Backend<T>,
+    /// See `ISO9660Reader::max_transfer_size`
+    max_transfer_size: usize,
+    /// Logical block size of the volume: 512, 1024 or 2048
+    block_size: u16,
+    /// Warnings collected in lenient mode; `None` in strict mode
+    warnings: Option<Shared<Vec<Warning>>>,
+    both_endian: BothEndianPolicy,
+    /// Both-endian numbers whose values differ, in the structures read
+    mismatches: Shared<Vec<BothEndianMismatch>>,
+    /// Sectors read last; `None` if caching is disabled
+    cache: Option<Shared<SectorCache>>,
+}
 
 impl<T: ISO9660Reader> Clone for FileRef<T> {
     fn clone(&self) -> FileRef<T> {
-        FileRef(self.0.clone())
+        FileRef {
+            reader: self.reader.clone(),
+            max_transfer_size: self.max_transfer_size,
+            block_size: self.block_size,
+            warnings: self.warnings.clone(),
+            both_endian: self.both_endian,
+            mismatches: self.mismatches.clone(),
+            cache: self.cache.clone(),
+        }
     }
 }
 
 impl<T: ISO9660Reader> FileRef<T> {
-    pub fn new(reader: T) -> FileRef<T> {
-        FileRef(Rc::new(RefCell::new(reader)))
+    pub fn new(reader: T, block_size: u16, options: &ISO9660Options) -> FileRef<T> {
+        let max_transfer_size = reader.max_transfer_size();
+        let reader = if reader.reads_shared() {
+            Backend::Unlocked(SharedRef::new(reader))
+        } else {
+            Backend::Locked(Shared::new(reader))
+        };
+        FileRef {
+            reader,
+            max_transfer_size,
+            block_size,
+            warnings: options.lenient.then(Default::default),
+            both_endian: options.both_endian,
+            mismatches: Default::default(),
+            cache: (options.cache > 0).then(|| Shared::new(SectorCache::new(options.cache))),
+        }
+    }
+
+    pub fn lenient(&self) -> bool {
+        self.warnings.is_some()
+    }
+
+    /// Record a warning, if in lenient mode
+    pub fn warn(&self, lba: u64, offset: u32, kind: WarningKind) {
+        if let Some(warnings) = &self.warnings {
+            warnings.lock().push(Warning { lba, offset, kind });
+        }
+    }
+
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.warnings
+            .as_ref()
+            .map_or_else(Vec::new, |warnings| warnings.lock().clone())
+    }
+
+    pub fn take_warnings(&self) -> Vec<Warning> {
+        self.warnings
+            .as_ref()
+            .map_or_else(Vec::new, |warnings| mem::take(&mut *warnings.lock()))
+    }
+
+    /// Parser of both-endian numbers, for a structure
+    pub fn both_endian(&self) -> BothEndian {
+        BothEndian::new(self.both_endian)
+    }
+
+    /// Record the mismatches found in a structure, unless they already
+    /// were, when reading it before
+    pub fn record_mismatches(&self, found: Vec<BothEndianMismatch>) {
+        let mut mismatches = self.mismatches.lock();
+        for mismatch in found {
+            if !mismatches.contains(&mismatch) {
+                mismatches.push(mismatch);
+            }
+        }
+    }
+
+    pub fn mismatches(&self) -> Vec<BothEndianMismatch> {
+        self.mismatches.lock().clone()
+    }
+
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.lock().stats())
     }
 
-    /// Read the block(s) at a given LBA (logical block address)
+    pub fn block_size(&self) -> u16 {
+        self.block_size
+    }
+
+    /// Read the logical block(s) at a given LBA (logical block address)
     pub fn read_at(&self, buf: &mut [u8], lba: u64) -> Result<usize, ReaderError!(T)> {
-        (*self.0).borrow_mut().read_at(buf, lba)
+        if self.block_size == 2048 {
+            return self.read_sector_at(buf, lba);
+        }
+
+        // Smaller blocks are read from the sectors containing them
+        let offset = lba * self.block_size as u64;
+        let mut sector = [0; 2048];
+        let mut count = 0;
+        while count < buf.len() {
+            let pos = offset + count as u64;
+            let start = (pos % 2048) as usize;
+            // Whole sectors are read straight into `buf`
+            let whole = (buf.len() - count) / 2048 * 2048;
+            if start == 0 && whole > 0 {
+                let read = self.read_sector_at(&mut buf[count..count + whole], pos / 2048)?;
+                count += read;
+                if read < whole {
+                    break;
+                }
+                continue;
+            }
+            let read = self.read_sector_at(&mut sector, pos / 2048)?;
+            let len = min(read.saturating_sub(start), buf.len() - count);
+            buf[count..count + len].copy_from_slice(&sector[start..start + len]);
+            count += len;
+            if read < 2048 {
+                break;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Read the 2048 byte sector(s) at `sector`, regardless of the logical
+    /// block size. In lenient mode, what can't be read is filled with zeros.
+    pub fn read_sector_at(&self, buf: &mut [u8], sector: u64) -> Result<usize, ReaderError!(T)> {
+        let result = self.read_sectors(buf, sector);
+        if !self.lenient() {
+            return result;
+        }
+
+        let (count, kind) = match result {
+            Ok(count) if count >= buf.len() => return Ok(count),
+            Ok(count) => (count, WarningKind::ShortRead),
+            Err(_) => (0, WarningKind::ReadFailed),
+        };
+        buf[count..].fill(0);
+        let pos = sector * 2048 + count as u64;
+        let block_size = self.block_size as u64;
+        self.warn(pos / block_size, (pos % block_size) as u32, kind);
+        Ok(buf.len())
+    }
+
+    /// Read `buf` in as few reads as the reader allows, see
+    /// `ISO9660Reader::max_transfer_size`
+    fn read_sectors(&self, buf: &mut [u8], sector: u64) -> Result<usize, ReaderError!(T)> {
+        let max_size = max(self.max_transfer_size / 2048, 1) * 2048;
+        let mut count = 0;
+        for chunk in buf.chunks_mut(max_size) {
+            let read = self.read_cached(chunk, sector + count as u64 / 2048)?;
+            count += read;
+            if read < chunk.len() {
+                break;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Read from the cache, if enabled, one sector at a time. The runs of
+    /// sectors which aren't cached are each read in one read, and cached
+    /// when read whole.
+    fn read_cached(&self, buf: &mut [u8], sector: u64) -> Result<usize, ReaderError!(T)> {
+        let Some(cache) = &self.cache else {
+            return self.read_reader(buf, sector);
+        };
+
+        let mut count = 0;
+        while count < buf.len() {
+            let start = sector + count as u64 / 2048;
+            let rest = &mut buf[count..];
+            if let Some(data) = cache.lock().get(start) {
+                let len = min(rest.len(), 2048);
+                rest[..len].copy_from_slice(&data[..len]);
+                count += len;
+                continue;
+            }
+
+            // Whole sectors up to the next cached one
+            let sectors = {
+                let mut cache = cache.lock();
+                let sectors = (1..rest.len() as u64 / 2048)
+                    .find(|&i| cache.contains(start + i))
+                    .unwrap_or(rest.len() as u64 / 2048);
+                cache.count_misses(sectors.saturating_sub(1));
+                sectors as usize
+            };
+            let (len, read) = if sectors > 0 {
+                let len = sectors * 2048;
+                (len, self.read_reader(&mut rest[..len], start)?)
+            } else {
+                // Less than a sector is left: read it whole, to cache it
+                let mut data = [0; 2048];
+                let read = self.read_reader(&mut data, start)?;
+                let len = rest.len();
+                if read == 2048 {
+                    cache.lock().insert(start, &data);
+                }
+                rest.copy_from_slice(&data[..len]);
+                (len, min(read, len))
+            };
+
+            // Sectors before the last `capacity` would be evicted anyway
+            let mut cache = cache.lock();
+            let skip = (read / 2048).saturating_sub(cache.stats().capacity);
+            for (i, data) in rest[..read].chunks_exact(2048).enumerate().skip(skip) {
+                cache.insert(start + i as u64, data.try_into().unwrap());
+            }
+            count += read;
+            if read < len {
+                break;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Read from the reader, without locking it if it reads through a
+    /// shared reference
+    fn read_reader(&self, buf: &mut [u8], sector: u64) -> Result<usize, ReaderError!(T)> {
+        match &self.reader {
+            Backend::Locked(reader) => reader.lock().read_at(buf, sector),
+            Backend::Unlocked(reader) => reader.read_at_shared(buf, sector),
+        }
+    }
+}
+
+impl<'a> FileRef<SliceReader<'a>> {
+    /// The `len` bytes at logical block `lba` of the image, shorter if the
+    /// image ends before, like `read_at`
+    pub fn slice(&self, lba: u64, len: u64) -> &'a [u8] {
+        let image = match &self.reader {
+            Backend::Locked(reader) => reader.read().0,
+            Backend::Unlocked(reader) => reader.0,
+        };
+        let start = lba.saturating_mul(self.block_size as u64);
+        let end = start.saturating_add(len);
+        let clamp =
+            |pos: u64| usize::try_from(pos).map_or(image.len(), |pos| min(pos, image.len()));
+        &image[clamp(start)..clamp(end)]
     }
 }
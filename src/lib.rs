@@ -14,101 +14,309 @@ extern crate time;
 // re-exports, etc..
 pub mod io;
 
-pub use directory_entry::{DirectoryEntry, ISODirectory};
-pub use error::ISOError;
+#[cfg(feature = "async")]
+pub use asynch::{
+    AsyncDirectoryEntries, AsyncDirectoryEntry, AsyncISO9660, AsyncISO9660Reader,
+    AsyncISODirectory, AsyncISOFile, AsyncISOFileReader,
+};
+#[cfg(feature = "joliet")]
+pub use builder::JolietNames;
+pub use builder::{BuilderDirectory, FileSource, ISOBuilder, InterchangeLevel, ReaderSource};
+pub use cache::CacheStats;
+pub use directory_entry::{
+    DirectoryEntry, DirectoryRecord, DirectoryRecords, Extent, ISODirectory, ISOFile,
+    ISOFileReader, ISOSymlink,
+};
+pub use error::{ISOError, ParseError, ParseErrorKind, Structure};
 pub(crate) use fileref::FileRef;
-pub use fileref::ISO9660Reader;
+pub use fileref::{ISO9660ReadAt, ISO9660Reader, PositionalReader, SliceReader};
+pub use options::{Hierarchy, ISO9660Options};
+pub use parse::{
+    BootCatalog, BootEntry, BootRecordDescriptor, BootSection, BothEndianMismatch,
+    BothEndianPolicy, CompressedFile, DeviceNumber, Emulation, Extension, PathTable,
+    PathTableRecord, PathTableType, Platform, PosixAttributes, PrimaryVolumeDescriptor, RockRidge,
+    RockRidgeFlags, SparseFile, SupplementaryVolumeDescriptor, SystemUseEntries, SystemUseEntry,
+    Timestamps, VolumeDescriptor, VolumeDescriptorSetEntry, VolumeInfo, VolumePartitionDescriptor,
+};
+#[cfg(feature = "tokio")]
+pub use tokio_io::TokioReader;
+pub use verify::{Finding, FindingKind};
+pub use warning::{Warning, WarningKind};
 
+#[cfg(feature = "async")]
+mod asynch;
+mod builder;
+mod cache;
 mod directory_entry;
 mod error;
 mod fileref;
+mod options;
 mod parse;
+mod shared;
+#[cfg(feature = "tokio")]
+mod tokio_io;
+mod verify;
+mod warning;
 
-use parse::{DirectoryEntryReader, VolumeDescriptor};
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::min;
+use parse::{BothEndian, DirectoryEntryReader, EL_TORITO_IDENTIFIER};
 
 pub struct ISO9660<T: ISO9660Reader> {
-    _file: FileRef<T>,
+    file: FileRef<T>,
+    hierarchy: Hierarchy,
     pub root: ISODirectory<T>,
-    primary: VolumeDescriptor,
+    rock_ridge_root: Option<ISODirectory<T>>,
+    descriptors: Vec<VolumeDescriptorSetEntry>,
+    primary: PrimaryVolumeDescriptor,
+    #[cfg(feature = "joliet")]
+    joliet: Option<SupplementaryVolumeDescriptor>,
+    extensions: Vec<Extension>,
+    boot_catalog_lba: Option<u32>,
+    /// Path table used by `open`, see `set_path_table_lookup`
+    path_table: Option<PathTable>,
 }
 
 macro_rules! primary_prop_str {
     ($name:ident) => {
         pub fn $name(&self) -> &str {
-            if let VolumeDescriptor::Primary(primary) = &self.primary {
-                &primary.$name
-            } else {
-                unreachable!()
-            }
+            &self.primary.info.$name
         }
     };
 }
 
 impl<T: ISO9660Reader> ISO9660<T> {
-    pub fn new(mut reader: T) -> Result<ISO9660<T>, ISOError<ReaderError!(T)>> {
+    /// Open an image, with the default options: the best hierarchy is
+    /// chosen as `root`
+    pub fn new(reader: T) -> Result<ISO9660<T>, ISOError<ReaderError!(T)>> {
+        ISO9660::with_options(reader, &ISO9660Options::default())
+    }
+
+    pub fn with_options(
+        mut reader: T,
+        options: &ISO9660Options,
+    ) -> Result<ISO9660<T>, ISOError<ReaderError!(T)>> {
         let mut buf: [u8; 2048] = [0; 2048];
-        let mut root = None;
-        let mut primary = None;
-        let mut entry_reader = DirectoryEntryReader::Primary;
+        let mut descriptors = Vec::new();
+        let mut mismatches = Vec::new();
 
         // Skip the "system area"
         let mut lba = 16;
 
         // Read volume descriptors
         loop {
-            let count = reader.read_at(&mut buf, lba).map_err(ISOError::Io)?;
+            let count = reader.read_at(&mut buf, lba as u64).map_err(ISOError::Io)?;
 
             if count != 2048 {
-                return Err(ISOError::ReadSize(2048, count));
+                return Err(ISOError::ReadSize(2048, count).at(
+                    Structure::VolumeDescriptor,
+                    lba as u64,
+                    0,
+                ));
             }
 
-            let descriptor = VolumeDescriptor::parse(&buf)?;
-            match &descriptor {
-                Some(VolumeDescriptor::Primary(primary_descriptor)) => {
-                    if primary_descriptor.logical_block_size != 2048 {
-                        // This is almost always the case, but technically
-                        // not guaranteed by the standard.
-                        // TODO: Implement this
-                        return Err(ISOError::InvalidFs("Block size not 2048"));
-                    }
+            let endian = BothEndian::new(options.both_endian);
+            let descriptor = VolumeDescriptor::parse(&buf, &endian)
+                .map_err(|err| err.at(Structure::VolumeDescriptor, lba as u64, 0))?;
+            mismatches.extend(endian.finish(Structure::VolumeDescriptor, lba as u64, 0)?);
+            let terminator = matches!(descriptor, VolumeDescriptor::VolumeDescriptorSetTerminator);
+            descriptors.push(VolumeDescriptorSetEntry { lba, descriptor });
+            if terminator {
+                break;
+            }
 
-                    root = Some((
-                        primary_descriptor.root_directory_entry.clone(),
-                        primary_descriptor.root_directory_entry_identifier.clone(),
-                    ));
-                    primary = descriptor;
+            lba += 1;
+        }
+
+        let mut primary = None;
+        #[cfg(feature = "joliet")]
+        let mut joliet = None;
+        let mut boot_catalog_lba = None;
+        for entry in &descriptors {
+            match &entry.descriptor {
+                VolumeDescriptor::Primary(primary_descriptor) if primary.is_none() => {
+                    primary = Some((entry.lba, primary_descriptor.clone()));
                 }
                 #[cfg(feature = "joliet")]
-                Some(VolumeDescriptor::SupplementaryVolumeDescriptor(svd)) => {
-                    if svd.is_joliet {
-                        root = Some((
-                            svd.root_directory_entry.clone(),
-                            svd.root_directory_entry_identifier.clone(),
-                        ));
-                        entry_reader = DirectoryEntryReader::Joliet;
-                    }
+                VolumeDescriptor::SupplementaryVolumeDescriptor(svd)
+                    if svd.is_joliet && joliet.is_none() =>
+                {
+                    joliet = Some(svd.clone());
+                }
+                VolumeDescriptor::BootRecord(boot_record)
+                    if boot_record.boot_system_identifier.trim_end_matches('\0')
+                        == EL_TORITO_IDENTIFIER =>
+                {
+                    // The boot catalog pointer is the start of the boot
+                    // system use area
+                    let pointer = &boot_record.data[..4];
+                    boot_catalog_lba = Some(u32::from_le_bytes([
+                        pointer[0], pointer[1], pointer[2], pointer[3],
+                    ]));
                 }
-                Some(VolumeDescriptor::VolumeDescriptorSetTerminator) => break,
                 _ => {}
             }
+        }
 
-            lba += 1;
+        let Some((primary_lba, primary)) = primary else {
+            // Located at the set terminator, where it should have been found
+            return Err(ISOError::InvalidFs("No primary volume descriptor").at(
+                Structure::VolumeDescriptor,
+                lba as u64,
+                0,
+            ));
+        };
+        // Almost always 2048, but the standard allows any power of two from
+        // 512 up to the sector size
+        let block_size = primary.info.logical_block_size;
+        if !matches!(block_size, 512 | 1024 | 2048) {
+            return Err(ISOError::InvalidFs("Unsupported logical block size").at(
+                Structure::VolumeDescriptor,
+                primary_lba as u64,
+                0,
+            ));
         }
 
-        if let (Some(root), Some(primary)) = (root, primary) {
-            let file = FileRef::new(reader);
-            let file2 = file.clone();
-            Ok(ISO9660 {
-                _file: file,
-                root: ISODirectory::new(root.0, root.1, file2, entry_reader),
-                primary,
-            })
-        } else {
-            Err(ISOError::InvalidFs("No primary volume descriptor"))
+        let file = FileRef::new(reader, block_size, options);
+        file.record_mismatches(mismatches);
+        let primary_root = ISODirectory::new(
+            primary.root_directory_entry.clone(),
+            primary.root_directory_entry_identifier.clone(),
+            Vec::new(),
+            None,
+            file.clone(),
+            DirectoryEntryReader::Primary,
+        );
+
+        let mut rock_ridge_root = None;
+        let mut extensions = Vec::new();
+        if let Some(susp_skip) = primary_root.susp_skip()? {
+            let mut root = primary_root.clone();
+            root.reader = DirectoryEntryReader::RockRidge { susp_skip };
+
+            // The root directory's own system use entries, including the
+            // extensions registered with "ER", are recorded in its "."
+            // entry.
+            let mut block = [0; 2048];
+            if let (DirectoryEntry::Directory(dot), _) =
+                root.read_entry_at(&mut block, &mut None, 0)?
+            {
+                extensions = dot
+                    .system_use_entries()
+                    .filter_map(|entry| Extension::parse(&entry))
+                    .collect();
+                rock_ridge_root = Some(dot);
+            }
         }
+
+        let mut fs = ISO9660 {
+            file,
+            hierarchy: Hierarchy::Primary,
+            root: primary_root,
+            rock_ridge_root,
+            descriptors,
+            primary,
+            #[cfg(feature = "joliet")]
+            joliet,
+            extensions,
+            boot_catalog_lba,
+            path_table: None,
+        };
+
+        // Like Linux, prefer Rock Ridge over Joliet if both are present
+        let hierarchy = match options.hierarchy {
+            Hierarchy::Auto if fs.rock_ridge_root.is_some() => Hierarchy::RockRidge,
+            #[cfg(feature = "joliet")]
+            Hierarchy::Auto if fs.joliet.is_some() => Hierarchy::Joliet,
+            Hierarchy::Auto => Hierarchy::Primary,
+            hierarchy => hierarchy,
+        };
+        fs.root = match hierarchy {
+            Hierarchy::Auto | Hierarchy::Primary => fs.primary_root(),
+            Hierarchy::RockRidge => fs
+                .rock_ridge_root()
+                .ok_or(ISOError::InvalidFs("No Rock Ridge hierarchy"))?,
+            #[cfg(feature = "joliet")]
+            Hierarchy::Joliet => fs
+                .joliet_root()
+                .ok_or(ISOError::InvalidFs("No Joliet hierarchy"))?,
+        };
+        fs.hierarchy = hierarchy;
+
+        fs.set_path_table_lookup(options.path_table_lookup)?;
+        Ok(fs)
+    }
+
+    /// Problems worked around so far in lenient mode, in the order they
+    /// were found. Always empty in strict mode.
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.file.warnings()
+    }
+
+    /// Like `warnings`, but also clears them
+    pub fn take_warnings(&self) -> Vec<Warning> {
+        self.file.take_warnings()
+    }
+
+    /// Both-endian numbers whose little and big endian values differ, in
+    /// the volume descriptors and the directory records read so far, in the
+    /// order they were found. Images written by buggy mastering software
+    /// often have some. See `ISO9660Options::both_endian`.
+    pub fn both_endian_mismatches(&self) -> Vec<BothEndianMismatch> {
+        self.file.mismatches()
+    }
+
+    /// Statistics of the sector cache, or `None` if it is disabled. See
+    /// `ISO9660Options::cache`.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.file.cache_stats()
+    }
+
+    /// The hierarchy of `root`
+    pub fn hierarchy(&self) -> Hierarchy {
+        self.hierarchy
+    }
+
+    /// Root of the primary hierarchy, without Rock Ridge extensions
+    pub fn primary_root(&self) -> ISODirectory<T> {
+        ISODirectory::new(
+            self.primary.root_directory_entry.clone(),
+            self.primary.root_directory_entry_identifier.clone(),
+            Vec::new(),
+            None,
+            self.file.clone(),
+            DirectoryEntryReader::Primary,
+        )
+    }
+
+    /// Root of the primary hierarchy with Rock Ridge extensions, if the
+    /// image has them
+    pub fn rock_ridge_root(&self) -> Option<ISODirectory<T>> {
+        self.rock_ridge_root.clone()
+    }
+
+    /// Root of the Joliet hierarchy, if the image has one
+    #[cfg(feature = "joliet")]
+    pub fn joliet_root(&self) -> Option<ISODirectory<T>> {
+        let joliet = self.joliet.as_ref()?;
+        Some(ISODirectory::new(
+            joliet.root_directory_entry.clone(),
+            joliet.root_directory_entry_identifier.clone(),
+            Vec::new(),
+            None,
+            self.file.clone(),
+            DirectoryEntryReader::Joliet,
+        ))
     }
 
     pub fn open(&self, path: &str) -> Result<Option<DirectoryEntry<T>>, ISOError<ReaderError!(T)>> {
+        if let Some(path_table) = &self.path_table {
+            return self.open_with_path_table(path_table, path);
+        }
+
         // TODO: avoid clone()
         let mut entry = DirectoryEntry::Directory(self.root.clone());
         for segment in path.split('/').filter(|x| !x.is_empty()) {
@@ -126,8 +334,346 @@ impl<T: ISO9660Reader> ISO9660<T> {
         Ok(Some(entry))
     }
 
+    /// Look up the parent directory of `path` in `path_table`, then the
+    /// last component of `path` in it
+    fn open_with_path_table(
+        &self,
+        path_table: &PathTable,
+        path: &str,
+    ) -> Result<Option<DirectoryEntry<T>>, ISOError<ReaderError!(T)>> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() {
+            return Ok(Some(DirectoryEntry::Directory(self.root.clone())));
+        }
+
+        let Some(record) = path_table.find(parent) else {
+            return Ok(None);
+        };
+        let parent = if record.extent_loc == self.root.header.extent_loc {
+            self.root.clone()
+        } else {
+            self.root
+                .directory_at(record.extent_loc, record.identifier.clone())?
+        };
+        parent.find(name)
+    }
+
+    /// Like `open`, but follows Rock Ridge symbolic links, both for the
+    /// last component of `path` and for the directories leading to it.
+    pub fn open_follow_symlinks(
+        &self,
+        path: &str,
+    ) -> Result<Option<DirectoryEntry<T>>, ISOError<ReaderError!(T)>> {
+        // The directories leading to the current one, starting at the root
+        let mut dirs = vec![self.root.clone()];
+        let mut segments: Vec<String> = path
+            .split('/')
+            .rev()
+            .filter(|x| !x.is_empty())
+            .map(String::from)
+            .collect();
+        let mut links = 0;
+
+        while let Some(segment) = segments.pop() {
+            match segment.as_str() {
+                "." => continue,
+                ".." => {
+                    if dirs.len() > 1 {
+                        dirs.pop();
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            let parent = dirs.last().unwrap();
+            match parent.find_located(&segment)? {
+                Some((DirectoryEntry::Directory(dir), _, _)) => dirs.push(dir),
+                Some((DirectoryEntry::Symlink(link), lba, offset)) => {
+                    // Same limit as Linux's MAXSYMLINKS
+                    links += 1;
+                    if links > 40 {
+                        return Err(ISOError::InvalidFs("Too many levels of symbolic links").at(
+                            Structure::DirectoryRecord,
+                            lba,
+                            offset,
+                        ));
+                    }
+                    if link.target.starts_with('/') {
+                        dirs.truncate(1);
+                    }
+                    segments.extend(
+                        link.target
+                            .split('/')
+                            .rev()
+                            .filter(|x| !x.is_empty())
+                            .map(String::from),
+                    );
+                }
+                Some((entry, _, _)) if segments.is_empty() => return Ok(Some(entry)),
+                _ => return Ok(None),
+            }
+        }
+
+        Ok(dirs.pop().map(DirectoryEntry::Directory))
+    }
+
+    /// Size and locations of the type L and M path tables of the hierarchy
+    /// of `root`
+    fn path_table_location(&self) -> (u32, u32, u32) {
+        #[cfg(feature = "joliet")]
+        if let (DirectoryEntryReader::Joliet, Some(joliet)) = (self.root.reader, &self.joliet) {
+            return (
+                joliet.path_table_size,
+                joliet.path_table_loc,
+                joliet.path_table_loc_be,
+            );
+        }
+
+        (
+            self.primary.path_table_size,
+            self.primary.path_table_loc,
+            self.primary.path_table_loc_be,
+        )
+    }
+
+    /// Sector of the volume descriptor recording the path tables of the
+    /// hierarchy of `root`
+    fn path_table_descriptor_lba(&self) -> u64 {
+        let joliet = matches!(self.root.reader, DirectoryEntryReader::Joliet);
+        self.descriptors
+            .iter()
+            .find(|entry| match &entry.descriptor {
+                VolumeDescriptor::Primary(_) => !joliet,
+                VolumeDescriptor::SupplementaryVolumeDescriptor(svd) => joliet && svd.is_joliet,
+                _ => false,
+            })
+            .map_or(16, |entry| entry.lba as u64)
+    }
+
+    /// Read one copy of the path table of the hierarchy of `root`
+    pub fn read_path_table(
+        &self,
+        type_: PathTableType,
+    ) -> Result<PathTable, ISOError<ReaderError!(T)>> {
+        let (size, l_loc, m_loc) = self.path_table_location();
+        let loc = match type_ {
+            PathTableType::L => l_loc,
+            PathTableType::M => m_loc,
+        };
+        if loc == 0 {
+            return Err(ISOError::InvalidFs("Path table not recorded").at(
+                Structure::VolumeDescriptor,
+                self.path_table_descriptor_lba(),
+                0,
+            ));
+        }
+        self.read_path_table_at(size, loc, type_, self.root.reader)
+    }
+
+    /// Read the path table of `size` bytes at `loc`, of a hierarchy read
+    /// with `reader`
+    fn read_path_table_at(
+        &self,
+        size: u32,
+        loc: u32,
+        type_: PathTableType,
+        reader: DirectoryEntryReader,
+    ) -> Result<PathTable, ISOError<ReaderError!(T)>> {
+        let block_size = self.block_size() as usize;
+        let size = size as usize;
+        let mut bytes = Vec::with_capacity(size);
+        let mut block = [0; 2048];
+        let mut lba = loc as u64;
+        while bytes.len() < size {
+            let count = self
+                .file
+                .read_at(&mut block[..block_size], lba)
+                .map_err(ISOError::Io)?;
+            if count != block_size {
+                return Err(ISOError::ReadSize(block_size, count).at(Structure::PathTable, lba, 0));
+            }
+            bytes.extend_from_slice(&block[..min(block_size, size - bytes.len())]);
+            lba += 1;
+        }
+
+        PathTable::parse(&bytes, type_, reader, loc, block_size)
+    }
+
+    /// The path table of the hierarchy of `root`, listing all of its
+    /// directories. The type L copy is checked against the type M copy,
+    /// if one is recorded.
+    pub fn path_table(&self) -> Result<PathTable, ISOError<ReaderError!(T)>> {
+        let path_table = self.read_path_table(PathTableType::L)?;
+        let (_, l_loc, m_loc) = self.path_table_location();
+        if m_loc == 0 {
+            return Ok(path_table);
+        }
+        let m_table = self.read_path_table(PathTableType::M)?;
+        let (l, m) = (path_table.records(), m_table.records());
+        if let Some(index) = (0..l.len().max(m.len())).find(|&i| l.get(i) != m.get(i)) {
+            let (field, expected, found) = match (l.get(index), m.get(index)) {
+                (Some(l), Some(m)) => l.mismatch(m).unwrap_or_default(),
+                _ => ("record count", l.len() as u64, m.len() as u64),
+            };
+            return Err(ISOError::Parse(ParseError {
+                structure: Structure::PathTable,
+                lba: m_loc as u64,
+                offset: 0,
+                kind: ParseErrorKind::PathTableCopiesDiffer {
+                    l_lba: l_loc as u64,
+                    index,
+                    field,
+                    expected,
+                    found,
+                },
+            }));
+        }
+        Ok(path_table)
+    }
+
+    /// Have `open` find the directory containing a path in the path table,
+    /// instead of reading every directory leading to it. This saves reads
+    /// when they are slow. The path table is read once, when enabling.
+    ///
+    /// Rock Ridge names aren't recorded in the path table, so this is only
+    /// done for the primary and Joliet hierarchies. With Rock Ridge, enabling
+    /// it does nothing, and `open` reads the directories.
+    pub fn set_path_table_lookup(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), ISOError<ReaderError!(T)>> {
+        let rock_ridge = matches!(self.root.reader, DirectoryEntryReader::RockRidge { .. });
+        if !enabled || rock_ridge {
+            self.path_table = None;
+            return Ok(());
+        }
+
+        self.path_table = Some(self.path_table()?);
+        Ok(())
+    }
+
+    /// Every descriptor of the volume descriptor set, in order, ending with
+    /// the terminator
+    pub fn volume_descriptors(&self) -> &[VolumeDescriptorSetEntry] {
+        &self.descriptors
+    }
+
+    /// The (first) primary volume descriptor
+    pub fn primary_volume_descriptor(&self) -> &PrimaryVolumeDescriptor {
+        &self.primary
+    }
+
+    /// Information about the volume, from the primary volume descriptor
+    pub fn volume_info(&self) -> &VolumeInfo {
+        &self.primary.info
+    }
+
+    /// Information about the volume recorded by the Joliet supplementary
+    /// volume descriptor, if there's one. Its text isn't limited to the
+    /// characters allowed in the primary volume descriptor.
+    #[cfg(feature = "joliet")]
+    pub fn joliet_volume_info(&self) -> Option<&VolumeInfo> {
+        self.joliet.as_ref().map(|joliet| &joliet.info)
+    }
+
+    /// SUSP extensions (such as Rock Ridge) registered by "ER" entries of
+    /// the root directory
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    /// The El Torito boot catalog, if the image is bootable
+    pub fn boot_catalog(&self) -> Result<Option<BootCatalog>, ISOError<ReaderError!(T)>> {
+        let Some(lba) = self.boot_catalog_lba else {
+            return Ok(None);
+        };
+
+        // The catalog is usually a single sector, but may be longer. Its
+        // location is in sectors, which may hold several logical blocks.
+        let at = |err: ISOError<ReaderError!(T)>| {
+            let blocks_per_sector = 2048 / self.block_size() as u64;
+            err.at(Structure::BootCatalog, lba as u64 * blocks_per_sector, 0)
+        };
+        let mut bytes = Vec::new();
+        let mut buf = [0; 2048];
+        for sector in 0..16 {
+            let count = self
+                .file
+                .read_sector_at(&mut buf, lba as u64 + sector)
+                .map_err(ISOError::Io)?;
+            if count != 2048 {
+                return Err(at(ISOError::ReadSize(2048, count)));
+            }
+
+            bytes.extend_from_slice(&buf);
+            if let Some(catalog) = BootCatalog::parse(&bytes).map_err(at)? {
+                return Ok(Some(catalog));
+            }
+        }
+
+        Err(at(ISOError::InvalidFs("El Torito boot catalog too long")))
+    }
+
+    /// Read the boot image of an entry of the boot catalog. Its size is
+    /// given by `boot_image_size`.
+    pub fn boot_image(
+        &self,
+        entry: &BootEntry,
+    ) -> Result<ISOFileReader<T>, ISOError<ReaderError!(T)>> {
+        let extent = Extent {
+            loc: self.boot_image_lba(entry),
+            length: self.boot_image_size(entry)? as u32,
+        };
+        Ok(ISOFileReader::from_extent(self.file.clone(), extent))
+    }
+
+    /// Size of the boot image of an entry, in bytes. No emulation entries,
+    /// such as EFI ones, often record a `sector_count` of 0 or 1, since
+    /// only BIOSes load that many sectors. The size is then the extent
+    /// length of the file whose extent starts at `load_rba`, found in the
+    /// directories of `root`. Images without such a file, which aren't
+    /// visible in the hierarchy, have the size of `BootEntry::size`.
+    pub fn boot_image_size(&self, entry: &BootEntry) -> Result<u64, ISOError<ReaderError!(T)>> {
+        if entry.emulation != Emulation::NoEmulation || entry.sector_count > 1 {
+            return Ok(entry.size());
+        }
+
+        let lba = self.boot_image_lba(entry);
+        let mut dirs = vec![self.root.clone()];
+        let mut visited = BTreeSet::new();
+        while let Some(dir) = dirs.pop() {
+            if !visited.insert(dir.header.extent_loc) {
+                continue;
+            }
+            for entry in dir.contents() {
+                match entry? {
+                    DirectoryEntry::File(file) if file.header.extent_loc == lba => {
+                        return Ok(file.header.extent_length as u64);
+                    }
+                    DirectoryEntry::Directory(dir)
+                        if dir.identifier != "." && dir.identifier != ".." =>
+                    {
+                        dirs.push(dir)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(entry.size())
+    }
+
+    /// Logical block of the boot image of an entry
+    fn boot_image_lba(&self, entry: &BootEntry) -> u32 {
+        // `load_rba` is in sectors, which may hold several logical blocks
+        let blocks_per_sector = 2048 / self.block_size() as u32;
+        entry.load_rba.saturating_mul(blocks_per_sector)
+    }
+
+    /// Logical block size of the volume: 512, 1024 or 2048
     pub fn block_size(&self) -> u16 {
-        2048 // XXX
+        self.file.block_size()
     }
 
     primary_prop_str!(volume_set_identifier);
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+use crate::BothEndianPolicy;
+
+/// Directory hierarchy of an image, each recording the same files with
+/// different names and metadata
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Hierarchy {
+    /// The best hierarchy present: Rock Ridge, then Joliet (with the
+    /// `joliet` feature), then the primary hierarchy. Only used to choose a
+    /// hierarchy, `ISO9660::hierarchy` returns the one chosen.
+    #[default]
+    Auto,
+    /// The primary hierarchy, ignoring any Rock Ridge extensions
+    Primary,
+    /// The primary hierarchy, with Rock Ridge names and metadata
+    RockRidge,
+    /// The hierarchy of the Joliet supplementary volume descriptor
+    #[cfg(feature = "joliet")]
+    Joliet,
+}
+
+/// Options for opening an image with `ISO9660::with_options`
+#[derive(Clone, Debug, Default)]
+pub struct ISO9660Options {
+    pub(crate) hierarchy: Hierarchy,
+    pub(crate) path_table_lookup: bool,
+    pub(crate) lenient: bool,
+    pub(crate) both_endian: BothEndianPolicy,
+    pub(crate) cache: usize,
+}
+
+impl ISO9660Options {
+    pub fn new() -> ISO9660Options {
+        ISO9660Options::default()
+    }
+
+    /// Hierarchy used for `ISO9660::root`. Opening the image fails if it
+    /// doesn't have it. Defaults to `Hierarchy::Auto`.
+    pub fn hierarchy(&mut self, hierarchy: Hierarchy) -> &mut Self {
+        self.hierarchy = hierarchy;
+        self
+    }
+
+    /// Resolve paths through the path table, see
+    /// `ISO9660::set_path_table_lookup`. Ignored if the hierarchy used is
+    /// Rock Ridge, whose names aren't in the path table. Defaults to
+    /// `false`.
+    pub fn path_table_lookup(&mut self, enabled: bool) -> &mut Self {
+        self.path_table_lookup = enabled;
+        self
+    }
+
+    /// Recover what can be read from damaged or truncated images, instead
+    /// of failing: reads past the end of the image or failing are filled
+    /// with zeros, and directory records that can't be parsed are skipped.
+    /// Each problem is recorded as a warning, see `ISO9660::warnings`. The
+    /// volume descriptors must still be readable. Defaults to `false`.
+    pub fn lenient(&mut self, enabled: bool) -> &mut Self {
+        self.lenient = enabled;
+        self
+    }
+
+    /// Which value of both-endian numbers to use in volume descriptors and
+    /// directory records, when the little and big endian values differ.
+    /// Mismatches are recorded whatever the policy, see
+    /// `ISO9660::both_endian_mismatches`. Defaults to
+    /// `BothEndianPolicy::Little`.
+    pub fn both_endian(&mut self, policy: BothEndianPolicy) -> &mut Self {
+        self.both_endian = policy;
+        self
+    }
+
+    /// Cache up to `sectors` sectors of 2048 bytes read from the image,
+    /// evicting the least recently used, so that directories and files read
+    /// again don't hit the reader. See `ISO9660::cache_stats`. Defaults to
+    /// 0, disabling the cache.
+    pub fn cache(&mut self, sectors: usize) -> &mut Self {
+        self.cache = sectors;
+        self
+    }
+}
@@ -1,21 +1,129 @@
 // SPDX-License-Identifier: (MIT OR Apache-2.0)
 
-use nom::bytes::complete::take;
+use alloc::vec::Vec;
+use core::cell::RefCell;
 use nom::number::complete::*;
-use nom::sequence::terminated;
+use nom::sequence::pair;
 use nom::IResult;
 
+use crate::{ISOError, ParseError, ParseErrorKind, Structure};
+
 // ISO 9660 uses a representation for integers with both little
 // and big endian representations of the same number.
 
-// This only reads the little endian version.
-// The Linux kernel does the same, with a comment about some programs
-// generating invalid ISO with incorrect big endian values.
+/// Which value of both-endian numbers is used, see
+/// `ISO9660Options::both_endian`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BothEndianPolicy {
+    /// The little endian value. The Linux kernel does the same, with a
+    /// comment about some programs generating invalid ISO with incorrect
+    /// big endian values.
+    #[default]
+    Little,
+    /// The big endian value
+    Big,
+    /// Fail to parse structures whose values differ
+    Strict,
+}
 
-pub fn both_endian16(i: &[u8]) -> IResult<&[u8], u16> {
-    terminated(le_u16, take(2usize))(i)
+/// Both-endian number whose little and big endian values differ
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BothEndianMismatch {
+    pub structure: Structure,
+    /// Logical block of the structure. Volume descriptors are located in
+    /// 2048 byte sectors, as in `VolumeDescriptorSetEntry`. System use
+    /// entries are located at their directory record, except "CE" entries,
+    /// located at the continuation area recording them, if any.
+    pub lba: u64,
+    /// Byte offset of the structure in that block
+    pub offset: u32,
+    pub field: &'static str,
+    pub little: u32,
+    pub big: u32,
 }
 
-pub fn both_endian32(i: &[u8]) -> IResult<&[u8], u32> {
-    terminated(le_u32, take(4usize))(i)
+/// Reads the both-endian numbers of a structure following a policy, and
+/// collects those whose values differ
+pub(crate) struct BothEndian {
+    policy: BothEndianPolicy,
+    mismatches: RefCell<Vec<(&'static str, u32, u32)>>,
+}
+
+impl BothEndian {
+    pub fn new(policy: BothEndianPolicy) -> BothEndian {
+        BothEndian {
+            policy,
+            mismatches: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn u16(&self, field: &'static str) -> impl Fn(&[u8]) -> IResult<&[u8], u16> + '_ {
+        move |i| {
+            let (i, (little, big)) = pair(le_u16, be_u16)(i)?;
+            Ok((i, self.choose(field, little as u32, big as u32) as u16))
+        }
+    }
+
+    pub fn u32(&self, field: &'static str) -> impl Fn(&[u8]) -> IResult<&[u8], u32> + '_ {
+        move |i| {
+            let (i, (little, big)) = pair(le_u32, be_u32)(i)?;
+            Ok((i, self.choose(field, little, big)))
+        }
+    }
+
+    fn choose(&self, field: &'static str, little: u32, big: u32) -> u32 {
+        if little != big {
+            self.mismatches.borrow_mut().push((field, little, big));
+        }
+        match self.policy {
+            BothEndianPolicy::Big => big,
+            BothEndianPolicy::Little | BothEndianPolicy::Strict => little,
+        }
+    }
+
+    /// The mismatches found, for the structure at byte `offset` of block
+    /// `lba`. With `BothEndianPolicy::Strict`, the first one is an error.
+    pub fn finish<E>(
+        self,
+        structure: Structure,
+        lba: u64,
+        offset: u32,
+    ) -> Result<Vec<BothEndianMismatch>, ISOError<E>> {
+        let strict = self.policy == BothEndianPolicy::Strict;
+        let mismatches = self.into_mismatches(structure, lba, offset);
+        if let (true, Some(mismatch)) = (strict, mismatches.first()) {
+            return Err(ISOError::Parse(ParseError {
+                structure,
+                lba,
+                offset,
+                kind: ParseErrorKind::Mismatch {
+                    field: mismatch.field,
+                    expected: mismatch.little as u64,
+                    found: mismatch.big as u64,
+                },
+            }));
+        }
+        Ok(mismatches)
+    }
+
+    /// The mismatches found, like `finish`, whatever the policy
+    pub fn into_mismatches(
+        self,
+        structure: Structure,
+        lba: u64,
+        offset: u32,
+    ) -> Vec<BothEndianMismatch> {
+        self.mismatches
+            .into_inner()
+            .into_iter()
+            .map(|(field, little, big)| BothEndianMismatch {
+                structure,
+                lba,
+                offset,
+                field,
+                little,
+                big,
+            })
+            .collect()
+    }
 }
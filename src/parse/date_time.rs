@@ -3,7 +3,6 @@
 use alloc::str;
 use core::convert::TryFrom;
 use nom::bytes::complete::take;
-use nom::combinator::map_res;
 use nom::number::complete::le_u8;
 use nom::sequence::tuple;
 use nom::IResult;
@@ -26,47 +25,43 @@ pub fn date_time(i: &[u8]) -> IResult<&[u8], OffsetDateTime> {
     let time =
         Time::from_hms(hour, minute, second).unwrap_or_else(|_| Time::from_hms(0, 0, 0).unwrap());
 
-    // gmt_offset represents 15 minutes intervals from GMT.
-    let offset =
-        UtcOffset::from_whole_seconds((gmt_offset as i32) * 15 * 60).unwrap_or(UtcOffset::UTC);
-
-    Ok((i, PrimitiveDateTime::new(date, time).assume_offset(offset)))
+    Ok((
+        i,
+        PrimitiveDateTime::new(date, time).assume_offset(gmt_offset_from(gmt_offset)),
+    ))
 }
 
-fn ascii_i32(n: usize) -> impl Fn(&[u8]) -> IResult<&[u8], i32> {
-    move |i: &[u8]| map_res(map_res(take(n), str::from_utf8), str::parse::<i32>)(i)
+/// `gmt_offset` represents (signed) 15 minutes intervals from GMT
+fn gmt_offset_from(gmt_offset: u8) -> UtcOffset {
+    UtcOffset::from_whole_seconds((gmt_offset as i8 as i32) * 15 * 60).unwrap_or(UtcOffset::UTC)
 }
 
-pub fn date_time_ascii(i: &[u8]) -> IResult<&[u8], OffsetDateTime> {
-    let (i, (tm_year, tm_mon, tm_mday, tm_hour, tm_min, tm_sec, centisecond, gmt_offset)) =
-        tuple((
-            ascii_i32(4),
-            ascii_i32(2),
-            ascii_i32(2),
-            ascii_i32(2),
-            ascii_i32(2),
-            ascii_i32(2),
-            ascii_i32(2),
-            le_u8,
-        ))(i)?;
-
-    let date = Date::from_calendar_date(
-        1900 + tm_year,
-        time::Month::try_from(tm_mon as u8).unwrap_or(time::Month::January),
-        tm_mday as u8,
-    )
-    .unwrap_or_else(|_| Date::from_calendar_date(0, time::Month::January, 1).unwrap());
-
-    let time = Time::from_hms_milli(
-        tm_hour as u8,
-        tm_min as u8,
-        tm_sec as u8,
-        centisecond as u16 * 10,
-    )
-    .unwrap_or_else(|_| Time::from_hms(0, 0, 0).unwrap());
+/// Date and time in the format of volume descriptors: digits for the year,
+/// month, day, hour, minute, second and hundredths of a second, then the
+/// offset from GMT. All digits zero means "not specified".
+pub fn date_time_ascii(i: &[u8]) -> IResult<&[u8], Option<OffsetDateTime>> {
+    let (i, (digits, gmt_offset)) = tuple((take(16usize), le_u8))(i)?;
 
-    let offset =
-        UtcOffset::from_whole_seconds((gmt_offset as i32) * 15 * 60).unwrap_or(UtcOffset::UTC);
+    let number = |range: core::ops::Range<usize>| -> Option<u32> {
+        str::from_utf8(&digits[range]).ok()?.parse().ok()
+    };
+    let date_time = || -> Option<OffsetDateTime> {
+        let date = Date::from_calendar_date(
+            number(0..4)? as i32,
+            time::Month::try_from(number(4..6)? as u8).ok()?,
+            number(6..8)? as u8,
+        )
+        .ok()?;
+        let time = Time::from_hms_milli(
+            number(8..10)? as u8,
+            number(10..12)? as u8,
+            number(12..14)? as u8,
+            number(14..16)? as u16 * 10,
+        )
+        .ok()?;
+        Some(PrimitiveDateTime::new(date, time).assume_offset(gmt_offset_from(gmt_offset)))
+    };
 
-    Ok((i, PrimitiveDateTime::new(date, time).assume_offset(offset)))
+    // Dates that aren't specified, or can't be parsed, are `None`
+    Ok((i, date_time()))
 }
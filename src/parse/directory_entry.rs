@@ -4,13 +4,14 @@ use time::OffsetDateTime;
 
 use crate::ISOError;
 
-use super::both_endian::{both_endian16, both_endian32};
+use super::both_endian::BothEndian;
 use super::date_time::date_time;
 use alloc::str;
 use alloc::string::String;
 use alloc::string::ToString;
 use core::char;
-use nom::combinator::{map, map_res};
+use core::cmp::min;
+use nom::combinator::{map, map_res, rest};
 use nom::multi::length_data;
 use nom::number::complete::le_u8;
 use nom::IResult;
@@ -42,11 +43,12 @@ pub struct DirectoryEntryHeader {
 }
 
 impl DirectoryEntryHeader {
-    pub fn parse<E>(
+    pub(crate) fn parse<E>(
         input: &[u8],
         reader: DirectoryEntryReader,
+        endian: &BothEndian,
     ) -> Result<(DirectoryEntryHeader, String), ISOError<E>> {
-        Ok(directory_entry_with_reader(input, reader)?.1)
+        Ok(directory_entry_with_reader(input, reader, endian)?.1)
     }
 }
 
@@ -56,11 +58,13 @@ pub enum DirectoryEntryReader {
     Primary,
     /// Joliet extensions
     Joliet,
+    /// Directory entry provided by Primary Volume Descriptor, with Rock Ridge
+    /// extensions recorded using SUSP. Each system use area starts with
+    /// `susp_skip` bytes that are not part of SUSP.
+    RockRidge { susp_skip: u8 },
 }
 
-impl DirectoryEntryReader {}
-
-fn decode_ucs2_be(bytes: &[u8]) -> String {
+pub(super) fn decode_ucs2_be(bytes: &[u8]) -> String {
     let mut decoded = String::new();
     let mut iter = bytes.chunks_exact(2);
     for chunk in iter.by_ref() {
@@ -80,31 +84,38 @@ fn decode_ucs2_be(bytes: &[u8]) -> String {
     decoded
 }
 
-pub fn directory_entry(i: &[u8]) -> IResult<&[u8], (DirectoryEntryHeader, String)> {
-    directory_entry_with_reader(i, DirectoryEntryReader::Primary)
+pub fn directory_entry_with_reader<'a>(
+    i: &'a [u8],
+    reader: DirectoryEntryReader,
+    endian: &BothEndian,
+) -> IResult<&'a [u8], (DirectoryEntryHeader, String)> {
+    let (i, (header, identifier)) = directory_record(i, endian)?;
+    let (_, identifier) = match reader {
+        DirectoryEntryReader::Primary | DirectoryEntryReader::RockRidge { .. } => {
+            map(map_res(rest, str::from_utf8), str::to_string)(identifier)?
+        }
+        DirectoryEntryReader::Joliet => map(rest, decode_ucs2_be)(identifier)?,
+    };
+    Ok((i, (header, identifier)))
 }
 
-pub fn directory_entry_with_reader(
-    i: &[u8],
-    reader: DirectoryEntryReader,
-) -> IResult<&[u8], (DirectoryEntryHeader, String)> {
+/// Parse a directory record, with its file identifier as recorded
+pub fn directory_record<'a>(
+    i: &'a [u8],
+    endian: &BothEndian,
+) -> IResult<&'a [u8], (DirectoryEntryHeader, &'a [u8])> {
     let (i, length) = le_u8(i)?;
     let (i, extended_attribute_record_length) = le_u8(i)?;
-    let (i, extent_loc) = both_endian32(i)?;
-    let (i, extent_length) = both_endian32(i)?;
+    let (i, extent_loc) = endian.u32("extent location")(i)?;
+    let (i, extent_length) = endian.u32("data length")(i)?;
     let (i, time) = date_time(i)?;
     let (i, file_flags) = le_u8(i)?;
     let (i, file_unit_size) = le_u8(i)?;
     let (i, interleave_gap_size) = le_u8(i)?;
-    let (i, volume_sequence_number) = both_endian16(i)?;
-    let (i, identifier) = match reader {
-        DirectoryEntryReader::Primary => {
-            map(map_res(length_data(le_u8), str::from_utf8), str::to_string)(i)?
-        }
-        DirectoryEntryReader::Joliet => map(length_data(le_u8), decode_ucs2_be)(i)?,
-    };
+    let (i, volume_sequence_number) = endian.u16("volume sequence number")(i)?;
+    let (i, identifier) = length_data(le_u8)(i)?;
     // After the file identifier, ISO 9660 allows addition space for
-    // system use. Ignore that for now.
+    // system use. See `system_use_area`.
 
     Ok((
         i,
@@ -124,3 +135,15 @@ pub fn directory_entry_with_reader(
         ),
     ))
 }
+
+/// Returns the system use area of the directory record at the start of
+/// `record`, which follows the file identifier (and a padding byte if the
+/// identifier has an even length).
+pub fn system_use_area(record: &[u8]) -> &[u8] {
+    let (Some(&length), Some(&identifier_length)) = (record.first(), record.get(32)) else {
+        return &[];
+    };
+    let start = 33 + identifier_length as usize + (1 - identifier_length as usize % 2);
+    let end = min(length as usize, record.len());
+    record.get(start..end).unwrap_or(&[])
+}
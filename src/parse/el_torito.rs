@@ -0,0 +1,235 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use nom::bytes::complete::take;
+use nom::number::complete::{le_u16, le_u32, le_u8};
+use nom::sequence::tuple;
+use nom::IResult;
+
+use crate::ISOError;
+
+// El Torito bootable CD-ROM format. The boot record volume descriptor
+// points to a boot catalog, made of 32 byte entries: a validation entry,
+// the default entry, then any number of sections, each with a header
+// followed by its entries.
+
+/// Identifier of the boot record volume descriptor used by El Torito
+pub(crate) const EL_TORITO_IDENTIFIER: &str = "EL TORITO SPECIFICATION";
+
+/// Bit of a section entry's media type, or of an extension entry's flags,
+/// set if an extension entry follows
+const EXTENSION_FOLLOWS: u8 = 1 << 5;
+
+/// Platform a boot catalog or section is meant for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    X86,
+    PowerPc,
+    Mac,
+    Efi,
+    Other(u8),
+}
+
+impl From<u8> for Platform {
+    fn from(id: u8) -> Platform {
+        match id {
+            0x00 => Platform::X86,
+            0x01 => Platform::PowerPc,
+            0x02 => Platform::Mac,
+            0xef => Platform::Efi,
+            id => Platform::Other(id),
+        }
+    }
+}
+
+/// Media emulated by the BIOS when booting an image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emulation {
+    NoEmulation,
+    /// 1.2 MB diskette
+    Floppy12,
+    /// 1.44 MB diskette
+    Floppy144,
+    /// 2.88 MB diskette
+    Floppy288,
+    HardDisk,
+    Other(u8),
+}
+
+impl From<u8> for Emulation {
+    fn from(media_type: u8) -> Emulation {
+        match media_type & 0x0f {
+            0 => Emulation::NoEmulation,
+            1 => Emulation::Floppy12,
+            2 => Emulation::Floppy144,
+            3 => Emulation::Floppy288,
+            4 => Emulation::HardDisk,
+            media_type => Emulation::Other(media_type),
+        }
+    }
+}
+
+/// The default entry, or an entry of a section
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BootEntry {
+    pub bootable: bool,
+    pub emulation: Emulation,
+    /// Segment the image is loaded to; 0 means the traditional 0x7c0
+    pub load_segment: u16,
+    /// Partition type of a hard disk image
+    pub system_type: u8,
+    /// Number of 512 byte sectors loaded by the BIOS
+    pub sector_count: u16,
+    /// Start of the image, in 2048 byte sectors
+    pub load_rba: u32,
+    /// Selection criteria type; always 0 for the default entry
+    pub selection_criteria_type: u8,
+    /// Vendor unique selection criteria, including any extension entries
+    pub selection_criteria: Vec<u8>,
+}
+
+impl BootEntry {
+    /// Size of the boot image in bytes. For diskette emulation this is the
+    /// size of the diskette. Otherwise it's the part loaded by the BIOS,
+    /// which for no emulation images (such as EFI system partitions) may be
+    /// smaller than the image itself, and is even 0 or 512 bytes for most
+    /// EFI entries. See `ISO9660::boot_image_size`, which handles those.
+    pub fn size(&self) -> u64 {
+        match self.emulation {
+            Emulation::Floppy12 => 1_228_800,
+            Emulation::Floppy144 => 1_474_560,
+            Emulation::Floppy288 => 2_949_120,
+            _ => self.sector_count as u64 * 512,
+        }
+    }
+}
+
+/// A section of the boot catalog, grouping entries for one platform
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BootSection {
+    pub platform: Platform,
+    pub id_string: String,
+    pub entries: Vec<BootEntry>,
+}
+
+/// El Torito boot catalog
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BootCatalog {
+    /// Platform of the default entry, from the validation entry
+    pub platform: Platform,
+    /// Manufacturer of the CD, from the validation entry
+    pub id_string: String,
+    pub default_entry: BootEntry,
+    pub sections: Vec<BootSection>,
+}
+
+impl BootCatalog {
+    /// Parse the boot catalog at the start of `bytes`. Returns `None` if
+    /// `bytes` ends before the catalog does.
+    pub(crate) fn parse<E>(bytes: &[u8]) -> Result<Option<BootCatalog>, ISOError<E>> {
+        let mut entries = bytes.chunks_exact(32);
+        let Some(validation) = entries.next() else {
+            return Ok(None);
+        };
+        if validation[0] != 0x01 || validation[30..] != [0x55, 0xaa] {
+            return Err(ISOError::InvalidFs("Invalid El Torito validation entry"));
+        }
+        let checksum = validation.chunks_exact(2).fold(0u16, |sum, x| {
+            sum.wrapping_add(u16::from_le_bytes([x[0], x[1]]))
+        });
+        if checksum != 0 {
+            return Err(ISOError::InvalidFs(
+                "Invalid El Torito validation entry checksum",
+            ));
+        }
+
+        let Some(entry) = entries.next() else {
+            return Ok(None);
+        };
+        let (_, default_entry) = boot_entry(entry)?;
+        let mut catalog = BootCatalog {
+            platform: Platform::from(validation[1]),
+            id_string: id_string(&validation[4..28]),
+            default_entry,
+            sections: Vec::new(),
+        };
+
+        loop {
+            let Some(header) = entries.next() else {
+                return Ok(None);
+            };
+            // 0x90 is followed by more sections, 0x91 is the last one.
+            // Anything else ends the catalog.
+            let last = match header[0] {
+                0x90 => false,
+                0x91 => true,
+                _ => return Ok(Some(catalog)),
+            };
+            let (_, (platform, count)) = tuple((le_u8, le_u16))(&header[1..])?;
+            let mut section = BootSection {
+                platform: Platform::from(platform),
+                id_string: id_string(&header[4..]),
+                entries: Vec::new(),
+            };
+
+            for _ in 0..count {
+                let Some(entry) = entries.next() else {
+                    return Ok(None);
+                };
+                let (i, mut section_entry) = boot_entry(entry)?;
+                let (_, (criteria_type, criteria)) = tuple((le_u8, take(19usize)))(i)?;
+                section_entry.selection_criteria_type = criteria_type;
+                section_entry.selection_criteria = criteria.to_vec();
+
+                // Extension entries continue the selection criteria
+                let mut extended = entry[1] & EXTENSION_FOLLOWS != 0;
+                while extended {
+                    let Some(extension) = entries.next() else {
+                        return Ok(None);
+                    };
+                    if extension[0] != 0x44 {
+                        return Err(ISOError::InvalidFs(
+                            "Invalid El Torito section entry extension",
+                        ));
+                    }
+                    section_entry
+                        .selection_criteria
+                        .extend_from_slice(&extension[2..]);
+                    extended = extension[1] & EXTENSION_FOLLOWS != 0;
+                }
+
+                section.entries.push(section_entry);
+            }
+
+            catalog.sections.push(section);
+            if last {
+                return Ok(Some(catalog));
+            }
+        }
+    }
+}
+
+fn id_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches(['\0', ' '])
+        .to_string()
+}
+
+fn boot_entry(i: &[u8]) -> IResult<&[u8], BootEntry> {
+    let (i, (indicator, media_type, load_segment, system_type, _, sector_count, load_rba)) =
+        tuple((le_u8, le_u8, le_u16, le_u8, le_u8, le_u16, le_u32))(i)?;
+    Ok((
+        i,
+        BootEntry {
+            bootable: indicator == 0x88,
+            emulation: Emulation::from(media_type),
+            load_segment,
+            system_type,
+            sector_count,
+            load_rba,
+            selection_criteria_type: 0,
+            selection_criteria: Vec::new(),
+        },
+    ))
+}
@@ -3,7 +3,27 @@
 mod both_endian;
 mod date_time;
 mod directory_entry;
+mod el_torito;
+mod path_table;
+mod rock_ridge;
+mod susp;
 mod volume_descriptor;
 
-pub(crate) use self::directory_entry::{DirectoryEntryHeader, DirectoryEntryReader, FileFlags};
-pub(crate) use self::volume_descriptor::VolumeDescriptor;
+pub(crate) use self::both_endian::BothEndian;
+pub use self::both_endian::{BothEndianMismatch, BothEndianPolicy};
+pub(crate) use self::directory_entry::{
+    directory_record, system_use_area, DirectoryEntryHeader, DirectoryEntryReader, FileFlags,
+};
+pub(crate) use self::el_torito::EL_TORITO_IDENTIFIER;
+pub use self::el_torito::{BootCatalog, BootEntry, BootSection, Emulation, Platform};
+pub use self::path_table::{PathTable, PathTableRecord, PathTableType};
+pub use self::rock_ridge::{
+    CompressedFile, DeviceNumber, PosixAttributes, RockRidge, RockRidgeFlags, SparseFile,
+    Timestamps,
+};
+pub(crate) use self::susp::{append_entries, susp_skip, system_use_entries, ContinuationArea};
+pub use self::susp::{Extension, SystemUseEntries, SystemUseEntry};
+pub use self::volume_descriptor::{
+    BootRecordDescriptor, PrimaryVolumeDescriptor, SupplementaryVolumeDescriptor, VolumeDescriptor,
+    VolumeDescriptorSetEntry, VolumeInfo, VolumePartitionDescriptor,
+};
@@ -0,0 +1,173 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+use alloc::str;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use nom::bytes::complete::take;
+use nom::combinator::{map, map_res};
+use nom::number::complete::{be_u16, be_u32, le_u16, le_u32, le_u8};
+use nom::sequence::pair;
+use nom::IResult;
+
+use super::directory_entry::{decode_ucs2_be, DirectoryEntryReader};
+use crate::{ISOError, Structure};
+
+// The path table lists every directory of a hierarchy, ordered by level
+// in the hierarchy, then by parent, then by identifier. It is recorded
+// twice, with numbers in little endian (type L) and big endian (type M).
+
+/// Byte order of a copy of the path table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathTableType {
+    /// Little endian
+    L,
+    /// Big endian
+    M,
+}
+
+/// Record of a path table, describing a directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathTableRecord {
+    /// Identifier of the directory; empty for the root
+    pub identifier: String,
+    pub extended_attribute_record_length: u8,
+    /// Location of the directory's extent
+    pub extent_loc: u32,
+    /// Number of the parent directory's record, counting from 1. The root
+    /// is its own parent.
+    pub parent: u16,
+}
+
+impl PathTableRecord {
+    /// The first field differing from `other`, with its value in each
+    /// record. Identifiers are compared by length, then by their first
+    /// differing character.
+    pub(crate) fn mismatch(&self, other: &PathTableRecord) -> Option<(&'static str, u64, u64)> {
+        let (a, b) = (&self.identifier, &other.identifier);
+        let fields = [
+            (
+                "extended attribute record length",
+                self.extended_attribute_record_length as u64,
+                other.extended_attribute_record_length as u64,
+            ),
+            (
+                "extent location",
+                self.extent_loc as u64,
+                other.extent_loc as u64,
+            ),
+            (
+                "parent directory number",
+                self.parent as u64,
+                other.parent as u64,
+            ),
+            ("identifier length", a.len() as u64, b.len() as u64),
+        ];
+        fields.iter().copied().find(|(_, a, b)| a != b).or_else(|| {
+            let (a, b) = a.chars().zip(b.chars()).find(|(a, b)| a != b)?;
+            Some(("identifier character", a as u64, b as u64))
+        })
+    }
+}
+
+/// Path table of a hierarchy, see `ISO9660::path_table`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathTable {
+    records: Vec<PathTableRecord>,
+}
+
+impl PathTable {
+    /// Parse the table in `table`, recorded from block `loc`
+    pub(crate) fn parse<E>(
+        table: &[u8],
+        type_: PathTableType,
+        reader: DirectoryEntryReader,
+        loc: u32,
+        block_size: usize,
+    ) -> Result<PathTable, ISOError<E>> {
+        let at = |err: ISOError<E>, pos: usize| {
+            let lba = loc as u64 + (pos / block_size) as u64;
+            err.at(Structure::PathTable, lba, (pos % block_size) as u32)
+        };
+
+        let mut records = Vec::new();
+        let mut bytes = table;
+        while !bytes.is_empty() {
+            let pos = table.len() - bytes.len();
+            let (rest, record) =
+                path_table_record(bytes, type_, reader).map_err(|err| at(err.into(), pos))?;
+            // Parents are recorded before their children, which also
+            // rules out loops
+            if record.parent == 0 || record.parent as usize > records.len().max(1) {
+                return Err(at(ISOError::InvalidFs("Invalid path table parent"), pos));
+            }
+            records.push(record);
+            bytes = rest;
+        }
+
+        if records.is_empty() {
+            return Err(at(ISOError::InvalidFs("Empty path table"), 0));
+        }
+        Ok(PathTable { records })
+    }
+
+    /// Records of the table, the first being the root directory
+    pub fn records(&self) -> &[PathTableRecord] {
+        &self.records
+    }
+
+    /// Record of the directory at `path`, relative to the root. Identifiers
+    /// are compared without regard to ASCII case, as in `ISODirectory::find`.
+    pub fn find(&self, path: &str) -> Option<&PathTableRecord> {
+        let mut number = 1;
+        for segment in path.split('/').filter(|x| !x.is_empty()) {
+            let index = self
+                .records
+                .iter()
+                .enumerate()
+                .position(|(index, record)| {
+                    record.parent as usize == number
+                        && index + 1 != number
+                        && record.identifier.eq_ignore_ascii_case(segment)
+                })?;
+            number = index + 1;
+        }
+        self.records.get(number - 1)
+    }
+}
+
+fn path_table_record(
+    i: &[u8],
+    type_: PathTableType,
+    reader: DirectoryEntryReader,
+) -> IResult<&[u8], PathTableRecord> {
+    let (i, identifier_length) = le_u8(i)?;
+    let (i, extended_attribute_record_length) = le_u8(i)?;
+    let (i, (extent_loc, parent)) = match type_ {
+        PathTableType::L => pair(le_u32, le_u16)(i)?,
+        PathTableType::M => pair(be_u32, be_u16)(i)?,
+    };
+    let (i, identifier) = match reader {
+        DirectoryEntryReader::Primary | DirectoryEntryReader::RockRidge { .. } => map(
+            map_res(take(identifier_length), str::from_utf8),
+            str::to_string,
+        )(i)?,
+        DirectoryEntryReader::Joliet => map(take(identifier_length), decode_ucs2_be)(i)?,
+    };
+    let identifier = if identifier == "\0" {
+        String::new()
+    } else {
+        identifier
+    };
+    // Identifiers of odd length are followed by a padding byte
+    let (i, _) = take(identifier_length % 2)(i)?;
+
+    Ok((
+        i,
+        PathTableRecord {
+            identifier,
+            extended_attribute_record_length,
+            extent_loc,
+            parent,
+        },
+    ))
+}
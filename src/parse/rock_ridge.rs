@@ -0,0 +1,394 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+use alloc::string::String;
+use nom::bytes::complete::take;
+use nom::combinator::{map, opt};
+use nom::number::complete::le_u8;
+use nom::sequence::tuple;
+use nom::IResult;
+use time::OffsetDateTime;
+
+use super::both_endian::BothEndian;
+use super::date_time::{date_time, date_time_ascii};
+use super::susp::system_use_entries;
+
+// Rock Ridge Interchange Protocol (RRIP, IEEE P1282) entries, recorded in
+// the system use area of directory records using SUSP.
+
+bitflags! {
+    /// Contents of the "RR" entry (RRIP 1.09), listing which Rock Ridge
+    /// entries are recorded for a directory record.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct RockRidgeFlags: u8 {
+        const PX = 1 << 0;
+        const PN = 1 << 1;
+        const SL = 1 << 2;
+        const NM = 1 << 3;
+        const CL = 1 << 4;
+        const PL = 1 << 5;
+        const RE = 1 << 6;
+        const TF = 1 << 7;
+    }
+}
+
+bitflags! {
+    #[derive(Clone, Copy, Debug)]
+    struct NameFlags: u8 {
+        const CONTINUE = 1 << 0;
+        const CURRENT = 1 << 1;
+        const PARENT = 1 << 2;
+    }
+}
+
+bitflags! {
+    #[derive(Clone, Copy, Debug)]
+    struct SymlinkComponentFlags: u8 {
+        const CONTINUE = 1 << 0;
+        const CURRENT = 1 << 1;
+        const PARENT = 1 << 2;
+        const ROOT = 1 << 3;
+        const VOLROOT = 1 << 4;
+        const HOST = 1 << 5;
+    }
+}
+
+bitflags! {
+    #[derive(Clone, Copy, Debug)]
+    struct TimestampFlags: u8 {
+        const CREATION = 1 << 0;
+        const MODIFY = 1 << 1;
+        const ACCESS = 1 << 2;
+        const ATTRIBUTES = 1 << 3;
+        const BACKUP = 1 << 4;
+        const EXPIRATION = 1 << 5;
+        const EFFECTIVE = 1 << 6;
+        const LONG_FORM = 1 << 7;
+    }
+}
+
+/// POSIX file attributes, from the "PX" entry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PosixAttributes {
+    /// File mode, as `st_mode` (including the file type bits)
+    pub mode: u32,
+    /// Number of links, as `st_nlink`
+    pub links: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// File serial number, as `st_ino` (RRIP 1.12 only)
+    pub serial: Option<u32>,
+}
+
+/// Device number of a block or character device, from the "PN" entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceNumber {
+    pub high: u32,
+    pub low: u32,
+}
+
+impl DeviceNumber {
+    /// Major device number. Usually the high word, but like Linux, handle
+    /// writers that store an old-style 16-bit `dev_t` in the low word.
+    pub fn major(&self) -> u32 {
+        if self.is_packed() {
+            self.low >> 8
+        } else {
+            self.high
+        }
+    }
+
+    /// Minor device number
+    pub fn minor(&self) -> u32 {
+        if self.is_packed() {
+            self.low & 0xff
+        } else {
+            self.low
+        }
+    }
+
+    fn is_packed(&self) -> bool {
+        self.high == 0 && self.low & !0xff != 0
+    }
+}
+
+/// Sparse file metadata, from the "SF" entry (RRIP 1.12)
+///
+/// The file's extent starts with a table of `block size / 4` little endian
+/// logical block numbers, each covering an equal part of the file. Tables
+/// are nested `table_depth` levels deep, and the entries of the last level
+/// point to data blocks. An entry of zero marks a region containing only
+/// zeros, which isn't recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SparseFile {
+    /// Logical size of the file
+    pub size: u64,
+    pub table_depth: u8,
+}
+
+/// Time stamps, from the "TF" entry
+#[derive(Clone, Debug, Default)]
+pub struct Timestamps {
+    pub creation: Option<OffsetDateTime>,
+    pub modify: Option<OffsetDateTime>,
+    pub access: Option<OffsetDateTime>,
+    pub attributes: Option<OffsetDateTime>,
+    pub backup: Option<OffsetDateTime>,
+    pub expiration: Option<OffsetDateTime>,
+    pub effective: Option<OffsetDateTime>,
+}
+
+/// zisofs compression metadata, from the "ZF" entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompressedFile {
+    /// Compression algorithm; only "pz" (zisofs) is defined
+    pub algorithm: [u8; 2],
+    /// Size of the zisofs header, in 4-byte units
+    pub header_size: u8,
+    /// Base 2 logarithm of the compression block size
+    pub block_size_log2: u8,
+    /// Uncompressed size of the file
+    pub size: u32,
+}
+
+/// Rock Ridge metadata of a directory record
+#[derive(Clone, Debug, Default)]
+pub struct RockRidge {
+    /// Alternate (POSIX) name, from the "NM" entries
+    pub name: Option<String>,
+    pub attributes: Option<PosixAttributes>,
+    pub timestamps: Timestamps,
+    /// Entries listed by the "RR" entry, if any
+    pub recorded: Option<RockRidgeFlags>,
+    /// Target of a symbolic link, from the "SL" entries
+    pub symlink: Option<String>,
+    /// Location of a relocated directory, from the "CL" entry
+    pub child_link: Option<u32>,
+    /// Location of the original parent of a relocated directory, from the
+    /// "PL" entry
+    pub parent_link: Option<u32>,
+    /// Whether this is a relocated directory ("RE" entry), which should not
+    /// be shown in its parent
+    pub relocated: bool,
+    pub device: Option<DeviceNumber>,
+    pub sparse: Option<SparseFile>,
+    pub compressed: Option<CompressedFile>,
+}
+
+impl RockRidge {
+    /// Parse the Rock Ridge entries of a system use area, reading
+    /// both-endian numbers with `endian`. Returns `None` if the area
+    /// contains no Rock Ridge entries.
+    pub(crate) fn parse(area: &[u8], endian: &BothEndian) -> Option<RockRidge> {
+        let mut rock_ridge = RockRidge::default();
+        let mut found = false;
+        let mut continued = false;
+        let mut symlink_continued = false;
+        let mut component_continued = false;
+
+        for entry in system_use_entries(area) {
+            match &entry.signature {
+                b"NM" => {
+                    let Some((&flags, content)) = entry.data.split_first() else {
+                        continue;
+                    };
+                    let flags = NameFlags::from_bits_truncate(flags);
+                    found = true;
+                    // "." and ".." keep their ISO 9660 identifiers
+                    if flags.intersects(NameFlags::CURRENT | NameFlags::PARENT) {
+                        continue;
+                    }
+                    // A name may be split across several "NM" entries
+                    if rock_ridge.name.is_none() || continued {
+                        rock_ridge
+                            .name
+                            .get_or_insert_with(String::new)
+                            .push_str(&String::from_utf8_lossy(content));
+                    }
+                    continued = flags.contains(NameFlags::CONTINUE);
+                }
+                b"PX" => {
+                    if let Ok((_, attributes)) = posix_attributes(entry.data, endian) {
+                        rock_ridge.attributes = Some(attributes);
+                        found = true;
+                    }
+                }
+                b"TF" => {
+                    if let Ok((_, timestamps)) = timestamps(entry.data) {
+                        rock_ridge.timestamps = timestamps;
+                        found = true;
+                    }
+                }
+                b"SL" => {
+                    let Some((&flags, components)) = entry.data.split_first() else {
+                        continue;
+                    };
+                    found = true;
+                    // Like names, a link may be split across several entries
+                    if rock_ridge.symlink.is_some() && !symlink_continued {
+                        continue;
+                    }
+                    let target = rock_ridge.symlink.get_or_insert_with(String::new);
+                    symlink_components(components, target, &mut component_continued);
+                    symlink_continued = SymlinkComponentFlags::from_bits_truncate(flags)
+                        .contains(SymlinkComponentFlags::CONTINUE);
+                }
+                b"CL" => {
+                    if let Ok((_, location)) = endian.u32("child directory location")(entry.data) {
+                        rock_ridge.child_link = Some(location);
+                        found = true;
+                    }
+                }
+                b"PL" => {
+                    if let Ok((_, location)) = endian.u32("parent directory location")(entry.data) {
+                        rock_ridge.parent_link = Some(location);
+                        found = true;
+                    }
+                }
+                b"RE" => {
+                    rock_ridge.relocated = true;
+                    found = true;
+                }
+                b"PN" => {
+                    if let Ok((_, device)) = device_number(entry.data, endian) {
+                        rock_ridge.device = Some(device);
+                        found = true;
+                    }
+                }
+                b"SF" => {
+                    if let Ok((_, sparse)) = sparse_file(entry.data, endian) {
+                        rock_ridge.sparse = Some(sparse);
+                        found = true;
+                    }
+                }
+                b"ZF" => {
+                    if let Ok((_, compressed)) = compressed_file(entry.data, endian) {
+                        rock_ridge.compressed = Some(compressed);
+                        found = true;
+                    }
+                }
+                b"RR" => {
+                    if let Some(&flags) = entry.data.first() {
+                        rock_ridge.recorded = Some(RockRidgeFlags::from_bits_truncate(flags));
+                        found = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if found {
+            Some(rock_ridge)
+        } else {
+            None
+        }
+    }
+}
+
+/// Append the component records of an "SL" entry to `target`, separating
+/// components with '/'. `continued` is set if the last component continues
+/// in the next entry.
+fn symlink_components(mut i: &[u8], target: &mut String, continued: &mut bool) {
+    while let [flags, length, rest @ ..] = i {
+        let Some((content, rest)) = rest.split_at_checked(*length as usize) else {
+            break;
+        };
+        let flags = SymlinkComponentFlags::from_bits_truncate(*flags);
+
+        if !*continued && !target.is_empty() && !target.ends_with('/') {
+            target.push('/');
+        }
+        if flags.contains(SymlinkComponentFlags::ROOT) {
+            target.push('/');
+        } else if flags.contains(SymlinkComponentFlags::CURRENT) {
+            target.push('.');
+        } else if flags.contains(SymlinkComponentFlags::PARENT) {
+            target.push_str("..");
+        } else if !flags.intersects(SymlinkComponentFlags::VOLROOT | SymlinkComponentFlags::HOST) {
+            target.push_str(&String::from_utf8_lossy(content));
+        }
+        *continued = flags.contains(SymlinkComponentFlags::CONTINUE);
+
+        i = rest;
+    }
+}
+
+fn posix_attributes<'a>(i: &'a [u8], endian: &BothEndian) -> IResult<&'a [u8], PosixAttributes> {
+    let (i, mode) = endian.u32("file mode")(i)?;
+    let (i, links) = endian.u32("file links")(i)?;
+    let (i, uid) = endian.u32("user ID")(i)?;
+    let (i, gid) = endian.u32("group ID")(i)?;
+    let (i, serial) = opt(endian.u32("file serial number"))(i)?;
+    Ok((
+        i,
+        PosixAttributes {
+            mode,
+            links,
+            uid,
+            gid,
+            serial,
+        },
+    ))
+}
+
+fn device_number<'a>(i: &'a [u8], endian: &BothEndian) -> IResult<&'a [u8], DeviceNumber> {
+    let (i, high) = endian.u32("device number high")(i)?;
+    let (i, low) = endian.u32("device number low")(i)?;
+    Ok((i, DeviceNumber { high, low }))
+}
+
+fn sparse_file<'a>(i: &'a [u8], endian: &BothEndian) -> IResult<&'a [u8], SparseFile> {
+    let (i, high) = endian.u32("virtual file size high")(i)?;
+    let (i, low) = endian.u32("virtual file size low")(i)?;
+    let (i, table_depth) = opt(le_u8)(i)?;
+    Ok((
+        i,
+        SparseFile {
+            size: ((high as u64) << 32) | low as u64,
+            table_depth: table_depth.unwrap_or(1),
+        },
+    ))
+}
+
+fn timestamps(i: &[u8]) -> IResult<&[u8], Timestamps> {
+    let (mut i, flags) = le_u8(i)?;
+    let flags = TimestampFlags::from_bits_truncate(flags);
+    let mut timestamps = Timestamps::default();
+
+    // Time stamps are recorded in this order, each only if its flag is set
+    let fields = [
+        (TimestampFlags::CREATION, &mut timestamps.creation),
+        (TimestampFlags::MODIFY, &mut timestamps.modify),
+        (TimestampFlags::ACCESS, &mut timestamps.access),
+        (TimestampFlags::ATTRIBUTES, &mut timestamps.attributes),
+        (TimestampFlags::BACKUP, &mut timestamps.backup),
+        (TimestampFlags::EXPIRATION, &mut timestamps.expiration),
+        (TimestampFlags::EFFECTIVE, &mut timestamps.effective),
+    ];
+    for (flag, field) in fields {
+        if flags.contains(flag) {
+            let (rest, time) = if flags.contains(TimestampFlags::LONG_FORM) {
+                date_time_ascii(i)?
+            } else {
+                map(date_time, Some)(i)?
+            };
+            *field = time;
+            i = rest;
+        }
+    }
+
+    Ok((i, timestamps))
+}
+
+fn compressed_file<'a>(i: &'a [u8], endian: &BothEndian) -> IResult<&'a [u8], CompressedFile> {
+    let (i, (algorithm, header_size, block_size_log2, size)) =
+        tuple((take(2usize), le_u8, le_u8, endian.u32("uncompressed size")))(i)?;
+    Ok((
+        i,
+        CompressedFile {
+            algorithm: [algorithm[0], algorithm[1]],
+            header_size,
+            block_size_log2,
+            size,
+        },
+    ))
+}
@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use nom::bytes::complete::take;
+use nom::combinator::verify;
+use nom::number::complete::le_u8;
+use nom::IResult;
+
+use super::both_endian::BothEndian;
+
+// The System Use Sharing Protocol (SUSP, IEEE P1281) divides the system use
+// area at the end of each directory record into a series of entries, each
+// starting with a two character signature, a length and a version.
+
+/// An entry of a directory record's system use area
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SystemUseEntry<'a> {
+    pub signature: [u8; 2],
+    pub version: u8,
+    pub data: &'a [u8],
+}
+
+impl SystemUseEntry<'_> {
+    /// Append the entry, as recorded on disk, to `buf`
+    pub(crate) fn write_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.signature);
+        buf.push(self.data.len() as u8 + 4);
+        buf.push(self.version);
+        buf.extend_from_slice(self.data);
+    }
+}
+
+fn system_use_entry(i: &[u8]) -> IResult<&[u8], SystemUseEntry<'_>> {
+    let (i, signature) = take(2usize)(i)?;
+    let (i, length) = verify(le_u8, |length| *length >= 4)(i)?;
+    let (i, version) = le_u8(i)?;
+    let (i, data) = take(length as usize - 4)(i)?;
+    Ok((
+        i,
+        SystemUseEntry {
+            signature: [signature[0], signature[1]],
+            version,
+            data,
+        },
+    ))
+}
+
+/// Iterate over the entries of a system use area, stopping at the first
+/// malformed entry, at the padding that may follow the last entry, or at
+/// an "ST" (terminator) entry.
+pub fn system_use_entries(area: &[u8]) -> SystemUseEntries<'_> {
+    SystemUseEntries { area }
+}
+
+/// Iterator over the entries of a system use area
+#[derive(Clone, Debug)]
+pub struct SystemUseEntries<'a> {
+    area: &'a [u8],
+}
+
+impl<'a> Iterator for SystemUseEntries<'a> {
+    type Item = SystemUseEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (rest, entry) = system_use_entry(self.area).ok()?;
+        if &entry.signature == b"ST" {
+            self.area = &[];
+            return None;
+        }
+        self.area = rest;
+        Some(entry)
+    }
+}
+
+/// Returns the number of bytes to skip at the start of every system use
+/// area, if `area` (the system use area of the root directory's "." record)
+/// begins with a valid "SP" entry.
+pub fn susp_skip(area: &[u8]) -> Option<u8> {
+    let entry = system_use_entries(area).next()?;
+    match entry.data {
+        [0xBE, 0xEF, skip] if &entry.signature == b"SP" => Some(*skip),
+        _ => None,
+    }
+}
+
+/// Append the entries of `area` (except "CE" entries) to `buf`, as recorded
+/// on disk. Returns the continuation area of the last "CE" entry, if any.
+pub fn append_entries(
+    area: &[u8],
+    buf: &mut Vec<u8>,
+    endian: &BothEndian,
+) -> Option<ContinuationArea> {
+    let mut continuation = None;
+    for entry in system_use_entries(area) {
+        match ContinuationArea::parse(&entry, endian) {
+            Some(area) => continuation = Some(area),
+            None => entry.write_to(buf),
+        }
+    }
+    continuation
+}
+
+/// Location of a continuation area, from a "CE" entry
+#[derive(Clone, Copy, Debug)]
+pub struct ContinuationArea {
+    pub block: u32,
+    pub offset: u32,
+    pub length: u32,
+}
+
+impl ContinuationArea {
+    pub fn parse(entry: &SystemUseEntry, endian: &BothEndian) -> Option<ContinuationArea> {
+        if &entry.signature != b"CE" {
+            return None;
+        }
+        let (_, (block, offset, length)) = continuation_area(entry.data, endian).ok()?;
+        Some(ContinuationArea {
+            block,
+            offset,
+            length,
+        })
+    }
+}
+
+fn continuation_area<'a>(i: &'a [u8], endian: &BothEndian) -> IResult<&'a [u8], (u32, u32, u32)> {
+    let (i, block) = endian.u32("continuation area location")(i)?;
+    let (i, offset) = endian.u32("continuation area offset")(i)?;
+    let (i, length) = endian.u32("continuation area length")(i)?;
+    Ok((i, (block, offset, length)))
+}
+
+/// An extension registered by an "ER" entry of the root directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extension {
+    pub identifier: String,
+    pub descriptor: String,
+    pub source: String,
+    pub version: u8,
+}
+
+impl Extension {
+    pub(crate) fn parse(entry: &SystemUseEntry) -> Option<Extension> {
+        if &entry.signature != b"ER" {
+            return None;
+        }
+        Some(extension(entry.data).ok()?.1)
+    }
+}
+
+fn extension(i: &[u8]) -> IResult<&[u8], Extension> {
+    let (i, identifier_length) = le_u8(i)?;
+    let (i, descriptor_length) = le_u8(i)?;
+    let (i, source_length) = le_u8(i)?;
+    let (i, version) = le_u8(i)?;
+    let (i, identifier) = take(identifier_length)(i)?;
+    let (i, descriptor) = take(descriptor_length)(i)?;
+    let (i, source) = take(source_length)(i)?;
+    Ok((
+        i,
+        Extension {
+            identifier: String::from_utf8_lossy(identifier).into_owned(),
+            descriptor: String::from_utf8_lossy(descriptor).into_owned(),
+            source: String::from_utf8_lossy(source).into_owned(),
+            version,
+        },
+    ))
+}
@@ -11,30 +11,29 @@ use nom::sequence::tuple;
 use nom::IResult;
 use time::OffsetDateTime;
 
-use super::both_endian::{both_endian16, both_endian32};
+use super::both_endian::BothEndian;
 use super::date_time::date_time_ascii;
 use super::directory_entry::{
-    directory_entry, directory_entry_with_reader, DirectoryEntryHeader, DirectoryEntryReader,
+    decode_ucs2_be, directory_entry_with_reader, DirectoryEntryHeader, DirectoryEntryReader,
 };
 use crate::ISOError;
 
-#[allow(dead_code)]
-#[derive(Clone, Debug)]
-pub(crate) struct PrimaryVolumeDescriptor {
+// The volume descriptor set starts at sector 16, with one descriptor per
+// sector, and ends with a volume descriptor set terminator.
+
+/// Information about the volume, recorded by the primary volume
+/// descriptor and by supplementary volume descriptors. Those of Joliet
+/// record text in UCS-2, which is decoded here.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VolumeInfo {
     pub system_identifier: String,
     pub volume_identifier: String,
+    /// Size of the volume, in logical blocks
     pub volume_space_size: u32,
     pub volume_set_size: u16,
     pub volume_sequence_number: u16,
     pub logical_block_size: u16,
 
-    pub path_table_size: u32,
-    pub path_table_loc: u32,
-    pub optional_path_table_loc: u32,
-
-    pub root_directory_entry: DirectoryEntryHeader,
-    pub root_directory_entry_identifier: String,
-
     pub volume_set_identifier: String,
     pub publisher_identifier: String,
     pub data_preparer_identifier: String,
@@ -43,50 +42,108 @@ pub(crate) struct PrimaryVolumeDescriptor {
     pub abstract_file_identifier: String,
     pub bibliographic_file_identifier: String,
 
-    pub creation_time: OffsetDateTime,
-    pub modification_time: OffsetDateTime,
-    pub expiration_time: OffsetDateTime,
-    pub effective_time: OffsetDateTime,
+    /// Dates are `None` when recorded as "not specified"
+    pub creation_time: Option<OffsetDateTime>,
+    pub modification_time: Option<OffsetDateTime>,
+    pub expiration_time: Option<OffsetDateTime>,
+    pub effective_time: Option<OffsetDateTime>,
 
     pub file_structure_version: u8,
 }
 
-#[allow(dead_code)]
+/// Type 1 descriptor, describing the volume and its primary hierarchy
 #[derive(Clone, Debug)]
-pub(crate) struct BootRecordDescriptor {
+pub struct PrimaryVolumeDescriptor {
+    pub info: VolumeInfo,
+
+    pub path_table_size: u32,
+    pub path_table_loc: u32,
+    pub optional_path_table_loc: u32,
+    pub path_table_loc_be: u32,
+    pub optional_path_table_loc_be: u32,
+
+    pub root_directory_entry: DirectoryEntryHeader,
+    pub root_directory_entry_identifier: String,
+}
+
+/// Type 0 descriptor, used by boot systems such as El Torito
+#[derive(Clone, Debug)]
+pub struct BootRecordDescriptor {
     pub boot_system_identifier: String,
     pub boot_identifier: String,
+    /// Boot system use area, whose meaning depends on the boot system
     pub data: Vec<u8>,
 }
 
-#[allow(dead_code)]
+/// Type 2 descriptor, describing another hierarchy, such as Joliet's, or an
+/// enhanced volume descriptor (version 2) of ISO 9660:1999
 #[derive(Clone, Debug)]
-pub(crate) struct SupplementaryVolumeDescriptor {
+pub struct SupplementaryVolumeDescriptor {
     pub type_: u8,
     pub version: u8,
     pub flags: u8,
+    /// Whether the escape sequences mark it as Joliet, recording names in
+    /// UCS-2
     pub is_joliet: bool,
+    pub escape_sequences: Vec<u8>,
+    pub info: VolumeInfo,
+
+    pub path_table_size: u32,
+    pub path_table_loc: u32,
+    pub optional_path_table_loc: u32,
+    pub path_table_loc_be: u32,
+    pub optional_path_table_loc_be: u32,
+
     pub root_directory_entry: DirectoryEntryHeader,
     pub root_directory_entry_identifier: String,
 }
-#[allow(dead_code)]
+
+/// Type 3 descriptor, describing a partition of the volume reserved for a
+/// system
+#[derive(Clone, Debug)]
+pub struct VolumePartitionDescriptor {
+    pub system_identifier: String,
+    pub volume_partition_identifier: String,
+    pub volume_partition_location: u32,
+    /// Size of the partition, in logical blocks
+    pub volume_partition_size: u32,
+    pub system_use: Vec<u8>,
+}
+
 #[allow(clippy::enum_variant_names)]
 #[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug)]
-pub(crate) enum VolumeDescriptor {
+pub enum VolumeDescriptor {
     Primary(PrimaryVolumeDescriptor),
     BootRecord(BootRecordDescriptor),
-    #[cfg(feature = "joliet")]
     SupplementaryVolumeDescriptor(SupplementaryVolumeDescriptor),
+    VolumePartition(VolumePartitionDescriptor),
     VolumeDescriptorSetTerminator,
+    /// A descriptor of a type this crate doesn't know about
+    Unknown {
+        type_: u8,
+        version: u8,
+        /// The rest of the sector, after the version
+        data: Vec<u8>,
+    },
 }
 
 impl VolumeDescriptor {
-    pub fn parse<E>(bytes: &[u8]) -> Result<Option<VolumeDescriptor>, ISOError<E>> {
-        Ok(volume_descriptor(bytes)?.1)
+    pub(crate) fn parse<E>(
+        bytes: &[u8],
+        endian: &BothEndian,
+    ) -> Result<VolumeDescriptor, ISOError<E>> {
+        Ok(volume_descriptor(bytes, endian)?.1)
     }
 }
 
+/// A volume descriptor, with the sector it was read from
+#[derive(Clone, Debug)]
+pub struct VolumeDescriptorSetEntry {
+    pub lba: u32,
+    pub descriptor: VolumeDescriptor,
+}
+
 fn take_string_trim(count: usize) -> impl Fn(&[u8]) -> IResult<&[u8], String> {
     move |i: &[u8]| {
         map(
@@ -112,82 +169,114 @@ fn boot_record(i: &[u8]) -> IResult<&[u8], VolumeDescriptor> {
     ))
 }
 
-fn volume_descriptor(i: &[u8]) -> IResult<&[u8], Option<VolumeDescriptor>> {
+fn volume_partition_descriptor<'a>(
+    i: &'a [u8],
+    endian: &BothEndian,
+) -> IResult<&'a [u8], VolumeDescriptor> {
+    let (i, _) = take(1usize)(i)?; // padding
+    let (i, system_identifier) = take_string_trim(32usize)(i)?;
+    let (i, volume_partition_identifier) = take_string_trim(32usize)(i)?;
+    let (i, volume_partition_location) = endian.u32("volume partition location")(i)?;
+    let (i, volume_partition_size) = endian.u32("volume partition size")(i)?;
+    let (i, system_use) = take(1960usize)(i)?;
+    Ok((
+        i,
+        VolumeDescriptor::VolumePartition(VolumePartitionDescriptor {
+            system_identifier,
+            volume_partition_identifier,
+            volume_partition_location,
+            volume_partition_size,
+            system_use: system_use.to_vec(),
+        }),
+    ))
+}
+
+fn volume_descriptor<'a>(i: &'a [u8], endian: &BothEndian) -> IResult<&'a [u8], VolumeDescriptor> {
     let (i, type_code) = le_u8(i)?;
-    let (i, _) = tag("CD001\u{1}")(i)?;
+    let (i, _) = tag("CD001")(i)?;
+    let (i, version) = le_u8(i)?;
     match type_code {
-        0 => map(boot_record, Some)(i),
-        1 => map(primary_descriptor, Some)(i),
-        #[cfg(feature = "joliet")]
-        2 => map(supplementary_descriptor, Some)(i),
-        //3 => map!(volume_partition_descriptor, Some)(i),
-        255 => Ok((i, Some(VolumeDescriptor::VolumeDescriptorSetTerminator))),
-        _ => Ok((i, None)),
+        0 => boot_record(i),
+        1 => primary_descriptor(i, endian),
+        2 => supplementary_descriptor(i, version, endian),
+        3 => volume_partition_descriptor(i, endian),
+        255 => Ok((i, VolumeDescriptor::VolumeDescriptorSetTerminator)),
+        _ => Ok((
+            &[],
+            VolumeDescriptor::Unknown {
+                type_: type_code,
+                version,
+                data: i.to_vec(),
+            },
+        )),
     }
 }
 
-#[cfg(feature = "joliet")]
-fn supplementary_descriptor(i: &[u8]) -> IResult<&[u8], VolumeDescriptor> {
-    let input = i;
-    let (i, flags) = le_u8(i)?;
-    let (i, _) = take(32usize)(i)?; // system_identifier
-    let (i, _) = take(32usize)(i)?; // volume_identifier
-    let (i, _) = take(8usize)(i)?; // padding
-    let (i, _) = take(8usize)(i)?; // volume_space_size
-    let (i, _) = take(32usize)(i)?; // padding
-    let (i, _) = take(4usize)(i)?; // volume_set_size
-    let (i, _) = take(4usize)(i)?; // volume_sequence_number
-    let (i, _) = take(4usize)(i)?; // logical_block_size
-    let (i, _) = take(8usize)(i)?; // path_table_size
-    let (i, _) = take(4usize)(i)?; // path_table_loc
-    let (i, _) = take(4usize)(i)?; // optional_path_table_loc
-    let (i, _) = take(8usize)(i)?; // path_table_loc_be + optional_path_table_loc_be
-    let (i, root_directory_entry) = directory_entry_with_reader(i, DirectoryEntryReader::Joliet)?;
-
-    let escape_sequences = input.get(81..113).unwrap_or(&[]);
-    let is_joliet = escape_sequences.starts_with(b"%/@")
-        || escape_sequences.starts_with(b"%/C")
-        || escape_sequences.starts_with(b"%/E");
+/// Text of a primary or supplementary volume descriptor, which is in UCS-2
+/// for Joliet
+fn take_text(count: usize, joliet: bool) -> impl Fn(&[u8]) -> IResult<&[u8], String> {
+    move |i: &[u8]| {
+        if joliet {
+            map(take(count), |bytes| {
+                decode_ucs2_be(bytes)
+                    .trim_end_matches([' ', '\0'])
+                    .to_string()
+            })(i)
+        } else {
+            take_string_trim(count)(i)
+        }
+    }
+}
 
-    Ok((
-        i,
-        VolumeDescriptor::SupplementaryVolumeDescriptor(SupplementaryVolumeDescriptor {
-            type_: 2,
-            version: 1,
-            flags,
-            is_joliet,
-            root_directory_entry: root_directory_entry.0,
-            root_directory_entry_identifier: root_directory_entry.1,
-        }),
-    ))
+/// The fields primary and supplementary volume descriptors have in common,
+/// which are most of them
+struct DescriptorBody {
+    flags: u8,
+    escape_sequences: Vec<u8>,
+    info: VolumeInfo,
+    path_table_size: u32,
+    path_table_loc: u32,
+    optional_path_table_loc: u32,
+    path_table_loc_be: u32,
+    optional_path_table_loc_be: u32,
+    root_directory_entry: (DirectoryEntryHeader, String),
 }
 
-fn primary_descriptor(i: &[u8]) -> IResult<&[u8], VolumeDescriptor> {
-    let (i, _) = take(1usize)(i)?; // padding
-    let (i, system_identifier) = take_string_trim(32usize)(i)?;
-    let (i, volume_identifier) = take_string_trim(32usize)(i)?;
+fn descriptor_body<'a>(
+    i: &'a [u8],
+    joliet: bool,
+    endian: &BothEndian,
+) -> IResult<&'a [u8], DescriptorBody> {
+    let (i, flags) = le_u8(i)?; // unused in primary volume descriptors
+    let (i, system_identifier) = take_text(32, joliet)(i)?;
+    let (i, volume_identifier) = take_text(32, joliet)(i)?;
     let (i, _) = take(8usize)(i)?; // padding
-    let (i, volume_space_size) = both_endian32(i)?;
-    let (i, _) = take(32usize)(i)?; // padding
-    let (i, volume_set_size) = both_endian16(i)?;
-    let (i, volume_sequence_number) = both_endian16(i)?;
-    let (i, logical_block_size) = both_endian16(i)?;
+    let (i, volume_space_size) = endian.u32("volume space size")(i)?;
+    let (i, escape_sequences) = take(32usize)(i)?; // unused in primary volume descriptors
+    let (i, volume_set_size) = endian.u16("volume set size")(i)?;
+    let (i, volume_sequence_number) = endian.u16("volume sequence number")(i)?;
+    let (i, logical_block_size) = endian.u16("logical block size")(i)?;
 
-    let (i, path_table_size) = both_endian32(i)?;
+    let (i, path_table_size) = endian.u32("path table size")(i)?;
     let (i, path_table_loc) = le_u32(i)?;
     let (i, optional_path_table_loc) = le_u32(i)?;
-    let (i, _) = take(4usize)(i)?; // path_table_loc_be
-    let (i, _) = take(4usize)(i)?; // optional_path_table_loc_be
+    let (i, path_table_loc_be) = be_u32(i)?;
+    let (i, optional_path_table_loc_be) = be_u32(i)?;
 
-    let (i, root_directory_entry) = directory_entry(i)?;
+    let reader = if joliet {
+        DirectoryEntryReader::Joliet
+    } else {
+        DirectoryEntryReader::Primary
+    };
+    let (i, root_directory_entry) = directory_entry_with_reader(i, reader, endian)?;
 
-    let (i, volume_set_identifier) = take_string_trim(128)(i)?;
-    let (i, publisher_identifier) = take_string_trim(128)(i)?;
-    let (i, data_preparer_identifier) = take_string_trim(128)(i)?;
-    let (i, application_identifier) = take_string_trim(128)(i)?;
-    let (i, copyright_file_identifier) = take_string_trim(38)(i)?;
-    let (i, abstract_file_identifier) = take_string_trim(36)(i)?;
-    let (i, bibliographic_file_identifier) = take_string_trim(37)(i)?;
+    let (i, volume_set_identifier) = take_text(128, joliet)(i)?;
+    let (i, publisher_identifier) = take_text(128, joliet)(i)?;
+    let (i, data_preparer_identifier) = take_text(128, joliet)(i)?;
+    let (i, application_identifier) = take_text(128, joliet)(i)?;
+    let (i, copyright_file_identifier) = take_text(37, joliet)(i)?;
+    let (i, abstract_file_identifier) = take_text(37, joliet)(i)?;
+    let (i, bibliographic_file_identifier) = take_text(37, joliet)(i)?;
 
     let (i, creation_time) = date_time_ascii(i)?;
     let (i, modification_time) = date_time_ascii(i)?;
@@ -198,35 +287,90 @@ fn primary_descriptor(i: &[u8]) -> IResult<&[u8], VolumeDescriptor> {
 
     Ok((
         i,
-        VolumeDescriptor::Primary(PrimaryVolumeDescriptor {
-            system_identifier,
-            volume_identifier,
-            volume_space_size,
-            volume_set_size,
-            volume_sequence_number,
-            logical_block_size,
+        DescriptorBody {
+            flags,
+            escape_sequences: escape_sequences.to_vec(),
+            info: VolumeInfo {
+                system_identifier,
+                volume_identifier,
+                volume_space_size,
+                volume_set_size,
+                volume_sequence_number,
+                logical_block_size,
+
+                volume_set_identifier,
+                publisher_identifier,
+                data_preparer_identifier,
+                application_identifier,
+                copyright_file_identifier,
+                abstract_file_identifier,
+                bibliographic_file_identifier,
 
+                creation_time,
+                modification_time,
+                expiration_time,
+                effective_time,
+
+                file_structure_version,
+            },
             path_table_size,
             path_table_loc,
             optional_path_table_loc,
+            path_table_loc_be,
+            optional_path_table_loc_be,
+            root_directory_entry,
+        },
+    ))
+}
+
+fn supplementary_descriptor<'a>(
+    i: &'a [u8],
+    version: u8,
+    endian: &BothEndian,
+) -> IResult<&'a [u8], VolumeDescriptor> {
+    let escape_sequences = i.get(81..113).unwrap_or(&[]);
+    let is_joliet = escape_sequences.starts_with(b"%/@")
+        || escape_sequences.starts_with(b"%/C")
+        || escape_sequences.starts_with(b"%/E");
+
+    let (i, body) = descriptor_body(i, is_joliet, endian)?;
+    Ok((
+        i,
+        VolumeDescriptor::SupplementaryVolumeDescriptor(SupplementaryVolumeDescriptor {
+            type_: 2,
+            version,
+            flags: body.flags,
+            is_joliet,
+            escape_sequences: body.escape_sequences,
+            info: body.info,
 
-            root_directory_entry: root_directory_entry.0,
-            root_directory_entry_identifier: root_directory_entry.1,
+            path_table_size: body.path_table_size,
+            path_table_loc: body.path_table_loc,
+            optional_path_table_loc: body.optional_path_table_loc,
+            path_table_loc_be: body.path_table_loc_be,
+            optional_path_table_loc_be: body.optional_path_table_loc_be,
+
+            root_directory_entry: body.root_directory_entry.0,
+            root_directory_entry_identifier: body.root_directory_entry.1,
+        }),
+    ))
+}
 
-            volume_set_identifier,
-            publisher_identifier,
-            data_preparer_identifier,
-            application_identifier,
-            copyright_file_identifier,
-            abstract_file_identifier,
-            bibliographic_file_identifier,
+fn primary_descriptor<'a>(i: &'a [u8], endian: &BothEndian) -> IResult<&'a [u8], VolumeDescriptor> {
+    let (i, body) = descriptor_body(i, false, endian)?;
+    Ok((
+        i,
+        VolumeDescriptor::Primary(PrimaryVolumeDescriptor {
+            info: body.info,
 
-            creation_time,
-            modification_time,
-            expiration_time,
-            effective_time,
+            path_table_size: body.path_table_size,
+            path_table_loc: body.path_table_loc,
+            optional_path_table_loc: body.optional_path_table_loc,
+            path_table_loc_be: body.path_table_loc_be,
+            optional_path_table_loc_be: body.optional_path_table_loc_be,
 
-            file_structure_version,
+            root_directory_entry: body.root_directory_entry.0,
+            root_directory_entry_identifier: body.root_directory_entry.1,
         }),
     ))
 }
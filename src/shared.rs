@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+//! State shared by the handles of an image: `ISO9660`, and the directories,
+//! files and readers opened from it.
+
+use core::ops::{Deref, DerefMut};
+
+#[cfg(not(feature = "sync"))]
+type Inner<T> = alloc::rc::Rc<core::cell::RefCell<T>>;
+#[cfg(all(feature = "sync", feature = "std"))]
+type Inner<T> = alloc::sync::Arc<std::sync::RwLock<T>>;
+#[cfg(all(feature = "sync", not(feature = "std")))]
+type Inner<T> = alloc::sync::Arc<spin::RwLock<T>>;
+
+/// An `Rc`, or with the `sync` feature an `Arc`, for values only accessed
+/// through shared references, which need no lock
+#[cfg(not(feature = "sync"))]
+pub(crate) type SharedRef<T> = alloc::rc::Rc<T>;
+#[cfg(feature = "sync")]
+pub(crate) type SharedRef<T> = alloc::sync::Arc<T>;
+
+/// An `Rc<RefCell<T>>`, or with the `sync` feature an `Arc` and a
+/// read-write lock, so that the handles are `Send` and `Sync` if the reader
+/// is `Send` and `Sync`. The lock is the one of `std` with the `std`
+/// feature, so that threads waiting for a read block instead of spinning,
+/// and a spin lock otherwise.
+pub(crate) struct Shared<T>(Inner<T>);
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Shared<T> {
+        Shared(self.0.clone())
+    }
+}
+
+impl<T: Default> Default for Shared<T> {
+    fn default() -> Shared<T> {
+        Shared::new(T::default())
+    }
+}
+
+impl<T> Shared<T> {
+    pub fn new(value: T) -> Shared<T> {
+        Shared(Inner::new(value.into()))
+    }
+
+    /// Access the value exclusively. It mustn't already be accessed by the
+    /// same thread, and with the `sync` feature, waits for other threads to
+    /// release it.
+    #[cfg(not(feature = "sync"))]
+    pub fn lock(&self) -> impl DerefMut<Target = T> + '_ {
+        self.0.borrow_mut()
+    }
+
+    #[cfg(all(feature = "sync", feature = "std"))]
+    pub fn lock(&self) -> impl DerefMut<Target = T> + '_ {
+        // A panic while holding the lock leaves the value usable
+        self.0
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    #[cfg(all(feature = "sync", not(feature = "std")))]
+    pub fn lock(&self) -> impl DerefMut<Target = T> + '_ {
+        self.0.write()
+    }
+
+    /// Access the value, along with other shared accesses. It mustn't
+    /// already be accessed exclusively by the same thread.
+    #[cfg(not(feature = "sync"))]
+    pub fn read(&self) -> impl Deref<Target = T> + '_ {
+        self.0.borrow()
+    }
+
+    #[cfg(all(feature = "sync", feature = "std"))]
+    pub fn read(&self) -> impl Deref<Target = T> + '_ {
+        self.0
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    #[cfg(all(feature = "sync", not(feature = "std")))]
+    pub fn read(&self) -> impl Deref<Target = T> + '_ {
+        self.0.read()
+    }
+}
@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+//! Reading images with tokio, through the async API.
+//!
+//! `TokioReader` adapts a tokio reader to `embedded-io-async`, so
+//! `AsyncISO9660` opens images and lists directories with it. The readers of
+//! files also implement the tokio traits, which are poll based, so read the
+//! data the parser lacks without the futures of the async API.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+use crate::asynch::{Staging, SECTOR_SIZE};
+use crate::shared::Shared;
+use crate::{AsyncISOFileReader, ISOError};
+
+/// Reader of an image implementing the tokio `AsyncRead` and `AsyncSeek`,
+/// for `AsyncISO9660`
+pub struct TokioReader<R>(pub R);
+
+impl<R> embedded_io_async::ErrorType for TokioReader<R> {
+    type Error = io::Error;
+}
+
+impl<R: AsyncRead + Unpin> embedded_io_async::Read for TokioReader<R> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        let mut buf = ReadBuf::new(buf);
+        poll_fn(|cx| Pin::new(&mut self.0).poll_read(cx, &mut buf)).await?;
+        Ok(buf.filled().len())
+    }
+}
+
+impl<R: AsyncSeek + Unpin> embedded_io_async::Seek for TokioReader<R> {
+    async fn seek(&mut self, pos: embedded_io_async::SeekFrom) -> Result<u64, io::Error> {
+        let pos = match pos {
+            embedded_io_async::SeekFrom::Start(pos) => io::SeekFrom::Start(pos),
+            embedded_io_async::SeekFrom::End(pos) => io::SeekFrom::End(pos),
+            embedded_io_async::SeekFrom::Current(pos) => io::SeekFrom::Current(pos),
+        };
+        Pin::new(&mut self.0).start_seek(pos)?;
+        poll_fn(|cx| Pin::new(&mut self.0).poll_complete(cx)).await
+    }
+}
+
+enum Step {
+    Seek,
+    Complete,
+    Read,
+}
+
+/// Read of data the parser lacks, in progress between polls
+pub(crate) struct PendingRead {
+    lba: u64,
+    len: usize,
+    data: Vec<u8>,
+    filled: usize,
+    step: Step,
+}
+
+/// Poll `op` until it succeeds or fails with all the data it needs, like
+/// `asynch::fetch`
+fn poll_fetch<R: AsyncRead + AsyncSeek + Unpin, U>(
+    reader: &mut R,
+    staging: &Shared<Staging>,
+    pending: &mut Option<PendingRead>,
+    cx: &mut Context<'_>,
+    mut op: impl FnMut() -> Result<U, ISOError<io::Error>>,
+) -> Poll<Result<U, ISOError<io::Error>>> {
+    loop {
+        let Some(read) = pending else {
+            let result = op();
+            let Some((lba, len)) = staging.lock().take_missing() else {
+                staging.lock().finish();
+                return Poll::Ready(result);
+            };
+            *pending = Some(PendingRead {
+                lba,
+                len,
+                data: vec![0; len],
+                filled: 0,
+                step: Step::Seek,
+            });
+            continue;
+        };
+
+        let result = match read.step {
+            Step::Seek => Pin::new(&mut *reader)
+                .start_seek(io::SeekFrom::Start(read.lba * SECTOR_SIZE as u64))
+                .map(|()| read.step = Step::Complete),
+            Step::Complete => {
+                ready!(Pin::new(&mut *reader).poll_complete(cx)).map(|_| read.step = Step::Read)
+            }
+            Step::Read => {
+                let mut buf = ReadBuf::new(&mut read.data[read.filled..]);
+                let result = ready!(Pin::new(&mut *reader).poll_read(cx, &mut buf));
+                let count = buf.filled().len();
+                read.filled += count;
+                if result.is_ok() && (count == 0 || read.filled == read.len) {
+                    let mut read = pending.take().unwrap();
+                    read.data.truncate(read.filled);
+                    staging.lock().add(read.lba, read.len, read.data);
+                }
+                result
+            }
+        };
+        if let Err(err) = result {
+            *pending = None;
+            staging.lock().finish();
+            return Poll::Ready(Err(ISOError::Io(err)));
+        }
+    }
+}
+
+impl<'a, R: AsyncRead + AsyncSeek + Unpin> AsyncRead for AsyncISOFileReader<'a, TokioReader<R>> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let file = &mut this.file;
+        let unfilled = buf.initialize_unfilled();
+        let count = ready!(poll_fetch(
+            &mut this.reader.0,
+            this.staging,
+            &mut this.pending,
+            cx,
+            || file.read_next(unfilled)
+        ))?;
+        buf.advance(count);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<'a, R: AsyncRead + AsyncSeek + Unpin> AsyncSeek for AsyncISOFileReader<'a, TokioReader<R>> {
+    fn start_seek(self: Pin<&mut Self>, pos: io::SeekFrom) -> io::Result<()> {
+        self.get_mut().file.seek_from(pos);
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.get_mut().file.seek_from(io::SeekFrom::Current(0))))
+    }
+}
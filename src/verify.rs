@@ -0,0 +1,670 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+//! Consistency checks of an image, like `fsck`. See `ISO9660::verify`.
+
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::iter;
+
+use crate::parse::{DirectoryEntryHeader, DirectoryEntryReader, FileFlags};
+use crate::{
+    BothEndianMismatch, Hierarchy, ISO9660Reader, ISOError, ParseErrorKind, PathTableType,
+    Structure, VolumeDescriptor, ISO9660,
+};
+
+/// Problem found by `ISO9660::verify`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub structure: Structure,
+    /// Logical block of the structure with the problem. Volume descriptors
+    /// are located in 2048 byte sectors, as in `VolumeDescriptorSetEntry`.
+    pub lba: u64,
+    /// Byte offset of the structure in that block
+    pub offset: u32,
+    /// Hierarchy of the directory record or path table
+    pub hierarchy: Option<Hierarchy>,
+    /// Path of the directory record from the root of its hierarchy, with
+    /// identifiers as recorded
+    pub path: Option<String>,
+    pub kind: FindingKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FindingKind {
+    /// The structure can't be parsed
+    Invalid(ParseErrorKind),
+    /// The little and big endian values of a both-endian field differ
+    BothEndianMismatch {
+        field: &'static str,
+        little: u32,
+        big: u32,
+    },
+    /// The extent of the record ends past the end of the volume
+    ExtentOutsideVolume {
+        loc: u32,
+        blocks: u32,
+        volume_space_size: u32,
+    },
+    /// The extent of the record overlaps the extent of the record at
+    /// `other`, without being the same extent (as hard links are)
+    OverlappingExtents { other: String },
+    /// The directory record continues past the end of its logical block
+    RecordStraddlesBlock,
+    /// The directory doesn't start with a "." record
+    MissingDot,
+    /// The "." record doesn't point at the directory
+    InvalidDot { expected: u32, found: u32 },
+    /// The second record of the directory isn't ".."
+    MissingDotDot,
+    /// The ".." record doesn't point at the parent directory
+    InvalidDotDot { expected: u32, found: u32 },
+    /// The record should come before `previous`, the record preceding it
+    Unsorted { previous: String },
+    /// The identifier of a record of the primary hierarchy isn't made of
+    /// d-characters, or a file identifier lacks its "." or version
+    InvalidIdentifier,
+    /// Record `index` differs between the type L and type M path tables
+    PathTableCopiesDiffer { index: usize },
+    /// Record `index` of the path table doesn't match a directory of the
+    /// hierarchy
+    PathTableMismatch { index: usize },
+    /// The directory isn't in the path table
+    MissingFromPathTable,
+    /// No file of the other hierarchy has the same extent
+    #[cfg(feature = "joliet")]
+    HierarchiesDiffer,
+}
+
+impl Finding {
+    fn at(structure: Structure, lba: u64, offset: u32, kind: FindingKind) -> Finding {
+        Finding {
+            structure,
+            lba,
+            offset,
+            hierarchy: None,
+            path: None,
+            kind,
+        }
+    }
+
+    fn record(hierarchy: Hierarchy, location: &Location, kind: FindingKind) -> Finding {
+        Finding {
+            structure: Structure::DirectoryRecord,
+            lba: location.lba,
+            offset: location.offset,
+            hierarchy: Some(hierarchy),
+            path: Some(location.path.clone()),
+            kind,
+        }
+    }
+}
+
+/// Finding of a both-endian number whose values differ
+fn mismatch_kind(mismatch: BothEndianMismatch) -> FindingKind {
+    FindingKind::BothEndianMismatch {
+        field: mismatch.field,
+        little: mismatch.little,
+        big: mismatch.big,
+    }
+}
+
+/// What an error parsing a structure is about
+fn parse_error_kind<E>(err: ISOError<E>) -> ParseErrorKind {
+    match err.at(Structure::DirectoryRecord, 0, 0) {
+        ISOError::Parse(err) => err.kind,
+        _ => ParseErrorKind::Invalid("Unreadable structure"),
+    }
+}
+
+/// Order of directory records: by name, then extension, each padded with
+/// spaces, then by descending version
+fn compare_identifiers(a: (&str, bool), b: (&str, bool)) -> Ordering {
+    fn key((identifier, directory): (&str, bool)) -> (&str, &str, u32) {
+        let (name, version) = match identifier.rsplit_once(';') {
+            Some((name, version)) => (name, version.parse().unwrap_or(0)),
+            None => (identifier, 0),
+        };
+        match name.rsplit_once('.') {
+            Some((name, extension)) if !directory => (name, extension, version),
+            _ => (name, "", version),
+        }
+    }
+    fn padded(a: &str, b: &str) -> Ordering {
+        let len = a.chars().count().max(b.chars().count());
+        let a = a.chars().chain(iter::repeat(' ')).take(len);
+        let b = b.chars().chain(iter::repeat(' ')).take(len);
+        a.cmp(b)
+    }
+
+    let (a, b) = (key(a), key(b));
+    padded(a.0, b.0)
+        .then_with(|| padded(a.1, b.1))
+        .then(b.2.cmp(&a.2))
+}
+
+/// Whether a primary identifier is made of d-characters, with files
+/// having a "." between name and extension and a version
+fn valid_identifier(identifier: &str, directory: bool) -> bool {
+    let d_characters = |s: &str| {
+        s.bytes()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == b'_')
+    };
+    if directory {
+        return !identifier.is_empty() && d_characters(identifier);
+    }
+
+    let Some((name, version)) = identifier.rsplit_once(';') else {
+        return false;
+    };
+    let Some((name, extension)) = name.split_once('.') else {
+        return false;
+    };
+    d_characters(name)
+        && d_characters(extension)
+        && !(name.is_empty() && extension.is_empty())
+        && version
+            .parse::<u16>()
+            .is_ok_and(|version| (1..=32767).contains(&version))
+}
+
+/// Where a directory record is, and the path it has
+struct Location {
+    path: String,
+    lba: u64,
+    offset: u32,
+}
+
+/// A directory record found walking a hierarchy, other than "." and ".."
+struct Record {
+    location: Location,
+    header: DirectoryEntryHeader,
+}
+
+/// A directory found walking a hierarchy
+struct Directory {
+    identifier: String,
+    /// Extent of the parent directory
+    parent: u32,
+    /// Index of the directory's record; `None` for the root
+    record: Option<usize>,
+}
+
+/// The records of one hierarchy
+struct Walk {
+    hierarchy: Hierarchy,
+    reader: DirectoryEntryReader,
+    records: Vec<Record>,
+    /// Directories by extent location
+    directories: BTreeMap<u32, Directory>,
+}
+
+struct Checker<'a, T: ISO9660Reader> {
+    fs: &'a ISO9660<T>,
+    block_size: usize,
+    findings: Vec<Finding>,
+}
+
+impl<T: ISO9660Reader> Checker<'_, T> {
+    fn check_descriptors(&mut self) -> Result<(), ISOError<ReaderError!(T)>> {
+        let mut buf = [0; 2048];
+        for entry in &self.fs.descriptors {
+            match entry.descriptor {
+                VolumeDescriptor::Primary(_)
+                | VolumeDescriptor::SupplementaryVolumeDescriptor(_)
+                | VolumeDescriptor::VolumePartition(_) => {}
+                _ => continue,
+            }
+            self.fs
+                .file
+                .read_sector_at(&mut buf, entry.lba as u64)
+                .map_err(ISOError::Io)?;
+
+            // The descriptor was parsed when opening the image, so only
+            // the mismatches are of interest
+            let endian = self.fs.file.both_endian();
+            let _ = VolumeDescriptor::parse::<ReaderError!(T)>(&buf, &endian);
+            let lba = entry.lba as u64;
+            for mismatch in endian.into_mismatches(Structure::VolumeDescriptor, lba, 0) {
+                self.findings.push(Finding::at(
+                    Structure::VolumeDescriptor,
+                    lba,
+                    0,
+                    mismatch_kind(mismatch),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read every directory of a hierarchy, breadth first, checking their
+    /// records
+    fn walk(
+        &mut self,
+        hierarchy: Hierarchy,
+        reader: DirectoryEntryReader,
+        root: &DirectoryEntryHeader,
+    ) -> Result<Walk, ISOError<ReaderError!(T)>> {
+        let mut walk = Walk {
+            hierarchy,
+            reader,
+            records: Vec::new(),
+            directories: BTreeMap::new(),
+        };
+        walk.directories.insert(
+            root.extent_loc,
+            Directory {
+                identifier: String::new(),
+                parent: root.extent_loc,
+                record: None,
+            },
+        );
+
+        let block_size = self.block_size;
+        let mut block = [0; 2048];
+        let mut queue = VecDeque::new();
+        queue.push_back((
+            "/".to_string(),
+            root.extent_loc,
+            root.extent_length,
+            root.extent_loc,
+        ));
+        while let Some((path, loc, length, parent)) = queue.pop_front() {
+            let dir = Location {
+                path,
+                lba: loc as u64,
+                offset: 0,
+            };
+            let mut index = 0;
+            let mut previous: Option<(String, bool)> = None;
+            'blocks: for block_num in 0..length.div_ceil(block_size as u32) {
+                let lba = loc as u64 + block_num as u64;
+                let count = self
+                    .fs
+                    .file
+                    .read_at(&mut block[..block_size], lba)
+                    .map_err(ISOError::Io)?;
+                if count != block_size {
+                    let kind =
+                        parse_error_kind(ISOError::<ReaderError!(T)>::ReadSize(block_size, count));
+                    let finding = Finding::record(hierarchy, &dir, FindingKind::Invalid(kind));
+                    self.findings.push(Finding { lba, ..finding });
+                    break;
+                }
+
+                let mut pos = 0;
+                // All bytes after the last record of a block are zero
+                while pos < block_size && block[pos] != 0 {
+                    let len = block[pos] as usize;
+                    let location = Location {
+                        path: dir.path.clone(),
+                        lba,
+                        offset: pos as u32,
+                    };
+                    if pos + len > block_size {
+                        self.findings.push(Finding::record(
+                            hierarchy,
+                            &location,
+                            FindingKind::RecordStraddlesBlock,
+                        ));
+                        continue 'blocks;
+                    }
+                    if len < 34 {
+                        let kind = ParseErrorKind::Invalid("Invalid directory record length");
+                        self.findings.push(Finding::record(
+                            hierarchy,
+                            &location,
+                            FindingKind::Invalid(kind),
+                        ));
+                        continue 'blocks;
+                    }
+                    let bytes = &block[pos..pos + len];
+                    pos += len;
+
+                    // Use the values files are read with, the mismatches
+                    // are reported whatever the policy
+                    let endian = self.fs.file.both_endian();
+                    let parsed =
+                        DirectoryEntryHeader::parse::<ReaderError!(T)>(bytes, reader, &endian);
+                    let mismatches = endian
+                        .into_mismatches(Structure::DirectoryRecord, lba, location.offset)
+                        .into_iter()
+                        .map(mismatch_kind);
+                    let (header, identifier) = match parsed {
+                        Ok(parsed) => parsed,
+                        Err(err) => {
+                            let kind = FindingKind::Invalid(parse_error_kind(err));
+                            for kind in mismatches.chain(iter::once(kind)) {
+                                self.findings
+                                    .push(Finding::record(hierarchy, &location, kind));
+                            }
+                            index += 1;
+                            continue;
+                        }
+                    };
+
+                    let dot = index == 0 && identifier == "\0";
+                    let dot_dot = index == 1 && identifier == "\u{1}";
+                    if index == 0 && !dot {
+                        self.findings.push(Finding::record(
+                            hierarchy,
+                            &location,
+                            FindingKind::MissingDot,
+                        ));
+                    }
+                    if index == 1 && !dot_dot {
+                        self.findings.push(Finding::record(
+                            hierarchy,
+                            &location,
+                            FindingKind::MissingDotDot,
+                        ));
+                    }
+                    index += 1;
+
+                    // Findings about other records than "." and ".." have
+                    // their own path
+                    let location = if dot || dot_dot {
+                        location
+                    } else {
+                        Location {
+                            path: match dir.path.as_str() {
+                                "/" => format!("/{}", identifier),
+                                path => format!("{}/{}", path, identifier),
+                            },
+                            ..location
+                        }
+                    };
+                    for kind in mismatches {
+                        self.findings
+                            .push(Finding::record(hierarchy, &location, kind));
+                    }
+                    if dot && header.extent_loc != loc {
+                        let kind = FindingKind::InvalidDot {
+                            expected: loc,
+                            found: header.extent_loc,
+                        };
+                        self.findings
+                            .push(Finding::record(hierarchy, &location, kind));
+                    }
+                    if dot_dot && header.extent_loc != parent {
+                        let kind = FindingKind::InvalidDotDot {
+                            expected: parent,
+                            found: header.extent_loc,
+                        };
+                        self.findings
+                            .push(Finding::record(hierarchy, &location, kind));
+                    }
+                    if dot || dot_dot {
+                        continue;
+                    }
+
+                    let directory = header.file_flags.contains(FileFlags::DIRECTORY);
+                    let record = Record { location, header };
+                    if let Some((previous, previous_directory)) = &previous {
+                        if compare_identifiers(
+                            (previous, *previous_directory),
+                            (&identifier, directory),
+                        ) == Ordering::Greater
+                        {
+                            let kind = FindingKind::Unsorted {
+                                previous: previous.clone(),
+                            };
+                            self.findings
+                                .push(Finding::record(hierarchy, &record.location, kind));
+                        }
+                    }
+                    if hierarchy == Hierarchy::Primary && !valid_identifier(&identifier, directory)
+                    {
+                        self.findings.push(Finding::record(
+                            hierarchy,
+                            &record.location,
+                            FindingKind::InvalidIdentifier,
+                        ));
+                    }
+                    self.check_extent(hierarchy, &record);
+
+                    if directory && !walk.directories.contains_key(&record.header.extent_loc) {
+                        walk.directories.insert(
+                            record.header.extent_loc,
+                            Directory {
+                                identifier: identifier.clone(),
+                                parent: loc,
+                                record: Some(walk.records.len()),
+                            },
+                        );
+                        queue.push_back((
+                            record.location.path.clone(),
+                            record.header.extent_loc,
+                            record.header.extent_length,
+                            loc,
+                        ));
+                    }
+                    previous = Some((identifier, directory));
+                    walk.records.push(record);
+                }
+            }
+            if index == 0 {
+                self.findings
+                    .push(Finding::record(hierarchy, &dir, FindingKind::MissingDot));
+            }
+        }
+
+        Ok(walk)
+    }
+
+    fn check_extent(&mut self, hierarchy: Hierarchy, record: &Record) {
+        let volume_space_size = self.fs.primary.info.volume_space_size;
+        let (loc, length) = (record.header.extent_loc, record.header.extent_length);
+        let blocks = length.div_ceil(self.block_size as u32);
+        if length != 0 && loc as u64 + blocks as u64 > volume_space_size as u64 {
+            let kind = FindingKind::ExtentOutsideVolume {
+                loc,
+                blocks,
+                volume_space_size,
+            };
+            self.findings
+                .push(Finding::record(hierarchy, &record.location, kind));
+        }
+    }
+
+    /// Report extents overlapping without being the same
+    fn check_overlaps(&mut self, walks: &[Walk]) {
+        let mut extents: Vec<(u64, u64, Hierarchy, &Record)> = walks
+            .iter()
+            .flat_map(|walk| {
+                walk.records
+                    .iter()
+                    .map(move |record| (walk.hierarchy, record))
+            })
+            .filter(|(_, record)| record.header.extent_length != 0)
+            .map(|(hierarchy, record)| {
+                let start = record.header.extent_loc as u64;
+                let blocks = record.header.extent_length.div_ceil(self.block_size as u32);
+                (start, start + blocks as u64, hierarchy, record)
+            })
+            .collect();
+        extents.sort_by_key(|&(start, end, _, _)| (start, end));
+
+        // The extent reaching the furthest so far
+        let mut furthest: Option<(u64, u64, &Record)> = None;
+        for (start, end, hierarchy, record) in extents {
+            if let Some((other_start, other_end, other)) = furthest {
+                if start < other_end && (start, end) != (other_start, other_end) {
+                    let kind = FindingKind::OverlappingExtents {
+                        other: other.location.path.clone(),
+                    };
+                    self.findings
+                        .push(Finding::record(hierarchy, &record.location, kind));
+                }
+                if end <= other_end {
+                    continue;
+                }
+            }
+            furthest = Some((start, end, record));
+        }
+    }
+
+    /// Check both copies of the path table of a hierarchy against each
+    /// other and against its directories
+    fn check_path_tables(
+        &mut self,
+        walk: &Walk,
+        (size, l_loc, m_loc): (u32, u32, u32),
+    ) -> Result<(), ISOError<ReaderError!(T)>> {
+        let mut tables = Vec::new();
+        for (type_, loc) in [(PathTableType::L, l_loc), (PathTableType::M, m_loc)] {
+            if loc == 0 {
+                continue;
+            }
+            match self.fs.read_path_table_at(size, loc, type_, walk.reader) {
+                Ok(table) => tables.push((loc, table)),
+                Err(ISOError::Parse(err)) => self.findings.push(Finding {
+                    hierarchy: Some(walk.hierarchy),
+                    ..Finding::at(
+                        err.structure,
+                        err.lba,
+                        err.offset,
+                        FindingKind::Invalid(err.kind),
+                    )
+                }),
+                Err(err) => return Err(err),
+            }
+        }
+        let path_table_finding = |loc: u32, kind: FindingKind| Finding {
+            hierarchy: Some(walk.hierarchy),
+            ..Finding::at(Structure::PathTable, loc as u64, 0, kind)
+        };
+
+        if let [(_, l), (m_loc, m)] = &tables[..] {
+            let (l, m) = (l.records(), m.records());
+            if let Some(index) = (0..l.len().max(m.len())).find(|&i| l.get(i) != m.get(i)) {
+                let kind = FindingKind::PathTableCopiesDiffer { index };
+                self.findings.push(path_table_finding(*m_loc, kind));
+            }
+        }
+
+        let Some((loc, table)) = tables.first() else {
+            return Ok(());
+        };
+        let records = table.records();
+        for (index, record) in records.iter().enumerate() {
+            let parent = records
+                .get(record.parent as usize - 1)
+                .map(|parent| parent.extent_loc);
+            let matches = walk.directories.get(&record.extent_loc).is_some_and(|dir| {
+                dir.identifier == record.identifier && Some(dir.parent) == parent
+            });
+            if !matches {
+                let kind = FindingKind::PathTableMismatch { index };
+                self.findings.push(path_table_finding(*loc, kind));
+            }
+        }
+
+        let recorded: BTreeSet<u32> = records.iter().map(|record| record.extent_loc).collect();
+        for (loc, dir) in &walk.directories {
+            if let (false, Some(index)) = (recorded.contains(loc), dir.record) {
+                self.findings.push(Finding::record(
+                    walk.hierarchy,
+                    &walk.records[index].location,
+                    FindingKind::MissingFromPathTable,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Report the files of each hierarchy without a file of the same
+    /// extent in the other
+    #[cfg(feature = "joliet")]
+    fn check_divergence(&mut self, primary: &Walk, joliet: &Walk) {
+        let files = |walk: &Walk| -> BTreeSet<(u32, u32)> {
+            walk.records
+                .iter()
+                .filter(|record| !record.header.file_flags.contains(FileFlags::DIRECTORY))
+                .filter(|record| record.header.extent_length != 0)
+                .map(|record| (record.header.extent_loc, record.header.extent_length))
+                .collect()
+        };
+        for (walk, other) in [(primary, files(joliet)), (joliet, files(primary))] {
+            for record in &walk.records {
+                let header = &record.header;
+                if !header.file_flags.contains(FileFlags::DIRECTORY)
+                    && header.extent_length != 0
+                    && !other.contains(&(header.extent_loc, header.extent_length))
+                {
+                    self.findings.push(Finding::record(
+                        walk.hierarchy,
+                        &record.location,
+                        FindingKind::HierarchiesDiffer,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl<T: ISO9660Reader> ISO9660<T> {
+    /// Check the structures of the image for problems: both-endian fields
+    /// whose values differ, extents outside the volume or overlapping,
+    /// invalid or unsorted directory records, path tables not matching the
+    /// directories, and (with the `joliet` feature) files missing from the
+    /// primary or Joliet hierarchy. Rock Ridge extensions aren't checked.
+    ///
+    /// Returns an empty list for a consistent image. Only errors reading
+    /// the image are returned as `Err`.
+    pub fn verify(&self) -> Result<Vec<Finding>, ISOError<ReaderError!(T)>> {
+        let mut checker = Checker {
+            fs: self,
+            block_size: self.block_size() as usize,
+            findings: Vec::new(),
+        };
+        checker.check_descriptors()?;
+
+        let primary = checker.walk(
+            Hierarchy::Primary,
+            DirectoryEntryReader::Primary,
+            &self.primary.root_directory_entry,
+        )?;
+        let primary_tables = (
+            self.primary.path_table_size,
+            self.primary.path_table_loc,
+            self.primary.path_table_loc_be,
+        );
+        #[cfg(feature = "joliet")]
+        let joliet = match &self.joliet {
+            Some(joliet) => {
+                let walk = checker.walk(
+                    Hierarchy::Joliet,
+                    DirectoryEntryReader::Joliet,
+                    &joliet.root_directory_entry,
+                )?;
+                let tables = (
+                    joliet.path_table_size,
+                    joliet.path_table_loc,
+                    joliet.path_table_loc_be,
+                );
+                Some((walk, tables))
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "joliet"))]
+        let joliet = None;
+        let walks: Vec<(Walk, (u32, u32, u32))> = iter::once((primary, primary_tables))
+            .chain(joliet)
+            .collect();
+
+        for (walk, tables) in &walks {
+            checker.check_path_tables(walk, *tables)?;
+        }
+        let walks: Vec<Walk> = walks.into_iter().map(|(walk, _)| walk).collect();
+        checker.check_overlaps(&walks);
+        #[cfg(feature = "joliet")]
+        if let [primary, joliet] = &walks[..] {
+            checker.check_divergence(primary, joliet);
+        }
+
+        Ok(checker.findings)
+    }
+}
@@ -0,0 +1,29 @@
+// SPDX-License-Identifier: (MIT OR Apache-2.0)
+
+/// Problem worked around in lenient mode, see `ISO9660Options::lenient`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Warning {
+    /// Logical block where the problem was found
+    pub lba: u64,
+    /// Byte offset of the problem in that block
+    pub offset: u32,
+    pub kind: WarningKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningKind {
+    /// The image ended before the end of a read, the rest was filled with
+    /// zeros
+    ShortRead,
+    /// A read failed, and was filled with zeros
+    ReadFailed,
+    /// A directory record couldn't be parsed, and was skipped. Without a
+    /// usable record length, the rest of the block is skipped too.
+    InvalidRecord,
+    /// The version suffix of a file identifier isn't a number. It was
+    /// removed, assuming version 1.
+    InvalidVersion,
+    /// The records of a multi-extent file end before its last extent. The
+    /// file only has the extents found.
+    MissingExtent,
+}
@@ -3,10 +3,12 @@
 extern crate iso9660;
 extern crate md5;
 
-use iso9660::io::Read;
+use iso9660::io::{Read, Seek, SeekFrom};
 use iso9660::{DirectoryEntry, ISO9660};
 use std::fs::File;
-use std::io::{self, Read as _, Seek as _};
+use std::io;
+#[cfg(not(feature = "std"))]
+use std::io::{Read as _, Seek as _};
 
 #[derive(Debug)]
 struct MyError(std::io::Error);
@@ -43,6 +45,98 @@ impl embedded_io::Seek for MyFile {
     }
 }
 
+/// In-memory image, for writing images and reading them back
+#[derive(Clone, Default)]
+struct MemFile {
+    data: Vec<u8>,
+    pos: usize,
+}
+impl embedded_io::ErrorType for MemFile {
+    type Error = embedded_io::ErrorKind;
+}
+impl embedded_io::Read for MemFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let data = self.data.get(self.pos..).unwrap_or(&[]);
+        let count = buf.len().min(data.len());
+        buf[..count].copy_from_slice(&data[..count]);
+        self.pos += count;
+        Ok(count)
+    }
+}
+impl embedded_io::Write for MemFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.data.len() < self.pos + buf.len() {
+            self.data.resize(self.pos + buf.len(), 0);
+        }
+        self.data[self.pos..self.pos + buf.len()].copy_from_slice(buf);
+        self.pos += buf.len();
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+impl embedded_io::Seek for MemFile {
+    fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Self::Error> {
+        self.pos = match pos {
+            embedded_io::SeekFrom::Start(i) => i as i64,
+            embedded_io::SeekFrom::End(i) => self.data.len() as i64 + i,
+            embedded_io::SeekFrom::Current(i) => self.pos as i64 + i,
+        } as usize;
+        Ok(self.pos as u64)
+    }
+}
+
+/// With the `std` feature, images are read and written through the std
+/// traits: implement them with the `embedded_io` ones
+#[cfg(feature = "std")]
+macro_rules! impl_std_io {
+    ($t:ty) => {
+        impl std::io::Read for $t {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                embedded_io::Read::read(self, buf)
+                    .map_err(|err| io::Error::other(format!("{:?}", err)))
+            }
+        }
+        impl std::io::Seek for $t {
+            fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+                let pos = match pos {
+                    io::SeekFrom::Start(i) => embedded_io::SeekFrom::Start(i),
+                    io::SeekFrom::End(i) => embedded_io::SeekFrom::End(i),
+                    io::SeekFrom::Current(i) => embedded_io::SeekFrom::Current(i),
+                };
+                embedded_io::Seek::seek(self, pos)
+                    .map_err(|err| io::Error::other(format!("{:?}", err)))
+            }
+        }
+    };
+}
+#[cfg(feature = "std")]
+impl_std_io!(MyFile);
+#[cfg(feature = "std")]
+impl_std_io!(MemFile);
+#[cfg(feature = "std")]
+impl std::io::Write for MemFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(embedded_io::Write::write(self, buf).unwrap())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn read_to_end<R: Read>(mut reader: R) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut buf = [0; 5000];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return data,
+            Ok(count) => data.extend_from_slice(&buf[..count]),
+            Err(_) => panic!("Read failed"),
+        }
+    }
+}
+
 #[test]
 fn test_dir_joliet() {
     let fs = ISO9660::new(MyFile(File::open("test_joliet.iso").unwrap())).unwrap();
@@ -72,6 +166,8 @@ fn test_dir() {
 }
 
 #[test]
+// `read` fills the whole buffer, which clippy can't know with the std traits
+#[cfg_attr(feature = "std", allow(clippy::unused_io_amount))]
 fn test_large_file() {
     let fs = ISO9660::new(MyFile(File::open("test.iso").unwrap())).unwrap();
 
@@ -88,6 +184,452 @@ fn test_large_file() {
     assert_eq!(format!("{:x}", hash), "1ebbd3e34237af26da5dc08a4e440464");
 }
 
+/// An image in memory, recording the size of each read
+struct TransferFile {
+    data: Vec<u8>,
+    max_transfer_size: usize,
+    reads: std::rc::Rc<std::cell::RefCell<Vec<usize>>>,
+}
+impl embedded_io::ErrorType for TransferFile {
+    type Error = MyError;
+}
+impl iso9660::ISO9660Reader for TransferFile {
+    fn read_at(
+        &mut self,
+        buf: &mut [u8],
+        lba: u64,
+    ) -> Result<usize, iso9660::ReaderError!(TransferFile)> {
+        self.reads.borrow_mut().push(buf.len());
+        let data = self.data.get(lba as usize * 2048..).unwrap_or(&[]);
+        let count = buf.len().min(data.len());
+        buf[..count].copy_from_slice(&data[..count]);
+        Ok(count)
+    }
+
+    fn max_transfer_size(&self) -> usize {
+        self.max_transfer_size
+    }
+}
+
+#[test]
+fn test_multi_block_reads() {
+    use iso9660::ISOBuilder;
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let mut builder = ISOBuilder::new();
+    builder.root().add_file("data.bin", data.as_slice());
+    let mut image = MemFile::default();
+    builder.write(&mut image).unwrap();
+
+    for (max_transfer_size, expected) in [
+        // 48 whole blocks in one read, then the last block
+        (usize::MAX, vec![48 * 2048, 2048]),
+        // Split in reads of 6 sectors
+        (
+            6 * 2048 + 100,
+            vec![6 * 2048; 8].into_iter().chain([2048]).collect(),
+        ),
+        (100, vec![2048; 49]),
+    ] {
+        let reads = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let fs = ISO9660::new(TransferFile {
+            data: image.data.clone(),
+            max_transfer_size,
+            reads: reads.clone(),
+        })
+        .unwrap();
+        let DirectoryEntry::File(file) = fs.open("data.bin").unwrap().unwrap() else {
+            panic!("Not a file");
+        };
+
+        reads.borrow_mut().clear();
+        let mut buf = vec![0; data.len()];
+        file.read().read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data);
+        assert_eq!(*reads.borrow(), expected);
+    }
+
+    // Unaligned reads go through a block first
+    let reads = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let fs = ISO9660::new(TransferFile {
+        data: image.data.clone(),
+        max_transfer_size: usize::MAX,
+        reads: reads.clone(),
+    })
+    .unwrap();
+    let DirectoryEntry::File(file) = fs.open("data.bin").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    reads.borrow_mut().clear();
+    let mut reader = file.read();
+    reader.seek(SeekFrom::Start(1000)).unwrap();
+    let mut buf = vec![0; 10_000];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, data[1000..11_000]);
+    assert_eq!(*reads.borrow(), [2048, 4 * 2048, 2048]);
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn test_sync() {
+    use iso9660::{ISO9660Options, ISOBuilder};
+
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let files: Vec<(String, Vec<u8>)> = (0..8u8)
+        .map(|i| (format!("file{}.bin", i), vec![i; 3000 * (i as usize + 1)]))
+        .collect();
+    let mut builder = ISOBuilder::new();
+    let root = builder.root();
+    for (name, data) in &files {
+        root.add_file(name, data.as_slice());
+    }
+    let mut image = MemFile::default();
+    builder.write(&mut image).unwrap();
+
+    // Sharing the cache between threads too
+    let fs = ISO9660::with_options(image.clone(), ISO9660Options::new().cache(4)).unwrap();
+    assert_send_sync(&fs);
+    std::thread::scope(|scope| {
+        for (name, data) in &files {
+            let fs = &fs;
+            scope.spawn(move || {
+                let DirectoryEntry::File(file) = fs.open(name).unwrap().unwrap() else {
+                    panic!("Not a file");
+                };
+                assert_send_sync(&file);
+                let reader = file.read();
+                assert_send_sync(&reader);
+                assert_eq!(&read_to_end(reader), data);
+            });
+        }
+    });
+
+    // Without waiting for each other to read
+    let fs = ISO9660::new(iso9660::PositionalReader(image.data.as_slice())).unwrap();
+    assert_send_sync(&fs);
+    std::thread::scope(|scope| {
+        for (name, data) in &files {
+            let fs = &fs;
+            scope.spawn(move || {
+                let DirectoryEntry::File(file) = fs.open(name).unwrap().unwrap() else {
+                    panic!("Not a file");
+                };
+                assert_eq!(&read_to_end(file.read()), data);
+            });
+        }
+    });
+}
+
+#[test]
+fn test_positional_reader() {
+    use iso9660::{ISO9660Options, PositionalReader};
+
+    let data = std::fs::read("test.iso").unwrap();
+    for cache in [0, 8] {
+        let fs = ISO9660::with_options(
+            PositionalReader(data.as_slice()),
+            ISO9660Options::new().cache(cache),
+        )
+        .unwrap();
+        let DirectoryEntry::File(file) = fs.open("gpl_3_0.txt").unwrap().unwrap() else {
+            panic!("Not a file");
+        };
+        let hash = md5::compute(read_to_end(file.read()));
+        assert_eq!(format!("{:x}", hash), "1ebbd3e34237af26da5dc08a4e440464");
+        assert!(fs.open("a/b/c/200").unwrap().is_some());
+    }
+
+    // Reads past the end of the data are short
+    let fs = ISO9660::new(PositionalReader(data.clone())).unwrap();
+    let DirectoryEntry::File(file) = fs.open("gpl_3_0.txt").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    let end = (file.extents()[0].loc as usize * 2048 + 1000).min(data.len());
+    let fs = ISO9660::new(PositionalReader(&data[..end])).unwrap();
+    let DirectoryEntry::File(file) = fs.open("gpl_3_0.txt").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    assert_eq!(read_to_end(file.read()).len(), 1000);
+}
+
+#[test]
+fn test_slice_reader() {
+    use iso9660::{PositionalReader, SliceReader};
+
+    let data = std::fs::read("test.iso").unwrap();
+    let fs: ISO9660<SliceReader> = ISO9660::new(PositionalReader(data.as_slice())).unwrap();
+    let DirectoryEntry::File(file) = fs.open("gpl_3_0.txt").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    // Borrowed from the image, not copied
+    let contents = file.as_slice().unwrap();
+    assert!(data.as_ptr_range().contains(&contents.as_ptr()));
+    let hash = md5::compute(contents);
+    assert_eq!(format!("{:x}", hash), "1ebbd3e34237af26da5dc08a4e440464");
+
+    // Records are named like entries
+    let names: Vec<&str> = fs
+        .root
+        .records()
+        .map(|record| record.unwrap().name().unwrap())
+        .collect();
+    let identifiers: Vec<String> = fs
+        .root
+        .contents()
+        .map(|entry| entry.unwrap().identifier().to_string())
+        .collect();
+    assert_eq!(names, identifiers);
+    assert_eq!(names[..2], [".", ".."]);
+
+    // Down to a file through the records of each directory
+    let mut records = fs.root.records();
+    for name in ["A", "B", "C"] {
+        let record = records
+            .find(|record| record.as_ref().unwrap().name() == Some(name))
+            .unwrap()
+            .unwrap();
+        records = record.records().unwrap();
+    }
+    let record = records
+        .find(|record| record.as_ref().unwrap().name() == Some("200"))
+        .unwrap()
+        .unwrap();
+    assert!(!record.is_directory());
+    assert!(record.records().is_none());
+    let DirectoryEntry::File(file) = fs.open("a/b/c/200").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    assert_eq!(record.data(), file.as_slice());
+    assert_eq!(record.data().unwrap(), read_to_end(file.read()));
+
+    // Not recorded contiguously
+    let data = std::fs::read("test_rockridge_special.iso").unwrap();
+    let fs = ISO9660::new(PositionalReader(data.as_slice())).unwrap();
+    let DirectoryEntry::File(file) = fs.open("sparse").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    assert!(file.as_slice().is_none());
+
+    // Contiguous extents of a multi-extent file
+    let data = std::fs::read("test_multiextent.iso").unwrap();
+    let fs = ISO9660::new(PositionalReader(data.as_slice())).unwrap();
+    let DirectoryEntry::File(file) = fs.open("big.bin").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    assert_eq!(file.as_slice().unwrap(), read_to_end(file.read()));
+
+    // The image ends before the data
+    let end = (file.extents()[0].loc as usize + 1) * 2048;
+    let fs = ISO9660::new(PositionalReader(&data[..end])).unwrap();
+    let DirectoryEntry::File(file) = fs.open("big.bin").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    assert!(file.as_slice().is_none());
+}
+
+/// `MemFile`, with reads pending once before completing
+#[cfg(feature = "async")]
+struct AsyncMemFile(MemFile);
+#[cfg(feature = "async")]
+impl embedded_io_async::ErrorType for AsyncMemFile {
+    type Error = embedded_io::ErrorKind;
+}
+#[cfg(feature = "async")]
+impl embedded_io_async::Read for AsyncMemFile {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut pending = true;
+        std::future::poll_fn(|cx| {
+            if std::mem::take(&mut pending) {
+                cx.waker().wake_by_ref();
+                return std::task::Poll::Pending;
+            }
+            std::task::Poll::Ready(())
+        })
+        .await;
+        embedded_io::Read::read(&mut self.0, buf)
+    }
+}
+#[cfg(feature = "async")]
+impl embedded_io_async::Seek for AsyncMemFile {
+    async fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Self::Error> {
+        embedded_io::Seek::seek(&mut self.0, pos)
+    }
+}
+
+#[cfg(feature = "async")]
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    struct Noop;
+    impl std::task::Wake for Noop {
+        fn wake(self: std::sync::Arc<Self>) {}
+    }
+
+    let mut future = std::pin::pin!(future);
+    let waker = std::task::Waker::from(std::sync::Arc::new(Noop));
+    let mut cx = std::task::Context::from_waker(&waker);
+    loop {
+        if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async() {
+    use embedded_io_async::{Read as _, Seek as _};
+    use iso9660::{AsyncDirectoryEntry, AsyncISO9660, ISO9660Options, ISOError};
+
+    let data = std::fs::read("test.iso").unwrap();
+    let image = AsyncMemFile(MemFile {
+        data: data.clone(),
+        pos: 0,
+    });
+    block_on(async {
+        let mut fs = AsyncISO9660::new(image).await.unwrap();
+        assert_eq!(fs.block_size(), 2048);
+
+        let root = fs.root();
+        let mut names = Vec::new();
+        let mut entries = fs.contents(&root);
+        while let Some(entry) = entries.next().await {
+            names.push(entry.unwrap().identifier().to_string());
+        }
+        drop(entries);
+        let sync_fs = ISO9660::new(MyFile(File::open("test.iso").unwrap())).unwrap();
+        let sync_names: Vec<String> = sync_fs
+            .root
+            .contents()
+            .map(|entry| entry.unwrap().identifier().to_string())
+            .collect();
+        assert_eq!(names, sync_names);
+
+        let Some(AsyncDirectoryEntry::File(file)) = fs.open("gpl_3_0.txt").await.unwrap() else {
+            panic!("Not a file");
+        };
+        let mut reader = fs.read(&file);
+        let mut contents = Vec::new();
+        let mut buf = [0; 5000];
+        loop {
+            match reader.read(&mut buf).await.unwrap() {
+                0 => break,
+                count => contents.extend_from_slice(&buf[..count]),
+            }
+        }
+        let hash = md5::compute(&contents);
+        assert_eq!(format!("{:x}", hash), "1ebbd3e34237af26da5dc08a4e440464");
+
+        let pos = reader
+            .seek(embedded_io::SeekFrom::Start(100))
+            .await
+            .unwrap();
+        assert_eq!(pos, 100);
+        let count = reader.read(&mut buf[..50]).await.unwrap();
+        assert_eq!(&buf[..count], &contents[100..100 + count]);
+
+        assert!(fs.open("a/b/c/200").await.unwrap().is_some());
+        assert!(fs.open("missing").await.unwrap().is_none());
+
+        // The sectors of the directories were cached by the first lookup
+        let hits = fs.cache_stats().hits;
+        assert!(fs.open("a/b/c/200").await.unwrap().is_some());
+        assert!(fs.cache_stats().hits > hits);
+
+        let image = AsyncMemFile(MemFile { data, pos: 0 });
+        let options = ISO9660Options::new().lenient(true).clone();
+        assert!(matches!(
+            AsyncISO9660::with_options(image, &options).await,
+            Err(ISOError::Unsupported(_))
+        ));
+    });
+}
+
+/// An image in memory read asynchronously, counting the reads
+#[cfg(feature = "async")]
+struct CountingAsyncFile {
+    data: Vec<u8>,
+    reads: std::rc::Rc<std::cell::Cell<usize>>,
+}
+#[cfg(feature = "async")]
+impl embedded_io_async::ErrorType for CountingAsyncFile {
+    type Error = embedded_io::ErrorKind;
+}
+#[cfg(feature = "async")]
+impl iso9660::AsyncISO9660Reader for CountingAsyncFile {
+    async fn read_at(&mut self, buf: &mut [u8], lba: u64) -> Result<usize, Self::Error> {
+        self.reads.set(self.reads.get() + 1);
+        let data = self.data.get(lba as usize * 2048..).unwrap_or(&[]);
+        let count = buf.len().min(data.len());
+        buf[..count].copy_from_slice(&data[..count]);
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_reads() {
+    use iso9660::{AsyncDirectoryEntry, AsyncISO9660, ISO9660Options};
+
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let image = CountingAsyncFile {
+        data: std::fs::read("test.iso").unwrap(),
+        reads: reads.clone(),
+    };
+    block_on(async {
+        // Directories are read whole, one at a time, so 4 sectors are enough
+        let options = ISO9660Options::new().cache(4).clone();
+        let mut fs = AsyncISO9660::with_options(image, &options).await.unwrap();
+
+        // One read for each directory searched, a and a/b: the root was
+        // cached when opening the image
+        let opened = reads.get();
+        let Some(AsyncDirectoryEntry::Directory(dir)) = fs.open("a/b/c").await.unwrap() else {
+            panic!("Not a directory");
+        };
+        assert_eq!(reads.get() - opened, 2);
+
+        // One read for the 202 entries of a/b/c, then none to list them again
+        for expected in [3, 3] {
+            let mut entries = fs.contents(&dir);
+            let mut count = 0;
+            while let Some(entry) = entries.next().await {
+                entry.unwrap();
+                count += 1;
+            }
+            assert_eq!(count, 202);
+            assert_eq!(reads.get() - opened, expected);
+        }
+    });
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_tokio() {
+    use iso9660::{AsyncDirectoryEntry, AsyncISO9660, TokioReader};
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let image = tokio::fs::File::open("test.iso").await.unwrap();
+    let mut fs = AsyncISO9660::new(TokioReader(image)).await.unwrap();
+    let Some(AsyncDirectoryEntry::File(file)) = fs.open("gpl_3_0.txt").await.unwrap() else {
+        panic!("Not a file");
+    };
+
+    let mut reader = fs.read(&file);
+    let mut contents = Vec::new();
+    reader.read_to_end(&mut contents).await.unwrap();
+    assert_eq!(contents.len() as u64, file.size());
+    let hash = md5::compute(&contents);
+    assert_eq!(format!("{:x}", hash), "1ebbd3e34237af26da5dc08a4e440464");
+
+    // From the middle of a block to the end
+    let middle = contents.len() as u64 / 2 + 7;
+    let pos = reader.seek(io::SeekFrom::Start(middle)).await.unwrap();
+    assert_eq!(pos, middle);
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).await.unwrap();
+    assert_eq!(rest, contents[middle as usize..]);
+}
+
 #[test]
 fn test_extra_slashes() {
     let fs = ISO9660::new(MyFile(File::open("test.iso").unwrap())).unwrap();
@@ -111,3 +653,1825 @@ fn test_large_dir() {
     assert_eq!(dir.contents().map(Result::unwrap).count(), 202);
     assert_eq!(dir.block_count(), 4);
 }
+
+#[test]
+fn test_block_size_512() {
+    let fs = ISO9660::new(MyFile(File::open("test_512.iso").unwrap())).unwrap();
+    assert_eq!(fs.block_size(), 512);
+
+    let dir = match fs.open("many").unwrap().unwrap() {
+        DirectoryEntry::Directory(dir) => dir,
+        _ => panic!("Not a directory"),
+    };
+    // 40 files, plus '.' and '..'
+    assert_eq!(dir.contents().map(Result::unwrap).count(), 42);
+    assert_eq!(dir.block_count(), 4);
+
+    let read = |path: &str| -> Vec<u8> {
+        match fs.open(path).unwrap().unwrap() {
+            DirectoryEntry::File(file) => read_to_end(file.read()),
+            _ => panic!("Not a file"),
+        }
+    };
+    assert_eq!(read("many/file39.txt"), b"file 39\n");
+    assert_eq!(read("small.txt"), b"small\n");
+    let data: Vec<u8> = (0..5000).map(|i| ((i * 7 + i / 251) % 256) as u8).collect();
+    assert_eq!(read("data.bin"), data);
+
+    // Seek to the middle of a block
+    let DirectoryEntry::File(file) = fs.open("data.bin").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    let mut reader = file.read();
+    reader.seek(SeekFrom::Start(1000)).unwrap();
+    assert_eq!(read_to_end(reader), &data[1000..]);
+}
+
+#[test]
+fn test_multi_extent() {
+    use iso9660::Extent;
+
+    let fs = ISO9660::new(MyFile(File::open("test_multiextent.iso").unwrap())).unwrap();
+
+    let names: Vec<String> = fs
+        .root
+        .contents()
+        .skip(2)
+        .map(|entry| entry.unwrap().identifier().to_string())
+        .collect();
+    assert_eq!(names, ["AFTER.TXT", "BIG.BIN", "SMALL.TXT"]);
+
+    let DirectoryEntry::File(file) = fs.open("big.bin").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    let loc = file.header.extent_loc;
+    assert_eq!(
+        file.extents(),
+        [
+            Extent { loc, length: 4096 },
+            Extent {
+                loc: loc + 2,
+                length: 2048
+            },
+            Extent {
+                loc: loc + 3,
+                length: 1000
+            },
+        ]
+    );
+    assert_eq!(file.size(), 7144);
+
+    let data: Vec<u8> = (0..7144)
+        .map(|i| ((i * 13 + i / 2048) % 256) as u8)
+        .collect();
+    assert_eq!(read_to_end(file.read()), data);
+
+    // Seek across extents
+    let mut reader = file.read();
+    reader.seek(SeekFrom::Start(6000)).unwrap();
+    assert_eq!(read_to_end(reader), &data[6000..]);
+
+    assert!(fs.open("small.txt").unwrap().is_some());
+}
+
+#[test]
+fn test_path_table() {
+    use iso9660::PathTableType;
+
+    let fs = ISO9660::new(MyFile(File::open("test.iso").unwrap())).unwrap();
+
+    let path_table = fs.path_table().unwrap();
+    let records: Vec<(&str, u16)> = path_table
+        .records()
+        .iter()
+        .map(|record| (record.identifier.as_str(), record.parent))
+        .collect();
+    assert_eq!(records, [("", 1), ("A", 1), ("B", 2), ("C", 3)]);
+    assert_eq!(fs.read_path_table(PathTableType::M).unwrap(), path_table);
+
+    let c = path_table.find("/a/b/c/").unwrap();
+    let dir = fs.open("a/b/c").unwrap().unwrap();
+    assert_eq!(c.extent_loc, dir.header().extent_loc);
+    assert!(path_table.find("a/c").is_none());
+
+    // The Joliet path table records names in UCS-2
+    let fs = ISO9660::new(MyFile(File::open("test_joliet.iso").unwrap())).unwrap();
+    let path_table = fs.path_table().unwrap();
+    assert_eq!(path_table.records()[3].identifier, "C");
+}
+
+/// Counts the sectors read from a file
+struct CountingFile {
+    file: MyFile,
+    reads: std::rc::Rc<std::cell::Cell<usize>>,
+}
+impl embedded_io::ErrorType for CountingFile {
+    type Error = MyError;
+}
+impl embedded_io::Read for CountingFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.reads.set(self.reads.get() + 1);
+        embedded_io::Read::read(&mut self.file, buf)
+    }
+}
+impl embedded_io::Seek for CountingFile {
+    fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Self::Error> {
+        embedded_io::Seek::seek(&mut self.file, pos)
+    }
+}
+#[cfg(feature = "std")]
+impl_std_io!(CountingFile);
+
+#[test]
+fn test_path_table_lookup() {
+    use iso9660::{Hierarchy, ISO9660Options};
+
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let file = CountingFile {
+        file: MyFile(File::open("test.iso").unwrap()),
+        reads: reads.clone(),
+    };
+    let mut fs = ISO9660::new(file).unwrap();
+
+    reads.set(0);
+    assert!(fs.open("a/b/c/200").unwrap().is_some());
+    let without_path_table = reads.get();
+
+    fs.set_path_table_lookup(true).unwrap();
+    reads.set(0);
+    let DirectoryEntry::File(file) = fs.open("a/b/c/200").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    assert!(reads.get() < without_path_table);
+    assert_eq!(file.identifier, "200");
+
+    assert!(fs.open("a/b/c").unwrap().is_some());
+    assert!(fs.open("/A/B//").unwrap().is_some());
+    assert!(fs.open("gpl_3_0.txt").unwrap().is_some());
+    assert!(fs.open("/").unwrap().is_some());
+    assert!(fs.open("a/c/1").unwrap().is_none());
+    assert!(fs.open("a/b/c/201").unwrap().is_none());
+
+    // Rock Ridge names aren't in the path table, so the option is ignored
+    let mut fs = ISO9660::with_options(
+        MyFile(File::open("test_rockridge.iso").unwrap()),
+        ISO9660Options::new().path_table_lookup(true),
+    )
+    .unwrap();
+    assert_eq!(fs.hierarchy(), Hierarchy::RockRidge);
+    assert!(fs
+        .open("Mixed_Case_Dir/a file with spaces")
+        .unwrap()
+        .is_some());
+    assert!(fs.open("mixed_case_dir").unwrap().is_none());
+    fs.set_path_table_lookup(true).unwrap();
+    assert!(fs.open("LONG_file_name.txt").unwrap().is_some());
+}
+
+#[test]
+fn test_cache() {
+    use iso9660::{CacheStats, ISO9660Options};
+
+    let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let file = CountingFile {
+        file: MyFile(File::open("test.iso").unwrap()),
+        reads: reads.clone(),
+    };
+    let fs = ISO9660::with_options(file, ISO9660Options::new().cache(64)).unwrap();
+    let opened = fs.cache_stats().unwrap();
+    assert_eq!(opened.capacity, 64);
+
+    reads.set(0);
+    assert!(fs.open("a/b/c/200").unwrap().is_some());
+    let first = reads.get();
+    let stats = fs.cache_stats().unwrap();
+    assert!(first > 0);
+    assert_eq!((stats.misses - opened.misses) as usize, first);
+
+    reads.set(0);
+    assert!(fs.open("a/b/c/200").unwrap().is_some());
+    assert_eq!(reads.get(), 0);
+    let again = fs.cache_stats().unwrap();
+    assert_eq!(again.misses, stats.misses);
+    assert!(again.hits > stats.hits);
+    assert_eq!(again.len, opened.len + first);
+
+    let DirectoryEntry::File(file) = fs.open("gpl_3_0.txt").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    let uncached = ISO9660::new(MyFile(File::open("test.iso").unwrap())).unwrap();
+    let DirectoryEntry::File(expected) = uncached.open("gpl_3_0.txt").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    assert_eq!(read_to_end(file.read()), read_to_end(expected.read()));
+    assert!(fs.cache_stats().unwrap().len > again.len);
+
+    // Reading the file again, with a new reader, only hits the cache
+    let read = fs.cache_stats().unwrap();
+    reads.set(0);
+    assert_eq!(read_to_end(file.read()), read_to_end(expected.read()));
+    assert_eq!(reads.get(), 0);
+    let reread = fs.cache_stats().unwrap();
+    assert_eq!(reread.misses, read.misses);
+    assert_eq!(reread.hits - read.hits, file.size().div_ceil(2048));
+
+    // With room for one sector, alternating between two always misses
+    let fs = ISO9660::with_options(
+        MyFile(File::open("test.iso").unwrap()),
+        ISO9660Options::new().cache(1),
+    )
+    .unwrap();
+    let DirectoryEntry::File(file) = fs.open("gpl_3_0.txt").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    let before = fs.cache_stats().unwrap();
+    let mut reader = file.read();
+    let mut buf = [0; 16];
+    for pos in [0, 4096, 0, 4096] {
+        reader.seek(SeekFrom::Start(pos)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+    }
+    let after = fs.cache_stats().unwrap();
+    assert_eq!(
+        after,
+        CacheStats {
+            hits: before.hits,
+            misses: before.misses + 4,
+            len: 1,
+            capacity: 1,
+        }
+    );
+
+    assert_eq!(
+        ISO9660::new(MyFile(File::open("test.iso").unwrap()))
+            .unwrap()
+            .cache_stats(),
+        None
+    );
+}
+
+#[test]
+fn test_volume_descriptors() {
+    use iso9660::VolumeDescriptor;
+
+    let fs = ISO9660::new(MyFile(File::open("test_descriptors.iso").unwrap())).unwrap();
+
+    let descriptors = fs.volume_descriptors();
+    let lbas: Vec<u32> = descriptors.iter().map(|entry| entry.lba).collect();
+    assert_eq!(lbas, [16, 17, 18, 19, 20, 21]);
+
+    let VolumeDescriptor::Primary(primary) = &descriptors[0].descriptor else {
+        panic!("Not a primary volume descriptor");
+    };
+    assert_eq!(primary.info.volume_identifier, "DESCRIPTORS");
+    assert_eq!(
+        fs.primary_volume_descriptor().info.volume_space_size,
+        primary.info.volume_space_size
+    );
+
+    let VolumeDescriptor::SupplementaryVolumeDescriptor(joliet) = &descriptors[1].descriptor else {
+        panic!("Not a supplementary volume descriptor");
+    };
+    assert!(joliet.is_joliet);
+    assert_eq!(joliet.version, 1);
+
+    let VolumeDescriptor::VolumePartition(partition) = &descriptors[2].descriptor else {
+        panic!("Not a volume partition descriptor");
+    };
+    assert_eq!(partition.system_identifier, "PARTSYS");
+    assert_eq!(partition.volume_partition_identifier, "PARTITION_1");
+    assert_eq!(
+        partition.volume_partition_location + partition.volume_partition_size,
+        primary.info.volume_space_size
+    );
+    assert_eq!(partition.system_use, [0xab; 1960]);
+
+    // An enhanced volume descriptor isn't Joliet
+    let VolumeDescriptor::SupplementaryVolumeDescriptor(enhanced) = &descriptors[3].descriptor
+    else {
+        panic!("Not a supplementary volume descriptor");
+    };
+    assert!(!enhanced.is_joliet);
+    assert_eq!(enhanced.version, 2);
+
+    let VolumeDescriptor::Unknown {
+        type_,
+        version,
+        data,
+    } = &descriptors[4].descriptor
+    else {
+        panic!("Not an unknown volume descriptor");
+    };
+    assert_eq!((*type_, *version), (42, 1));
+    assert_eq!(data.len(), 2041);
+
+    assert!(matches!(
+        descriptors[5].descriptor,
+        VolumeDescriptor::VolumeDescriptorSetTerminator
+    ));
+
+    // The Joliet hierarchy is still found
+    assert!(fs.open("ReadMe.txt").unwrap().is_some());
+}
+
+#[test]
+fn test_volume_info() {
+    let fs = ISO9660::new(MyFile(File::open("test.iso").unwrap())).unwrap();
+
+    let info = fs.volume_info();
+    assert_eq!(info.system_identifier, "LINUX");
+    assert_eq!(info.volume_identifier, "CDROM");
+    assert_eq!(info.logical_block_size, 2048);
+    assert_eq!((info.volume_set_size, info.volume_sequence_number), (1, 1));
+    assert!(info.application_identifier.starts_with("MKISOFS"));
+    assert_eq!(info.copyright_file_identifier, "");
+    assert_eq!(info.file_structure_version, 1);
+
+    let creation = info.creation_time.unwrap();
+    assert_eq!(
+        (creation.year(), creation.month() as u8, creation.day()),
+        (2018, 3, 20)
+    );
+    assert_eq!(
+        (creation.hour(), creation.minute(), creation.second()),
+        (14, 24, 3)
+    );
+    assert_eq!(creation.millisecond(), 120);
+    assert_eq!(creation.offset().whole_hours(), -7);
+    assert_eq!(info.modification_time, info.creation_time);
+    assert_eq!(info.expiration_time, None);
+}
+
+#[cfg(feature = "joliet")]
+#[test]
+fn test_joliet_volume_info() {
+    use iso9660::{ISOBuilder, JolietNames};
+
+    // Joliet records text in UCS-2, so it isn't limited to d-characters
+    let mut builder = ISOBuilder::new();
+    builder
+        .volume_identifier("Données été")
+        .publisher_identifier("Éditions ☃")
+        .joliet(Some(JolietNames::Standard));
+    let mut image = MemFile::default();
+    builder.write(&mut image).unwrap();
+    let fs = ISO9660::new(image).unwrap();
+
+    let joliet = fs.joliet_volume_info().unwrap();
+    assert_eq!(joliet.volume_identifier, "Données été");
+    assert_eq!(joliet.publisher_identifier, "Éditions ☃");
+    assert_eq!(joliet.volume_space_size, fs.volume_info().volume_space_size);
+    assert_eq!(joliet.creation_time, fs.volume_info().creation_time);
+    assert!(joliet.creation_time.is_some());
+}
+
+#[test]
+fn test_rock_ridge_names() {
+    let fs = ISO9660::new(MyFile(File::open("test_rockridge.iso").unwrap())).unwrap();
+
+    let names: Vec<String> = fs
+        .root
+        .contents()
+        .map(|entry| entry.unwrap().identifier().to_string())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ".",
+            "..",
+            "long_file_name.txt",
+            "LONG_file_name.txt",
+            "Mixed_Case_Dir",
+            "PLAIN.TXT",
+            &format!(
+                "{}end.txt",
+                "a_name_long_enough_to_be_continued_in_another_block_".repeat(3)
+            ),
+        ]
+    );
+
+    // Names split across several NM entries are joined
+    assert!(fs
+        .open("Mixed_Case_Dir/a file with spaces")
+        .unwrap()
+        .is_some());
+    // Rock Ridge names are case sensitive
+    assert!(fs.open("mixed_case_dir").unwrap().is_none());
+    // Entries without Rock Ridge metadata are still matched ignoring case
+    assert!(fs.open("plain.txt").unwrap().is_some());
+
+    let file = match fs.open("LONG_file_name.txt").unwrap().unwrap() {
+        DirectoryEntry::File(file) => file,
+        _ => panic!("Not a file"),
+    };
+    let mut buf = vec![0; file.size() as usize];
+    file.read().read_exact(&mut buf).unwrap();
+    assert_eq!(buf, b"case\n");
+}
+
+#[test]
+fn test_hierarchy() {
+    use iso9660::{Hierarchy, ISO9660Options};
+
+    let open = |path: &str, hierarchy: Hierarchy| {
+        let mut options = ISO9660Options::new();
+        options.hierarchy(hierarchy);
+        ISO9660::with_options(MyFile(File::open(path).unwrap()), &options)
+    };
+    let names = |dir: iso9660::ISODirectory<MyFile>| -> Vec<String> {
+        dir.contents()
+            .skip(2)
+            .map(|entry| entry.unwrap().identifier().to_string())
+            .collect()
+    };
+
+    let fs = open("test_rockridge.iso", Hierarchy::Auto).unwrap();
+    assert_eq!(fs.hierarchy(), Hierarchy::RockRidge);
+    assert!(names(fs.rock_ridge_root().unwrap()).contains(&"Mixed_Case_Dir".to_string()));
+    assert!(names(fs.primary_root()).contains(&"MIXED_CA".to_string()));
+
+    let fs = open("test_rockridge.iso", Hierarchy::Primary).unwrap();
+    assert_eq!(fs.hierarchy(), Hierarchy::Primary);
+    assert!(fs.open("mixed_ca").unwrap().is_some());
+    assert!(fs.open("Mixed_Case_Dir").unwrap().is_none());
+
+    let fs = open("test.iso", Hierarchy::Auto).unwrap();
+    assert_eq!(fs.hierarchy(), Hierarchy::Primary);
+    assert!(fs.rock_ridge_root().is_none());
+    assert!(matches!(
+        open("test.iso", Hierarchy::RockRidge),
+        Err(iso9660::ISOError::InvalidFs(_))
+    ));
+}
+
+#[cfg(feature = "joliet")]
+#[test]
+fn test_hierarchy_joliet() {
+    use iso9660::{Hierarchy, ISO9660Options, ISOBuilder, JolietNames};
+
+    let mut builder = ISOBuilder::new();
+    builder.joliet(Some(JolietNames::Standard));
+    builder.root().add_file("Read Me.txt", b"Hello".as_slice());
+    let mut image = MemFile::default();
+    builder.write(&mut image).unwrap();
+
+    let open = |hierarchy: Hierarchy| {
+        let mut options = ISO9660Options::new();
+        options.hierarchy(hierarchy);
+        ISO9660::with_options(image.clone(), &options)
+    };
+    let names = |dir: iso9660::ISODirectory<MemFile>| -> Vec<String> {
+        dir.contents()
+            .skip(2)
+            .map(|entry| entry.unwrap().identifier().to_string())
+            .collect()
+    };
+
+    let fs = open(Hierarchy::Auto).unwrap();
+    assert_eq!(fs.hierarchy(), Hierarchy::Joliet);
+    assert_eq!(names(fs.joliet_root().unwrap()), ["Read Me.txt"]);
+    assert_eq!(names(fs.primary_root()), ["READ_ME.TXT"]);
+    assert!(fs.open("Read Me.txt").unwrap().is_some());
+
+    let fs = open(Hierarchy::Primary).unwrap();
+    assert_eq!(fs.hierarchy(), Hierarchy::Primary);
+    assert!(fs.joliet_root().is_some());
+    assert!(fs.open("read_me.txt").unwrap().is_some());
+
+    assert!(open(Hierarchy::RockRidge).is_err());
+    let mut options = ISO9660Options::new();
+    options.hierarchy(Hierarchy::Joliet);
+    assert!(ISO9660::with_options(MyFile(File::open("test.iso").unwrap()), &options).is_err());
+}
+
+#[test]
+fn test_lenient() {
+    use iso9660::{ISO9660Options, ISOBuilder, Warning, WarningKind};
+
+    let big = vec![0xaa; 10_000];
+    let mut builder = ISOBuilder::new();
+    let root = builder.root();
+    root.add_file("a.txt", b"first".as_slice());
+    root.add_file("b.txt", b"second".as_slice());
+    root.add_file("c.txt", b"third".as_slice());
+    root.add_file("d.dat", big.as_slice());
+    let mut image = MemFile::default();
+    builder.write(&mut image).unwrap();
+
+    let fs = ISO9660::new(image.clone()).unwrap();
+    let root_lba = DirectoryEntry::Directory(fs.root.clone())
+        .header()
+        .extent_loc;
+    let extent = match fs.open("d.dat").unwrap().unwrap() {
+        DirectoryEntry::File(file) => file.extents()[0],
+        _ => panic!("Not a file"),
+    };
+    assert!(extent.loc > root_lba);
+
+    // Damage the image: an invalid version, an identifier that isn't
+    // UTF-8, and the end of the image missing
+    let dir = root_lba as usize * 2048;
+    let find = |data: &[u8], name: &[u8]| {
+        dir + data[dir..dir + 2048]
+            .windows(name.len())
+            .position(|window| window == name)
+            .unwrap()
+    };
+    let mut data = image.data.clone();
+    let b = find(&data, b"B.TXT;1");
+    data[b + 6] = b'X';
+    let c = find(&data, b"C.TXT;1");
+    data[c] = 0xff;
+    data.truncate(extent.loc as usize * 2048 + 4096);
+
+    let image = MemFile { data, pos: 0 };
+
+    let fs = ISO9660::new(image.clone()).unwrap();
+    assert!(fs.root.contents().any(|entry| entry.is_err()));
+    assert!(fs.warnings().is_empty());
+
+    let mut options = ISO9660Options::new();
+    options.lenient(true);
+    let fs = ISO9660::with_options(image, &options).unwrap();
+    let entries: Vec<DirectoryEntry<MemFile>> =
+        fs.root.contents().map(|entry| entry.unwrap()).collect();
+    let names: Vec<&str> = entries.iter().map(|entry| entry.identifier()).collect();
+    assert_eq!(names, [".", "..", "A.TXT", "B.TXT", "D.DAT"]);
+    match &entries[3] {
+        DirectoryEntry::File(file) => assert_eq!(file.version, 1),
+        _ => panic!("Not a file"),
+    }
+    let offset = |pos: usize| (pos - 33 - dir) as u32;
+    assert_eq!(
+        fs.take_warnings(),
+        [
+            Warning {
+                lba: root_lba as u64,
+                offset: offset(b),
+                kind: WarningKind::InvalidVersion,
+            },
+            Warning {
+                lba: root_lba as u64,
+                offset: offset(c),
+                kind: WarningKind::InvalidRecord,
+            },
+        ]
+    );
+
+    // Data past the end of the image reads as zeros
+    let data = match &entries[4] {
+        DirectoryEntry::File(file) => read_to_end(file.read()),
+        _ => panic!("Not a file"),
+    };
+    assert_eq!(data.len(), big.len());
+    assert_eq!(data[..4096], big[..4096]);
+    assert!(data[4096..].iter().all(|&byte| byte == 0));
+    let warnings = fs.warnings();
+    assert_eq!(
+        warnings[0],
+        Warning {
+            lba: extent.loc as u64 + 2,
+            offset: 0,
+            kind: WarningKind::ShortRead,
+        }
+    );
+    assert!(warnings
+        .iter()
+        .all(|warning| warning.kind == WarningKind::ShortRead));
+}
+
+#[test]
+fn test_parse_error_location() {
+    use iso9660::{ISOBuilder, ISOError, ParseError, ParseErrorKind, Structure};
+
+    let mut builder = ISOBuilder::new();
+    let root = builder.root();
+    root.add_file("a.txt", b"first".as_slice());
+    root.add_file("b.txt", b"second".as_slice());
+    let mut image = MemFile::default();
+    builder.write(&mut image).unwrap();
+
+    let fs = ISO9660::new(image.clone()).unwrap();
+    let root_lba = DirectoryEntry::Directory(fs.root.clone())
+        .header()
+        .extent_loc as u64;
+    let dir = root_lba as usize * 2048;
+    let find = |data: &[u8], name: &[u8]| {
+        data[dir..dir + 2048]
+            .windows(name.len())
+            .position(|window| window == name)
+            .unwrap()
+    };
+    let open = |data: Vec<u8>| ISO9660::new(MemFile { data, pos: 0 });
+
+    let mut data = image.data.clone();
+    data[16 * 2048 + 1] = b'X';
+    match open(data) {
+        Err(ISOError::Parse(err)) => assert_eq!(
+            err,
+            ParseError {
+                structure: Structure::VolumeDescriptor,
+                lba: 16,
+                offset: 0,
+                kind: ParseErrorKind::Nom(nom::error::ErrorKind::Tag),
+            }
+        ),
+        _ => panic!("Expected a parse error"),
+    }
+
+    let mut data = image.data.clone();
+    let a = find(&data, b"A.TXT;1") - 33;
+    data[dir + a] = 10;
+    let fs = open(data).unwrap();
+    let err = fs.root.contents().find_map(Result::err).unwrap();
+    match err {
+        ISOError::Parse(ref err) => assert_eq!(
+            *err,
+            ParseError {
+                structure: Structure::DirectoryRecord,
+                lba: root_lba,
+                offset: a as u32,
+                kind: ParseErrorKind::Invalid("Invalid directory record length"),
+            }
+        ),
+        _ => panic!("Expected a parse error"),
+    }
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Invalid directory record at block {}, offset {}: \
+             Invalid directory record length",
+            root_lba, a
+        )
+    );
+
+    let mut data = image.data.clone();
+    let b = find(&data, b"B.TXT;1");
+    data[dir + b + 6] = b'X';
+    let fs = open(data).unwrap();
+    match fs.open("b.txt") {
+        Err(ISOError::Parse(ParseError {
+            structure: Structure::DirectoryRecord,
+            lba,
+            offset,
+            kind: ParseErrorKind::ParseInt(_),
+        })) => assert_eq!((lba, offset), (root_lba, b as u32 - 33)),
+        _ => panic!("Expected a parse error"),
+    }
+
+    // Located at the set terminator
+    let terminator = (16..).find(|lba| image.data[lba * 2048] == 255).unwrap();
+    let mut data = image.data.clone();
+    data[16 * 2048] = 0;
+    match open(data) {
+        Err(ISOError::Parse(err)) => assert_eq!(
+            err,
+            ParseError {
+                structure: Structure::VolumeDescriptor,
+                lba: terminator as u64,
+                offset: 0,
+                kind: ParseErrorKind::Invalid("No primary volume descriptor"),
+            }
+        ),
+        _ => panic!("Expected a parse error"),
+    }
+}
+
+#[test]
+fn test_path_table_errors() {
+    use iso9660::{ISOError, ParseError, ParseErrorKind, PathTableType, Structure};
+
+    let image = std::fs::read("test.iso").unwrap();
+    let open = |data: Vec<u8>| ISO9660::new(MemFile { data, pos: 0 }).unwrap();
+    let m_loc = u32::from_be_bytes([
+        image[16 * 2048 + 148],
+        image[16 * 2048 + 149],
+        image[16 * 2048 + 150],
+        image[16 * 2048 + 151],
+    ]);
+
+    // No type L path table, located at the primary volume descriptor
+    let mut data = image.clone();
+    data[16 * 2048 + 140..][..4].fill(0);
+    match open(data).read_path_table(PathTableType::L) {
+        Err(ISOError::Parse(err)) => assert_eq!(
+            err,
+            ParseError {
+                structure: Structure::VolumeDescriptor,
+                lba: 16,
+                offset: 0,
+                kind: ParseErrorKind::Invalid("Path table not recorded"),
+            }
+        ),
+        _ => panic!("Expected a parse error"),
+    }
+
+    // The extent of "A", the second record, moved in the type M copy
+    let fs = open(image.clone());
+    let records = fs.path_table().unwrap().records().to_vec();
+    let l_loc = fs.primary_volume_descriptor().path_table_loc;
+    let mut data = image.clone();
+    let extent = m_loc as usize * 2048 + 10 + 2;
+    data[extent..][..4].copy_from_slice(&(records[1].extent_loc + 1).to_be_bytes());
+    match open(data).path_table() {
+        Err(ISOError::Parse(err)) => assert_eq!(
+            err,
+            ParseError {
+                structure: Structure::PathTable,
+                lba: m_loc as u64,
+                offset: 0,
+                kind: ParseErrorKind::PathTableCopiesDiffer {
+                    l_lba: l_loc as u64,
+                    index: 1,
+                    field: "extent location",
+                    expected: records[1].extent_loc as u64,
+                    found: records[1].extent_loc as u64 + 1,
+                },
+            }
+        ),
+        _ => panic!("Expected a parse error"),
+    }
+}
+
+#[test]
+fn test_verify() {
+    use iso9660::{Finding, FindingKind, Hierarchy, ISOBuilder, PathTableType, Structure};
+
+    let mut builder = ISOBuilder::new();
+    let root = builder.root();
+    root.add_file("a.txt", b"first".as_slice());
+    root.add_file("b.txt", b"second".as_slice());
+    root.add_directory("sub")
+        .add_file("c.txt", b"third".as_slice());
+    let mut image = MemFile::default();
+    builder.write(&mut image).unwrap();
+
+    let fs = ISO9660::new(image.clone()).unwrap();
+    assert_eq!(fs.verify().unwrap(), []);
+
+    let extent_loc = |fs: &ISO9660<MemFile>, path: &str| -> u32 {
+        fs.open(path).unwrap().unwrap().header().extent_loc
+    };
+    let root_lba = DirectoryEntry::Directory(fs.root.clone())
+        .header()
+        .extent_loc as usize;
+    let sub_lba = extent_loc(&fs, "sub") as usize;
+    let volume_space_size = fs.volume_info().volume_space_size;
+    let path_table_loc = fs.primary_volume_descriptor().path_table_loc as usize;
+    assert_eq!(
+        fs.read_path_table(PathTableType::L)
+            .unwrap()
+            .records()
+            .len(),
+        2
+    );
+
+    // Offset of the record with `identifier` in the directory at `lba`
+    let find = |data: &[u8], lba: usize, identifier: &[u8]| {
+        data[lba * 2048..lba * 2048 + 2048]
+            .windows(identifier.len())
+            .position(|window| window == identifier)
+            .unwrap()
+            - 33
+    };
+    let set_both32 = |data: &mut [u8], pos: usize, value: u32| {
+        data[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+        data[pos + 4..pos + 8].copy_from_slice(&value.to_be_bytes());
+    };
+    let mut data = image.data.clone();
+
+    // A big endian extent location differing from the little endian one
+    let a = find(&data, root_lba, b"A.TXT;1");
+    data[root_lba * 2048 + a + 6..][..4].copy_from_slice(&12345u32.to_be_bytes());
+    // A lowercase identifier, which is also out of order
+    let b = find(&data, root_lba, b"B.TXT;1");
+    data[root_lba * 2048 + b + 33] = b'b';
+    // "." pointing elsewhere
+    set_both32(&mut data, sub_lba * 2048 + 2, 3);
+    // An extent past the end of the volume
+    let c = find(&data, sub_lba, b"C.TXT;1");
+    set_both32(&mut data, sub_lba * 2048 + c + 2, volume_space_size);
+    // Path table record of "SUB" pointing elsewhere
+    data[path_table_loc * 2048 + 12..][..4].copy_from_slice(&7u32.to_le_bytes());
+    // A big endian volume set size differing from the little endian one
+    data[16 * 2048 + 122..][..2].copy_from_slice(&5u16.to_be_bytes());
+
+    let fs = ISO9660::new(MemFile { data, pos: 0 }).unwrap();
+    let findings = fs.verify().unwrap();
+    let record = |lba: usize, offset: usize, path: &str, kind: FindingKind| Finding {
+        structure: Structure::DirectoryRecord,
+        lba: lba as u64,
+        offset: offset as u32,
+        hierarchy: Some(Hierarchy::Primary),
+        path: Some(path.to_string()),
+        kind,
+    };
+    let path_table = |kind: FindingKind| Finding {
+        structure: Structure::PathTable,
+        lba: path_table_loc as u64,
+        offset: 0,
+        hierarchy: Some(Hierarchy::Primary),
+        path: None,
+        kind,
+    };
+    let expected = [
+        record(
+            root_lba,
+            a,
+            "/A.TXT;1",
+            FindingKind::BothEndianMismatch {
+                field: "extent location",
+                little: extent_loc(&fs, "a.txt"),
+                big: 12345,
+            },
+        ),
+        record(root_lba, b, "/b.TXT;1", FindingKind::InvalidIdentifier),
+        record(
+            root_lba,
+            find(&image.data, root_lba, b"SUB"),
+            "/SUB",
+            FindingKind::Unsorted {
+                previous: "b.TXT;1".to_string(),
+            },
+        ),
+        record(
+            sub_lba,
+            0,
+            "/SUB",
+            FindingKind::InvalidDot {
+                expected: sub_lba as u32,
+                found: 3,
+            },
+        ),
+        record(
+            sub_lba,
+            c,
+            "/SUB/C.TXT;1",
+            FindingKind::ExtentOutsideVolume {
+                loc: volume_space_size,
+                blocks: 1,
+                volume_space_size,
+            },
+        ),
+        path_table(FindingKind::PathTableMismatch { index: 1 }),
+        Finding {
+            structure: Structure::VolumeDescriptor,
+            lba: 16,
+            offset: 0,
+            hierarchy: None,
+            path: None,
+            kind: FindingKind::BothEndianMismatch {
+                field: "volume set size",
+                little: 1,
+                big: 5,
+            },
+        },
+        record(
+            root_lba,
+            find(&image.data, root_lba, b"SUB"),
+            "/SUB",
+            FindingKind::MissingFromPathTable,
+        ),
+    ];
+    for finding in &expected {
+        assert!(
+            findings.contains(finding),
+            "{:?} in {:#?}",
+            finding,
+            findings
+        );
+    }
+    assert!(findings.contains(&Finding {
+        lba: fs.primary_volume_descriptor().path_table_loc_be as u64,
+        ..path_table(FindingKind::PathTableCopiesDiffer { index: 1 })
+    }));
+}
+
+#[cfg(feature = "joliet")]
+#[test]
+fn test_verify_joliet() {
+    use iso9660::{FindingKind, Hierarchy, ISOBuilder, JolietNames};
+
+    let mut builder = ISOBuilder::new();
+    builder.joliet(Some(JolietNames::Standard));
+    let root = builder.root();
+    root.add_file("First File.txt", b"first".as_slice());
+    root.add_file("Second File.txt", b"second".as_slice());
+    let mut image = MemFile::default();
+    builder.write(&mut image).unwrap();
+
+    let fs = ISO9660::new(image.clone()).unwrap();
+    assert_eq!(fs.verify().unwrap(), []);
+
+    // Make the first file of the primary hierarchy overlap the second
+    let root_lba = DirectoryEntry::Directory(fs.primary_root())
+        .header()
+        .extent_loc as usize;
+    let dir = &image.data[root_lba * 2048..root_lba * 2048 + 2048];
+    let first = dir
+        .windows(7)
+        .position(|window| window == b"FIRST_F")
+        .unwrap()
+        - 33;
+    let mut data = image.data.clone();
+    let pos = root_lba * 2048 + first + 10;
+    data[pos..pos + 4].copy_from_slice(&3000u32.to_le_bytes());
+    data[pos + 4..pos + 8].copy_from_slice(&3000u32.to_be_bytes());
+
+    let fs = ISO9660::new(MemFile { data, pos: 0 }).unwrap();
+    let findings = fs.verify().unwrap();
+    let kinds: Vec<(Option<Hierarchy>, Option<&str>, &FindingKind)> = findings
+        .iter()
+        .map(|finding| (finding.hierarchy, finding.path.as_deref(), &finding.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (
+                Some(Hierarchy::Primary),
+                Some("/FIRST_FI.TXT;1"),
+                &FindingKind::OverlappingExtents {
+                    other: "/First File.txt;1".to_string()
+                }
+            ),
+            (
+                Some(Hierarchy::Primary),
+                Some("/SECOND_F.TXT;1"),
+                &FindingKind::OverlappingExtents {
+                    other: "/FIRST_FI.TXT;1".to_string()
+                }
+            ),
+            (
+                Some(Hierarchy::Joliet),
+                Some("/Second File.txt;1"),
+                &FindingKind::OverlappingExtents {
+                    other: "/FIRST_FI.TXT;1".to_string()
+                }
+            ),
+            (
+                Some(Hierarchy::Primary),
+                Some("/FIRST_FI.TXT;1"),
+                &FindingKind::HierarchiesDiffer
+            ),
+            (
+                Some(Hierarchy::Joliet),
+                Some("/First File.txt;1"),
+                &FindingKind::HierarchiesDiffer
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_both_endian() {
+    use iso9660::{
+        BothEndianMismatch, BothEndianPolicy, ISO9660Options, ISOBuilder, ISOError, ParseError,
+        ParseErrorKind, Structure,
+    };
+
+    let mut builder = ISOBuilder::new();
+    let root = builder.root();
+    root.add_file("a.txt", b"first".as_slice());
+    root.add_file("b.txt", b"second".as_slice());
+    let mut image = MemFile::default();
+    builder.write(&mut image).unwrap();
+
+    let fs = ISO9660::new(image.clone()).unwrap();
+    let root_lba = DirectoryEntry::Directory(fs.root.clone())
+        .header()
+        .extent_loc as usize;
+    let a = image.data[root_lba * 2048..][..2048]
+        .windows(7)
+        .position(|window| window == b"A.TXT;1")
+        .unwrap()
+        - 33;
+
+    // Big endian values differing from the little endian ones: a data
+    // length of 3 for "A.TXT", and a volume set size of 2
+    let mut data = image.data.clone();
+    data[root_lba * 2048 + a + 14..][..4].copy_from_slice(&3u32.to_be_bytes());
+    let mut descriptor_data = data.clone();
+    descriptor_data[16 * 2048 + 122..][..2].copy_from_slice(&2u16.to_be_bytes());
+
+    let open = |data: &[u8], policy: BothEndianPolicy| {
+        ISO9660::with_options(
+            MemFile {
+                data: data.to_vec(),
+                pos: 0,
+            },
+            ISO9660Options::new().both_endian(policy),
+        )
+    };
+    let contents = |fs: &ISO9660<MemFile>| match fs.open("a.txt").unwrap().unwrap() {
+        DirectoryEntry::File(file) => read_to_end(file.read()),
+        _ => panic!("Not a file"),
+    };
+    let mismatch = |structure, lba: usize, offset: usize, field, little, big| BothEndianMismatch {
+        structure,
+        lba: lba as u64,
+        offset: offset as u32,
+        field,
+        little,
+        big,
+    };
+    let descriptor_mismatch = mismatch(Structure::VolumeDescriptor, 16, 0, "volume set size", 1, 2);
+    let record_mismatch = mismatch(Structure::DirectoryRecord, root_lba, a, "data length", 5, 3);
+
+    let fs = open(&descriptor_data, BothEndianPolicy::Little).unwrap();
+    assert_eq!(fs.volume_info().volume_set_size, 1);
+    assert_eq!(fs.both_endian_mismatches(), [descriptor_mismatch]);
+    assert_eq!(contents(&fs), b"first");
+    assert_eq!(contents(&fs), b"first");
+    assert_eq!(
+        fs.both_endian_mismatches(),
+        [descriptor_mismatch, record_mismatch]
+    );
+
+    let fs = open(&descriptor_data, BothEndianPolicy::Big).unwrap();
+    assert_eq!(fs.volume_info().volume_set_size, 2);
+    assert_eq!(contents(&fs), b"fir");
+    assert_eq!(
+        fs.both_endian_mismatches(),
+        [descriptor_mismatch, record_mismatch]
+    );
+
+    let located = |structure, lba: usize, offset: usize, field, expected, found| ParseError {
+        structure,
+        lba: lba as u64,
+        offset: offset as u32,
+        kind: ParseErrorKind::Mismatch {
+            field,
+            expected,
+            found,
+        },
+    };
+    match open(&descriptor_data, BothEndianPolicy::Strict) {
+        Err(ISOError::Parse(err)) => assert_eq!(
+            err,
+            located(Structure::VolumeDescriptor, 16, 0, "volume set size", 1, 2)
+        ),
+        _ => panic!("Expected a parse error"),
+    }
+    let fs = open(&data, BothEndianPolicy::Strict).unwrap();
+    assert_eq!(fs.both_endian_mismatches(), []);
+    match fs.open("a.txt") {
+        Err(ISOError::Parse(err)) => assert_eq!(
+            err,
+            located(Structure::DirectoryRecord, root_lba, a, "data length", 5, 3)
+        ),
+        _ => panic!("Expected a parse error"),
+    }
+}
+
+#[test]
+fn test_both_endian_system_use() {
+    use iso9660::{
+        BothEndianMismatch, BothEndianPolicy, ISO9660Options, ISOError, ParseError, ParseErrorKind,
+        Structure,
+    };
+    use std::convert::TryInto;
+
+    let image = std::fs::read("test_rockridge.iso").unwrap();
+    let fs = ISO9660::new(MemFile {
+        data: image.clone(),
+        pos: 0,
+    })
+    .unwrap();
+    let root_lba = DirectoryEntry::Directory(fs.root.clone())
+        .header()
+        .extent_loc as usize;
+    let root = &image[root_lba * 2048..][..2048];
+    // The record with the "NM" entry of "long_file_name.txt"
+    let mut record = 0;
+    while !root[record..record + root[record] as usize]
+        .windows(18)
+        .any(|window| window == b"long_file_name.txt")
+    {
+        record += root[record] as usize;
+    }
+    let find = |start: usize, signature: &[u8]| {
+        start
+            + root[start..]
+                .windows(2)
+                .position(|window| window == signature)
+                .unwrap()
+    };
+
+    // Big endian values differing from the little endian ones: a user ID
+    // of 1001 for "long_file_name.txt", and a continuation area location
+    // one block further for the root's "."
+    let mut px_data = image.clone();
+    let px = root_lba * 2048 + find(record, b"PX");
+    px_data[px + 24..][..4].copy_from_slice(&1001u32.to_be_bytes());
+    let mut ce_data = image.clone();
+    let ce = root_lba * 2048 + find(34, b"CE");
+    let block = u32::from_le_bytes(image[ce + 4..][..4].try_into().unwrap());
+    ce_data[ce + 8..][..4].copy_from_slice(&(block + 1).to_be_bytes());
+
+    let open = |data: &[u8], policy: BothEndianPolicy| {
+        ISO9660::with_options(
+            MemFile {
+                data: data.to_vec(),
+                pos: 0,
+            },
+            ISO9660Options::new().both_endian(policy),
+        )
+    };
+    let uid = |fs: &ISO9660<MemFile>| {
+        let entry = fs.open("long_file_name.txt").unwrap().unwrap();
+        entry.rock_ridge().unwrap().attributes.as_ref().unwrap().uid
+    };
+    let mismatch = |offset: usize, field, little, big| BothEndianMismatch {
+        structure: Structure::SystemUseEntry,
+        lba: root_lba as u64,
+        offset: offset as u32,
+        field,
+        little,
+        big,
+    };
+
+    let fs = open(&px_data, BothEndianPolicy::Little).unwrap();
+    assert_eq!(fs.both_endian_mismatches(), []);
+    assert_eq!(uid(&fs), 1000);
+    assert_eq!(
+        fs.both_endian_mismatches(),
+        [mismatch(record, "user ID", 1000, 1001)]
+    );
+    let fs = open(&px_data, BothEndianPolicy::Big).unwrap();
+    assert_eq!(uid(&fs), 1001);
+
+    let fs = open(&ce_data, BothEndianPolicy::Little).unwrap();
+    assert_eq!(fs.extensions()[0].identifier, "RRIP_1991A");
+    assert_eq!(
+        fs.both_endian_mismatches(),
+        [mismatch(0, "continuation area location", block, block + 1)]
+    );
+
+    let located = |offset: usize, field, expected, found| ParseError {
+        structure: Structure::SystemUseEntry,
+        lba: root_lba as u64,
+        offset: offset as u32,
+        kind: ParseErrorKind::Mismatch {
+            field,
+            expected,
+            found,
+        },
+    };
+    let fs = open(&px_data, BothEndianPolicy::Strict).unwrap();
+    match fs.open("long_file_name.txt") {
+        Err(ISOError::Parse(err)) => assert_eq!(err, located(record, "user ID", 1000, 1001)),
+        _ => panic!("Expected a parse error"),
+    }
+    match open(&ce_data, BothEndianPolicy::Strict) {
+        Err(ISOError::Parse(err)) => assert_eq!(
+            err,
+            located(
+                0,
+                "continuation area location",
+                block as u64,
+                block as u64 + 1
+            )
+        ),
+        _ => panic!("Expected a parse error"),
+    }
+}
+
+#[test]
+fn test_rock_ridge_attributes() {
+    let fs = ISO9660::new(MyFile(File::open("test_rockridge.iso").unwrap())).unwrap();
+
+    let entry = fs.open("long_file_name.txt").unwrap().unwrap();
+    let rock_ridge = entry.rock_ridge().unwrap();
+    let attributes = rock_ridge.attributes.as_ref().unwrap();
+    assert_eq!(attributes.mode, 0o100644);
+    assert_eq!(attributes.links, 1);
+    assert_eq!((attributes.uid, attributes.gid), (1000, 100));
+    let modify = rock_ridge.timestamps.modify.unwrap();
+    assert_eq!(
+        (modify.year(), modify.hour(), modify.second()),
+        (2021, 8, 10)
+    );
+    assert!(rock_ridge.timestamps.creation.is_none());
+
+    let entry = fs.open("Mixed_Case_Dir").unwrap().unwrap();
+    assert_eq!(
+        entry
+            .rock_ridge()
+            .unwrap()
+            .attributes
+            .as_ref()
+            .unwrap()
+            .mode,
+        0o40755
+    );
+    assert!(fs
+        .open("PLAIN.TXT")
+        .unwrap()
+        .unwrap()
+        .rock_ridge()
+        .is_none());
+}
+
+#[test]
+fn test_susp() {
+    let fs = ISO9660::new(MyFile(File::open("test_rockridge.iso").unwrap())).unwrap();
+
+    // The "ER" entry is recorded in a continuation area of the root's "."
+    let extensions = fs.extensions();
+    assert_eq!(extensions.len(), 1);
+    assert_eq!(extensions[0].identifier, "RRIP_1991A");
+    assert_eq!(extensions[0].version, 1);
+
+    let signatures: Vec<[u8; 2]> = fs
+        .root
+        .system_use_entries()
+        .map(|entry| entry.signature)
+        .collect();
+    assert_eq!(signatures, [*b"SP", *b"RR", *b"PX", *b"ER"]);
+
+    // Entries the crate doesn't understand are still available
+    let name = format!(
+        "{}end.txt",
+        "a_name_long_enough_to_be_continued_in_another_block_".repeat(3)
+    );
+    let entry = fs.open(&name).unwrap().unwrap();
+    let vendor = entry
+        .system_use_entries()
+        .find(|entry| &entry.signature == b"ZZ")
+        .unwrap();
+    assert_eq!(vendor.version, 2);
+    assert_eq!(vendor.data, b"vendor");
+}
+
+#[test]
+fn test_rock_ridge_symlinks() {
+    use iso9660::{ISOError, ParseError, ParseErrorKind, Structure};
+
+    let fs = ISO9660::new(MyFile(File::open("test_rockridge_links.iso").unwrap())).unwrap();
+
+    let target = |path| match fs.open(path).unwrap().unwrap() {
+        DirectoryEntry::Symlink(link) => link.target,
+        _ => panic!("Not a symlink"),
+    };
+    // Components may be split across component records and "SL" entries
+    assert_eq!(target("abs"), "/deep/moved");
+    assert_eq!(target("to_target"), "./target.txt");
+    assert_eq!(target("sub/up"), "../target.txt");
+
+    let open = |path| fs.open_follow_symlinks(path).unwrap().unwrap();
+    assert_eq!(open("to_target").identifier(), "target.txt");
+    assert_eq!(open("dir_link/up").identifier(), "target.txt");
+    assert_eq!(open("sub/../dir_link/./up").identifier(), "target.txt");
+    assert_eq!(open("/abs/inner.txt").identifier(), "inner.txt");
+    assert!(matches!(open("dir_link"), DirectoryEntry::Directory(_)));
+    assert!(fs.open_follow_symlinks("to_target/x").unwrap().is_none());
+    // Located at the link followed last, one of the two links of the loop
+    match fs.open_follow_symlinks("loop_a") {
+        Err(ISOError::Parse(ParseError {
+            structure: Structure::DirectoryRecord,
+            lba,
+            offset,
+            kind: ParseErrorKind::Invalid("Too many levels of symbolic links"),
+        })) => {
+            let image = std::fs::read("test_rockridge_links.iso").unwrap();
+            let record = &image[lba as usize * 2048 + offset as usize..];
+            assert!(record[..record[0] as usize]
+                .windows(5)
+                .any(|window| window == b"loop_"));
+        }
+        _ => panic!("Expected a parse error"),
+    }
+}
+
+#[test]
+fn test_rock_ridge_relocated_directories() {
+    let fs = ISO9660::new(MyFile(File::open("test_rockridge_links.iso").unwrap())).unwrap();
+
+    let deep = fs.open("deep").unwrap().unwrap();
+    // "moved" is recorded in "rr_moved", and linked to from "deep"
+    let moved = match fs.open("deep/moved").unwrap().unwrap() {
+        DirectoryEntry::Directory(dir) => dir,
+        _ => panic!("Not a directory"),
+    };
+    assert!(fs.open("deep/moved/inner.txt").unwrap().is_some());
+    assert!(fs.open("rr_moved/moved").unwrap().is_none());
+    assert_eq!(
+        fs.open("rr_moved").unwrap().unwrap().identifier(),
+        "rr_moved"
+    );
+
+    // ".." of the relocated directory is its original parent
+    let parent = moved.find("..").unwrap().unwrap();
+    assert_eq!(parent.header().extent_loc, deep.header().extent_loc);
+}
+
+#[test]
+fn test_rock_ridge_devices_and_links() {
+    let fs = ISO9660::new(MyFile(File::open("test_rockridge_special.iso").unwrap())).unwrap();
+
+    let rock_ridge = |path| {
+        fs.open(path)
+            .unwrap()
+            .unwrap()
+            .rock_ridge()
+            .unwrap()
+            .clone()
+    };
+    let null = rock_ridge("null");
+    assert_eq!(null.attributes.unwrap().mode, 0o20666);
+    let device = null.device.unwrap();
+    assert_eq!((device.major(), device.minor()), (1, 3));
+    // An old-style dev_t recorded in the low word
+    let device = rock_ridge("sda1").device.unwrap();
+    assert_eq!((device.major(), device.minor()), (8, 1));
+
+    let hard_a = rock_ridge("hard_a").attributes.unwrap();
+    let hard_b = rock_ridge("hard_b").attributes.unwrap();
+    assert_eq!(hard_a.links, 2);
+    assert_eq!(hard_a.serial, Some(5));
+    assert_eq!(hard_a.serial, hard_b.serial);
+}
+
+#[test]
+fn test_rock_ridge_sparse_file() {
+    let fs = ISO9660::new(MyFile(File::open("test_rockridge_special.iso").unwrap())).unwrap();
+
+    let file = match fs.open("sparse").unwrap().unwrap() {
+        DirectoryEntry::File(file) => file,
+        _ => panic!("Not a file"),
+    };
+    assert_eq!(file.size(), 2 * 1024 * 1024 + 5);
+
+    let mut reader = file.read();
+    let mut buf = vec![0xff; file.size() as usize + 10];
+    assert_eq!(reader.read(&mut buf).unwrap(), file.size() as usize);
+    assert_eq!(&buf[..5], b"start");
+    assert_eq!(&buf[5 * 2048..5 * 2048 + 6], b"middle");
+    assert_eq!(&buf[1024 * 2048..1024 * 2048 + 5], b"tail!");
+    let zeros = buf[..file.size() as usize]
+        .iter()
+        .filter(|&&byte| byte == 0)
+        .count();
+    assert_eq!(zeros, file.size() as usize - 16);
+
+    reader.seek(SeekFrom::Start(5 * 2048 + 2)).unwrap();
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"ddle");
+}
+
+#[cfg(feature = "zisofs")]
+#[test]
+fn test_zisofs() {
+    let fs = ISO9660::new(MyFile(File::open("test_rockridge_zisofs.iso").unwrap())).unwrap();
+
+    let file = match fs.open("big.txt").unwrap().unwrap() {
+        DirectoryEntry::File(file) => file,
+        _ => panic!("Not a file"),
+    };
+    assert_eq!(file.size(), 102768);
+
+    // Read in chunks that don't line up with the compressed blocks
+    let mut reader = file.read();
+    let mut data = Vec::new();
+    let mut buf = [0; 5000];
+    loop {
+        let count = reader.read(&mut buf).unwrap();
+        if count == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..count]);
+    }
+    let hash = md5::compute(&data);
+    assert_eq!(format!("{:x}", hash), "a463a63bb9333bb204f539739c71c0e4");
+
+    // The second block is all zeros and has no compressed data
+    reader.seek(SeekFrom::Start(40000)).unwrap();
+    let mut buf = [0xff; 32768];
+    assert_eq!(reader.read(&mut buf).unwrap(), 32768);
+    assert!(buf.iter().all(|&byte| byte == 0));
+
+    reader.seek(SeekFrom::End(-15)).unwrap();
+    let mut buf = [0; 20];
+    assert_eq!(reader.read(&mut buf).unwrap(), 15);
+    assert_eq!(&buf[..15], &data[data.len() - 15..]);
+
+    let file = match fs.open("small.txt").unwrap().unwrap() {
+        DirectoryEntry::File(file) => file,
+        _ => panic!("Not a file"),
+    };
+    let mut buf = [0; 20];
+    assert_eq!(file.read().read(&mut buf).unwrap(), 13);
+    assert_eq!(&buf[..13], b"hello zisofs\n");
+}
+
+#[test]
+fn test_el_torito() {
+    use iso9660::{Emulation, Platform};
+
+    let fs = ISO9660::new(MyFile(File::open("test_boot.iso").unwrap())).unwrap();
+    let catalog = fs.boot_catalog().unwrap().unwrap();
+    assert_eq!(catalog.platform, Platform::X86);
+    assert_eq!(catalog.id_string, "TESTCORP");
+
+    let default = &catalog.default_entry;
+    assert!(default.bootable);
+    assert_eq!(default.emulation, Emulation::NoEmulation);
+    assert_eq!(default.load_segment, 0);
+    assert_eq!(default.sector_count, 4);
+    assert_eq!(default.load_rba, 23);
+    assert_eq!(default.size(), 2048);
+
+    assert_eq!(catalog.sections.len(), 2);
+    let floppy = &catalog.sections[0];
+    assert_eq!(floppy.platform, Platform::X86);
+    assert_eq!(floppy.id_string, "FLOPPY");
+    assert_eq!(floppy.entries.len(), 1);
+    assert!(!floppy.entries[0].bootable);
+    assert_eq!(floppy.entries[0].emulation, Emulation::Floppy144);
+    assert_eq!(floppy.entries[0].load_segment, 0x7c0);
+    assert_eq!(floppy.entries[0].size(), 1_474_560);
+    assert_eq!(floppy.entries[0].selection_criteria_type, 1);
+    assert_eq!(floppy.entries[0].selection_criteria.len(), 19 + 30 + 30);
+    assert!(floppy.entries[0].selection_criteria.starts_with(b"vendor"));
+    assert_eq!(&floppy.entries[0].selection_criteria[19..23], b"more");
+    assert_eq!(&floppy.entries[0].selection_criteria[49..53], b"last");
+
+    let efi = &catalog.sections[1];
+    assert_eq!(efi.platform, Platform::Efi);
+    assert_eq!(efi.id_string, "UEFI");
+    assert_eq!(efi.entries.len(), 1);
+    assert_eq!(efi.entries[0].load_rba, 25);
+
+    let mut buf = vec![0; 4096];
+    let mut image = fs.boot_image(default).unwrap();
+    assert_eq!(image.read(&mut buf).unwrap(), 2048);
+    assert_eq!(&buf[..4], b"BIOS");
+    assert_eq!(&buf[4..260], (0..=255).collect::<Vec<u8>>().as_slice());
+
+    let mut image = fs.boot_image(&efi.entries[0]).unwrap();
+    assert_eq!(image.read(&mut buf).unwrap(), 1024);
+    assert!(buf[..1024].chunks(4).all(|x| x == b"EFI!"));
+
+    // An EFI entry loading 0 sectors, whose image is "README.TXT": its
+    // size is the extent length of the file
+    let mut data = std::fs::read("test_boot.iso").unwrap();
+    let readme = match fs.open("readme.txt").unwrap().unwrap() {
+        DirectoryEntry::File(file) => file,
+        _ => panic!("Not a file"),
+    };
+    let catalog_lba = u32::from_le_bytes([
+        data[17 * 2048 + 71],
+        data[17 * 2048 + 72],
+        data[17 * 2048 + 73],
+        data[17 * 2048 + 74],
+    ]) as usize;
+    let catalog = &mut data[catalog_lba * 2048..][..2048];
+    let entry = catalog
+        .chunks_exact_mut(32)
+        .rfind(|entry| entry[8..12] == 25u32.to_le_bytes())
+        .unwrap();
+    entry[6..8].fill(0);
+    entry[8..12].copy_from_slice(&readme.header.extent_loc.to_le_bytes());
+    let fs = ISO9660::new(MemFile { data, pos: 0 }).unwrap();
+    let efi = &fs.boot_catalog().unwrap().unwrap().sections[1].entries[0];
+    assert_eq!(efi.sector_count, 0);
+    assert_eq!(efi.size(), 0);
+    assert_eq!(
+        fs.boot_image_size(efi).unwrap(),
+        readme.header.extent_length as u64
+    );
+    assert_eq!(
+        read_to_end(fs.boot_image(efi).unwrap()),
+        read_to_end(readme.read())
+    );
+
+    // Images without El Torito have no boot catalog
+    let fs = ISO9660::new(MyFile(File::open("test.iso").unwrap())).unwrap();
+    assert!(fs.boot_catalog().unwrap().is_none());
+}
+
+#[test]
+fn test_builder() {
+    use iso9660::{ISOBuilder, ReaderSource};
+
+    let big: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let names: Vec<String> = (0..200).map(|i| format!("file_{:03}.dat", i)).collect();
+    let mut builder = ISOBuilder::new();
+    builder
+        .volume_identifier("RECOVERY")
+        .publisher_identifier("FIRMWARE TEAM");
+    let root = builder.root();
+    root.add_file("readme.txt", b"Hello, world!\n".as_slice());
+    root.add_file("empty", b"".as_slice());
+    let boot = root.add_directory("boot");
+    boot.add_file("kernel.img", ReaderSource::new(&big[..], big.len() as u64));
+    boot.add_directory("grub")
+        .add_file("grub.cfg", b"set timeout=0\n".as_slice());
+    // A directory spanning several blocks
+    let many = root.add_directory("many");
+    for name in &names {
+        many.add_file(name, name.as_bytes());
+    }
+
+    let mut image = MemFile::default();
+    builder.write(&mut image).unwrap();
+    assert_eq!(image.data.len() % 2048, 0);
+
+    let fs = ISO9660::new(image).unwrap();
+    assert_eq!(fs.publisher_identifier(), "FIRMWARE TEAM");
+
+    let names_in = |path: &str| -> Vec<String> {
+        match fs.open(path).unwrap().unwrap() {
+            DirectoryEntry::Directory(dir) => dir
+                .contents()
+                .map(|entry| entry.unwrap().identifier().to_string())
+                .collect(),
+            _ => panic!("Not a directory"),
+        }
+    };
+    assert_eq!(
+        names_in("/"),
+        [".", "..", "BOOT", "EMPTY", "MANY", "README.TXT"]
+    );
+    assert_eq!(names_in("/boot"), [".", "..", "GRUB", "KERNEL.IMG"]);
+    assert_eq!(names_in("/many").len(), 202);
+
+    let read = |path: &str| -> Vec<u8> {
+        match fs.open(path).unwrap().unwrap() {
+            DirectoryEntry::File(file) => read_to_end(file.read()),
+            _ => panic!("Not a file"),
+        }
+    };
+    assert_eq!(read("README.TXT"), b"Hello, world!\n");
+    assert_eq!(read("EMPTY"), b"");
+    assert_eq!(read("BOOT/KERNEL.IMG"), big);
+    assert_eq!(read("BOOT/GRUB/GRUB.CFG"), b"set timeout=0\n");
+    assert_eq!(read("MANY/FILE_123.DAT"), b"file_123.dat");
+
+    // ".." of a subdirectory is the root
+    let root = fs.open("/").unwrap().unwrap();
+    let parent = fs.open("BOOT/GRUB/../..").unwrap().unwrap();
+    assert_eq!(parent.header().extent_loc, root.header().extent_loc);
+
+    let mut builder = ISOBuilder::new();
+    builder.root().add_file("a.txt", b"".as_slice());
+    builder.root().add_file("a.txt", b"".as_slice());
+    assert!(builder.write(&mut MemFile::default()).is_err());
+}
+
+/// Image whose blocks of zeros aren't stored, for images of several GiB
+#[derive(Default)]
+struct SparseFile {
+    blocks: std::collections::BTreeMap<u64, Vec<u8>>,
+    pos: u64,
+    len: u64,
+}
+impl embedded_io::ErrorType for SparseFile {
+    type Error = embedded_io::ErrorKind;
+}
+impl embedded_io::Read for SparseFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let offset = (self.pos % 2048) as usize;
+        let count = buf
+            .len()
+            .min(2048 - offset)
+            .min(self.len.saturating_sub(self.pos) as usize);
+        match self.blocks.get(&(self.pos / 2048)) {
+            Some(block) => buf[..count].copy_from_slice(&block[offset..offset + count]),
+            None => buf[..count].fill(0),
+        }
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+impl embedded_io::Write for SparseFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let offset = (self.pos % 2048) as usize;
+        let count = buf.len().min(2048 - offset);
+        let data = &buf[..count];
+        let index = self.pos / 2048;
+        if data != &[0; 2048][..count] || self.blocks.contains_key(&index) {
+            let block = self.blocks.entry(index).or_insert_with(|| vec![0; 2048]);
+            block[offset..offset + count].copy_from_slice(data);
+        }
+        self.pos += count as u64;
+        self.len = self.len.max(self.pos);
+        Ok(count)
+    }
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+impl embedded_io::Seek for SparseFile {
+    fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Self::Error> {
+        self.pos = match pos {
+            embedded_io::SeekFrom::Start(i) => i as i64,
+            embedded_io::SeekFrom::End(i) => self.len as i64 + i,
+            embedded_io::SeekFrom::Current(i) => self.pos as i64 + i,
+        } as u64;
+        Ok(self.pos)
+    }
+}
+#[cfg(feature = "std")]
+impl_std_io!(SparseFile);
+#[cfg(feature = "std")]
+impl std::io::Write for SparseFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(embedded_io::Write::write(self, buf).unwrap())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Zeros ending with "end"
+struct BigSource {
+    size: u64,
+    pos: u64,
+}
+impl iso9660::FileSource for BigSource {
+    fn size(&self) -> u64 {
+        self.size
+    }
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, iso9660::io::ErrorKind> {
+        let count = buf.len().min((self.size - self.pos) as usize);
+        buf[..count].fill(0);
+        self.pos += count as u64;
+        // The part of "end" read, at the end of `buf`
+        let end = (self.pos + 3).saturating_sub(self.size) as usize;
+        let tail = end.min(count);
+        buf[count - tail..count].copy_from_slice(&b"end"[end - tail..end]);
+        Ok(count)
+    }
+}
+
+#[test]
+fn test_builder_level3() {
+    use iso9660::{Extent, ISOBuilder, ISOError, InterchangeLevel};
+
+    // An extent of 4 GiB - 2048 bytes, and a last one of 4096 bytes
+    let extent = u32::MAX as u64 / 2048 * 2048;
+    let size = extent + 4096;
+    let build = |level| {
+        let mut builder = ISOBuilder::new();
+        builder.interchange_level(level);
+        let root = builder.root();
+        root.add_file("before.txt", b"before".as_slice());
+        root.add_file("big.bin", BigSource { size, pos: 0 });
+        root.add_file("small.txt", b"small".as_slice());
+        let mut image = SparseFile::default();
+        builder.write(&mut image).map(|()| image)
+    };
+
+    match build(InterchangeLevel::Level2) {
+        Err(ISOError::FileTooLarge(too_large)) => assert_eq!(too_large, size),
+        _ => panic!("Expected an error"),
+    }
+
+    let fs = ISO9660::new(build(InterchangeLevel::Level3).unwrap()).unwrap();
+    let names: Vec<String> = fs
+        .root
+        .contents()
+        .skip(2)
+        .map(|entry| entry.unwrap().identifier().to_string())
+        .collect();
+    assert_eq!(names, ["BEFORE.TXT", "BIG.BIN", "SMALL.TXT"]);
+
+    let DirectoryEntry::File(file) = fs.open("big.bin").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    let loc = file.header.extent_loc;
+    let blocks = (extent / 2048) as u32;
+    assert_eq!(
+        file.extents(),
+        [
+            Extent {
+                loc,
+                length: extent as u32
+            },
+            Extent {
+                loc: loc + blocks,
+                length: 4096
+            },
+        ]
+    );
+    assert_eq!(file.size(), size);
+    let mut reader = file.read();
+    reader.seek(SeekFrom::Start(size - 5)).unwrap();
+    assert_eq!(read_to_end(reader), b"\0\0end");
+
+    let DirectoryEntry::File(file) = fs.open("small.txt").unwrap().unwrap() else {
+        panic!("Not a file");
+    };
+    assert_eq!(read_to_end(file.read()), b"small");
+}
+
+#[test]
+fn test_builder_names() {
+    use iso9660::{ISOBuilder, InterchangeLevel};
+
+    let names = |level: InterchangeLevel| -> Vec<String> {
+        let mut builder = ISOBuilder::new();
+        builder.interchange_level(level);
+        let root = builder.root();
+        root.add_file("a.txt", b"".as_slice());
+        root.add_file("A.TXT", b"".as_slice());
+        root.add_file("long file name one.text", b"".as_slice());
+        root.add_file("long file name two.text", b"".as_slice());
+        root.add_file("Makefile", b"".as_slice());
+        root.add_file("archive.tar.gz", b"".as_slice());
+        root.add_directory("a directory.d");
+
+        let mut image = MemFile::default();
+        builder.write(&mut image).unwrap();
+        let fs = ISO9660::new(image).unwrap();
+        let names = fs
+            .root
+            .contents()
+            .skip(2)
+            .map(|entry| entry.unwrap().identifier().to_string())
+            .collect();
+        names
+    };
+
+    assert_eq!(
+        names(InterchangeLevel::Level1),
+        [
+            "A.TXT",
+            "A00.TXT",
+            "ARCHIVE_.GZ",
+            "A_DIRECT",
+            "LONG_F00.TEX",
+            "LONG_FIL.TEX",
+            "MAKEFILE"
+        ]
+    );
+    assert_eq!(
+        names(InterchangeLevel::Level2),
+        [
+            "A.TXT",
+            "A00.TXT",
+            "ARCHIVE_TAR.GZ",
+            "A_DIRECTORY_D",
+            "LONG_FILE_NAME_ONE.TEXT",
+            "LONG_FILE_NAME_TWO.TEXT",
+            "MAKEFILE"
+        ]
+    );
+}
+
+#[cfg(feature = "joliet")]
+#[test]
+fn test_builder_joliet() {
+    use iso9660::{ISOBuilder, JolietNames};
+
+    let long = "a very long file name that goes past the sixty four characters Joliet allows.txt";
+    let build = |names: JolietNames| -> ISO9660<MemFile> {
+        let mut builder = ISOBuilder::new();
+        builder.volume_identifier("JOLIET").joliet(Some(names));
+        let root = builder.root();
+        root.add_file("Read Me.txt", b"Hello".as_slice());
+        root.add_file("Grüße: ☃?.txt", b"Unicode".as_slice());
+        root.add_file(long, b"Long".as_slice());
+        root.add_file(&long.replace("allows", "permits"), b"Long 2".as_slice());
+        root.add_directory("Sub Directory.d")
+            .add_file("nested file", b"Nested".as_slice());
+
+        let mut image = MemFile::default();
+        builder.write(&mut image).unwrap();
+        ISO9660::new(image).unwrap()
+    };
+
+    let fs = build(JolietNames::Standard);
+    let names: Vec<String> = fs
+        .root
+        .contents()
+        .skip(2)
+        .map(|entry| entry.unwrap().identifier().to_string())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "Grüße_ ☃_.txt",
+            "Read Me.txt",
+            "Sub Directory.d",
+            "a very long file name that goes past the sixty four charac00.txt",
+            "a very long file name that goes past the sixty four characte.txt",
+        ]
+    );
+    assert!(names.iter().all(|name| name.chars().count() <= 64));
+
+    let read = |fs: &ISO9660<MemFile>, path: &str| -> Vec<u8> {
+        match fs.open(path).unwrap().unwrap() {
+            DirectoryEntry::File(file) => read_to_end(file.read()),
+            _ => panic!("Not a file"),
+        }
+    };
+    assert_eq!(read(&fs, "Read Me.txt"), b"Hello");
+    assert_eq!(read(&fs, "Grüße_ ☃_.txt"), b"Unicode");
+    assert_eq!(read(&fs, "Sub Directory.d/nested file"), b"Nested");
+
+    let fs = build(JolietNames::Relaxed);
+    assert_eq!(read(&fs, long), b"Long");
+}